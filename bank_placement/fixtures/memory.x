@@ -0,0 +1,23 @@
+/* the regions generated by bus_behavior/build.rs, without its sections */
+MEMORY {
+    BOOT2 : ORIGIN = 0x10000000, LENGTH = 0x100
+    FLASH : ORIGIN = 0x10000100, LENGTH = 2048K - 0x100
+    /* sram0, sram1, direct */
+    RAM : ORIGIN = 0x21000000, LENGTH = 128K
+    /* sram2, direct */
+    SRAM2 : ORIGIN = 0x21020000, LENGTH = 64K
+    /* sram3, direct */
+    SRAM3 : ORIGIN = 0x21030000, LENGTH = 64K
+    /* sram4, direct */
+    SRAM4 : ORIGIN = 0x20040000, LENGTH = 4K
+    /* sram5, direct */
+    SRAM5 : ORIGIN = 0x20041000, LENGTH = 4K
+}
+
+REGION_ALIAS("CORE0_CODE", SRAM2);
+REGION_ALIAS("CORE0_DATA", SRAM2);
+REGION_ALIAS("CORE0_STACK", RAM);
+REGION_ALIAS("CORE1_CODE", SRAM3);
+REGION_ALIAS("CORE1_DATA", SRAM3);
+REGION_ALIAS("CORE1_STACK", SRAM5);
+_stack_start = __core0_stack_top;
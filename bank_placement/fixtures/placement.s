@@ -0,0 +1,95 @@
+@ Fixture of the bank placement tests, the memory partitioning of `lockstep` with the ISR of
+@ core1 of one experiment placed in the bank of core0 by mistake:
+@   llvm-mc -triple=thumbv6m-none-eabi -filetype=obj placement.s -o placement.o
+@   rust-lld -flavor gnu -N -e 0 -L . -T link.ld placement.o -o placement.elf
+
+    .syntax unified
+    .thumb
+
+
+@ core0: vector table, task and handler in SRAM2
+
+    .section .sram2_code, "awx", %progbits
+    .globl _ZN12bus_behavior8lockstep18CORE0_VECTOR_TABLE17h0123456789abcdefE
+    .type _ZN12bus_behavior8lockstep18CORE0_VECTOR_TABLE17h0123456789abcdefE, %object
+    .p2align 2
+_ZN12bus_behavior8lockstep18CORE0_VECTOR_TABLE17h0123456789abcdefE:
+    .space 192
+    .size _ZN12bus_behavior8lockstep18CORE0_VECTOR_TABLE17h0123456789abcdefE, 192
+
+    .section .sram2_code, "awx", %progbits
+    .globl _ZN12bus_behavior8lockstep10CORE0_TASK17h0123456789abcdefE
+    .type _ZN12bus_behavior8lockstep10CORE0_TASK17h0123456789abcdefE, %object
+    .p2align 2
+_ZN12bus_behavior8lockstep10CORE0_TASK17h0123456789abcdefE:
+    .space 8
+    .size _ZN12bus_behavior8lockstep10CORE0_TASK17h0123456789abcdefE, 8
+
+    .section .sram2_code, "awx", %progbits
+    .globl _ZN12bus_behavior8lockstep15core0_timer_irq17h0123456789abcdefE
+    .type _ZN12bus_behavior8lockstep15core0_timer_irq17h0123456789abcdefE, %function
+    .p2align 1
+    .thumb_func
+_ZN12bus_behavior8lockstep15core0_timer_irq17h0123456789abcdefE:
+    bx lr
+    .size _ZN12bus_behavior8lockstep15core0_timer_irq17h0123456789abcdefE, . - _ZN12bus_behavior8lockstep15core0_timer_irq17h0123456789abcdefE
+
+
+@ the mistake: an ISR of core1 in SRAM2
+
+    .section .sram2_code, "awx", %progbits
+    .globl _ZN11experiments14exp7_core1_isr17h0123456789abcdefE
+    .type _ZN11experiments14exp7_core1_isr17h0123456789abcdefE, %function
+    .p2align 1
+    .thumb_func
+_ZN11experiments14exp7_core1_isr17h0123456789abcdefE:
+    nop
+    bx lr
+    .size _ZN11experiments14exp7_core1_isr17h0123456789abcdefE, . - _ZN11experiments14exp7_core1_isr17h0123456789abcdefE
+
+
+@ core1: vector table, handler and flag in SRAM3, the stacks are sections of link.ld
+
+    .section .sram3_code, "awx", %progbits
+    .globl _ZN12bus_behavior8lockstep18CORE1_VECTOR_TABLE17h0123456789abcdefE
+    .type _ZN12bus_behavior8lockstep18CORE1_VECTOR_TABLE17h0123456789abcdefE, %object
+    .p2align 2
+_ZN12bus_behavior8lockstep18CORE1_VECTOR_TABLE17h0123456789abcdefE:
+    .space 192
+    .size _ZN12bus_behavior8lockstep18CORE1_VECTOR_TABLE17h0123456789abcdefE, 192
+
+    .section .sram3_code, "awx", %progbits
+    .globl _ZN12bus_behavior8lockstep15core1_timer_irq17h0123456789abcdefE
+    .type _ZN12bus_behavior8lockstep15core1_timer_irq17h0123456789abcdefE, %function
+    .p2align 1
+    .thumb_func
+_ZN12bus_behavior8lockstep15core1_timer_irq17h0123456789abcdefE:
+    bx lr
+    .size _ZN12bus_behavior8lockstep15core1_timer_irq17h0123456789abcdefE, . - _ZN12bus_behavior8lockstep15core1_timer_irq17h0123456789abcdefE
+
+    .section .sram3_code, "awx", %progbits
+    .globl _ZN12bus_behavior8lockstep12CORE1_PARKED17h0123456789abcdefE
+    .type _ZN12bus_behavior8lockstep12CORE1_PARKED17h0123456789abcdefE, %object
+    .p2align 2
+_ZN12bus_behavior8lockstep12CORE1_PARKED17h0123456789abcdefE:
+    .space 4
+    .size _ZN12bus_behavior8lockstep12CORE1_PARKED17h0123456789abcdefE, 4
+
+
+@ shared: the data of the experiments in SRAM4, the flags of the handlers in RAM
+
+    .section .sram4_code, "awx", %progbits
+    .globl _ZN12bus_behavior10experiment11SHARED_DATA17h0123456789abcdefE
+    .type _ZN12bus_behavior10experiment11SHARED_DATA17h0123456789abcdefE, %object
+    .p2align 2
+_ZN12bus_behavior10experiment11SHARED_DATA17h0123456789abcdefE:
+    .space 16
+    .size _ZN12bus_behavior10experiment11SHARED_DATA17h0123456789abcdefE, 16
+
+    .section .bss, "aw", %nobits
+    .globl _ZN12bus_behavior8lockstep4DONE17h0123456789abcdefE
+    .type _ZN12bus_behavior8lockstep4DONE17h0123456789abcdefE, %object
+    .p2align 2
+_ZN12bus_behavior8lockstep4DONE17h0123456789abcdefE:
+    .space 8
+    .size _ZN12bus_behavior8lockstep4DONE17h0123456789abcdefE, 8
@@ -0,0 +1,126 @@
+//! Physical SRAM banks behind the addresses of the rp2040.
+
+use core::fmt;
+use std::collections::BTreeSet;
+
+/// One of the six SRAM banks, each behind its own port of the crossbar
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Bank {
+    Sram0,
+    Sram1,
+    Sram2,
+    Sram3,
+    Sram4,
+    Sram5,
+}
+
+impl Bank {
+    pub const ALL: [Bank; 6] = [
+        Bank::Sram0,
+        Bank::Sram1,
+        Bank::Sram2,
+        Bank::Sram3,
+        Bank::Sram4,
+        Bank::Sram5,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Bank::Sram0 => "sram0",
+            Bank::Sram1 => "sram1",
+            Bank::Sram2 => "sram2",
+            Bank::Sram3 => "sram3",
+            Bank::Sram4 => "sram4",
+            Bank::Sram5 => "sram5",
+        }
+    }
+
+    /// Bank holding the byte at `address`, `None` outside of the SRAM
+    pub fn from_address(address: u32) -> Option<Bank> {
+        const STRIPED: [Bank; 4] = [Bank::Sram0, Bank::Sram1, Bank::Sram2, Bank::Sram3];
+        match address {
+            // striped, consecutive words are in consecutive banks
+            0x2000_0000..=0x2003_ffff => Some(STRIPED[(address as usize >> 2) & 3]),
+            0x2004_0000..=0x2004_0fff => Some(Bank::Sram4),
+            0x2004_1000..=0x2004_1fff => Some(Bank::Sram5),
+            // non-striped alias, 64 KiB per bank
+            0x2100_0000..=0x2103_ffff => Some(STRIPED[(address as usize - 0x2100_0000) >> 16]),
+            _ => None,
+        }
+    }
+
+    /// Banks holding the `size` bytes from `address`, in bank order. A range of the striped
+    /// mapping longer than a word is spread over several banks.
+    pub fn of_range(address: u32, size: u32) -> Vec<Bank> {
+        const STRIPED: [Bank; 4] = [Bank::Sram0, Bank::Sram1, Bank::Sram2, Bank::Sram3];
+        let start = address as u64;
+        let end = start + size.max(1) as u64;
+        let mut banks = BTreeSet::new();
+
+        // part of the range in `first..=last`, if any
+        let overlap = |first: u64, last: u64| {
+            let (lo, hi) = (start.max(first), (end - 1).min(last));
+            (lo <= hi).then_some((lo, hi))
+        };
+        if let Some((lo, hi)) = overlap(0x2000_0000, 0x2003_ffff) {
+            // 4 consecutive words cover the 4 banks
+            let words = ((hi >> 2) - (lo >> 2) + 1).min(4);
+            banks.extend((0..words).map(|i| STRIPED[(((lo >> 2) + i) & 3) as usize]));
+        }
+        if overlap(0x2004_0000, 0x2004_0fff).is_some() {
+            banks.insert(Bank::Sram4);
+        }
+        if overlap(0x2004_1000, 0x2004_1fff).is_some() {
+            banks.insert(Bank::Sram5);
+        }
+        if let Some((lo, hi)) = overlap(0x2100_0000, 0x2103_ffff) {
+            banks.extend(
+                STRIPED[((lo - 0x2100_0000) >> 16) as usize..=((hi - 0x2100_0000) >> 16) as usize]
+                    .iter()
+                    .copied(),
+            );
+        }
+        banks.into_iter().collect()
+    }
+}
+
+impl fmt::Display for Bank {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Bank;
+
+    #[test]
+    fn test_from_address() {
+        assert_eq!(Bank::from_address(0x2000_0000), Some(Bank::Sram0));
+        assert_eq!(Bank::from_address(0x2000_0004), Some(Bank::Sram1));
+        assert_eq!(Bank::from_address(0x2000_000f), Some(Bank::Sram3));
+        assert_eq!(Bank::from_address(0x2004_0ffc), Some(Bank::Sram4));
+        assert_eq!(Bank::from_address(0x2004_1000), Some(Bank::Sram5));
+        assert_eq!(Bank::from_address(0x2102_0000), Some(Bank::Sram2));
+        assert_eq!(Bank::from_address(0x2104_0000), None);
+        assert_eq!(Bank::from_address(0x1000_0000), None);
+    }
+
+    #[test]
+    fn test_of_range() {
+        // a word, then a word straddling two banks of the striped mapping
+        assert_eq!(Bank::of_range(0x2000_0008, 4), [Bank::Sram2]);
+        assert_eq!(Bank::of_range(0x2000_000a, 4), [Bank::Sram2, Bank::Sram3]);
+        assert_eq!(Bank::of_range(0x2000_0000, 16), &Bank::ALL[..4]);
+        assert_eq!(Bank::of_range(0x2003_fff8, 0x1010), Bank::ALL[2..].to_vec());
+        // non-striped alias
+        assert_eq!(
+            Bank::of_range(0x2100_0000, 0x2_0000),
+            [Bank::Sram0, Bank::Sram1]
+        );
+        assert_eq!(Bank::of_range(0x2102_fffc, 8), [Bank::Sram2, Bank::Sram3]);
+        // empty symbols still have an address
+        assert_eq!(Bank::of_range(0x2004_1000, 0), [Bank::Sram5]);
+        assert_eq!(Bank::of_range(0x1000_0000, 0x100), []);
+    }
+}
@@ -0,0 +1,93 @@
+//! Symbols of a firmware ELF placed in the SRAM.
+
+use core::fmt;
+
+use object::{Object, ObjectSection, ObjectSymbol, SymbolKind};
+
+use crate::bank::Bank;
+
+/// A function or a static
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Symbol {
+    /// Demangled, without the hash
+    pub name: String,
+    pub section: String,
+    /// Without the Thumb bit of the functions
+    pub address: u32,
+    pub size: u32,
+    pub code: bool,
+}
+
+#[derive(Debug)]
+pub enum ElfError {
+    Parse(object::Error),
+}
+
+impl fmt::Display for ElfError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ElfError::Parse(e) => write!(f, "invalid ELF: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for ElfError {}
+
+/// Symbols of the ELF
+pub struct Firmware {
+    /// The functions and statics in the SRAM, in address order
+    pub symbols: Vec<Symbol>,
+    /// Initial stack pointer of core0 defined by cortex-m-rt, if any
+    pub stack_start: Option<u32>,
+    /// Address and size of the `.core0_stack` and `.core1_stack` sections of the `memory.x` of
+    /// `bus_behavior`, with their guard
+    pub stacks: [Option<(u32, u32)>; 2],
+}
+
+impl Firmware {
+    pub fn parse(data: &[u8]) -> Result<Self, ElfError> {
+        let file = object::File::parse(data).map_err(ElfError::Parse)?;
+        let mut symbols = Vec::new();
+        let mut stack_start = None;
+        for symbol in file.symbols() {
+            let Ok(name) = symbol.name() else {
+                continue;
+            };
+            if name == "_stack_start" {
+                stack_start = Some(symbol.address() as u32);
+            }
+            let code = match symbol.kind() {
+                SymbolKind::Text => true,
+                SymbolKind::Data => false,
+                _ => continue,
+            };
+            let address = symbol.address() as u32 & if code { !1 } else { !0 };
+            if Bank::from_address(address).is_none() {
+                continue;
+            }
+            let section = symbol
+                .section_index()
+                .and_then(|i| file.section_by_index(i).ok())
+                .and_then(|s| s.name().ok().map(str::to_string))
+                .unwrap_or_default();
+            symbols.push(Symbol {
+                name: format!("{:#}", rustc_demangle::demangle(name)),
+                section,
+                address,
+                size: symbol.size() as u32,
+                code,
+            });
+        }
+        symbols.sort_by(|a, b| (a.address, &a.name).cmp(&(b.address, &b.name)));
+
+        let stacks = [0, 1].map(|core| {
+            file.section_by_name(&format!(".core{core}_stack"))
+                .map(|s| (s.address() as u32, s.size() as u32))
+        });
+        Ok(Firmware {
+            symbols,
+            stack_start,
+            stacks,
+        })
+    }
+}
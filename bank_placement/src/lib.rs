@@ -0,0 +1,12 @@
+//! Attribution of the symbols of a dual-core firmware to the SRAM banks, to check the memory
+//! partitioning of the `bus_behavior` experiments on the ELF actually built.
+
+mod bank;
+mod elf;
+mod memory;
+mod placement;
+
+pub use bank::Bank;
+pub use elf::{ElfError, Firmware, Symbol};
+pub use memory::{parse_memory, MemoryError, Region};
+pub use placement::{place, Conflict, Declaration, Entry, Kind, Owner, Placement, PlacementError};
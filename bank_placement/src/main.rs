@@ -0,0 +1,108 @@
+use std::{env, fs, process::ExitCode};
+
+use bank_placement::{parse_memory, place, Declaration, Firmware};
+
+fn read(path: &str) -> Option<Vec<u8>> {
+    match fs::read(path) {
+        Ok(data) => Some(data),
+        Err(e) => {
+            eprintln!("could not read {path}: {e}");
+            None
+        }
+    }
+}
+
+fn main() -> ExitCode {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let [elf_path, memory_path, cores_path] = &args[..] else {
+        eprintln!("usage: bank_placement <firmware.elf> <memory.x> <cores.toml>");
+        return ExitCode::FAILURE;
+    };
+    let (Some(elf), Some(memory), Some(cores)) =
+        (read(elf_path), read(memory_path), read(cores_path))
+    else {
+        return ExitCode::FAILURE;
+    };
+
+    let firmware = match Firmware::parse(&elf) {
+        Ok(firmware) => firmware,
+        Err(e) => {
+            eprintln!("{elf_path}: {e}");
+            return ExitCode::FAILURE;
+        }
+    };
+    let regions = match parse_memory(&String::from_utf8_lossy(&memory)) {
+        Ok(regions) => regions,
+        Err(e) => {
+            eprintln!("{memory_path}: {e}");
+            return ExitCode::FAILURE;
+        }
+    };
+    let declaration = match Declaration::from_toml(&String::from_utf8_lossy(&cores)) {
+        Ok(declaration) => declaration,
+        Err(e) => {
+            eprintln!("{cores_path}: {e}");
+            return ExitCode::FAILURE;
+        }
+    };
+    let placement = match place(&firmware, &regions, &declaration) {
+        Ok(placement) => placement,
+        Err(e) => {
+            eprintln!("{e}");
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let banks = |banks: &[bank_placement::Bank]| {
+        if banks.is_empty() {
+            "-".to_string()
+        } else {
+            banks
+                .iter()
+                .map(|b| b.name())
+                .collect::<Vec<_>>()
+                .join(", ")
+        }
+    };
+
+    println!("{:<8} {:<10} {:>8}  banks", "region", "origin", "length");
+    for region in &regions {
+        println!(
+            "{:<8} {:#010x} {:>8}  {}",
+            region.name,
+            region.origin,
+            region.length,
+            banks(&region.banks())
+        );
+    }
+
+    println!(
+        "\n{:<6} {:<5} {:<10} {:>6} {:<8} {:<18} symbol",
+        "owner", "kind", "address", "size", "region", "banks"
+    );
+    for entry in &placement.entries {
+        println!(
+            "{:<6} {:<5} {:#010x} {:>6} {:<8} {:<18} {}",
+            entry.owner.map_or("-", |o| o.name()),
+            entry.kind.name(),
+            entry.address,
+            entry.size,
+            entry.region.as_deref().unwrap_or("-"),
+            banks(&entry.banks),
+            entry.name
+        );
+    }
+
+    for pattern in &placement.unmatched {
+        println!("\nwarning: `{pattern}` matches no symbol in the SRAM");
+    }
+    if placement.conflicts.is_empty() {
+        println!("\nno bank is shared by the two cores");
+        return ExitCode::SUCCESS;
+    }
+    println!();
+    for conflict in &placement.conflicts {
+        println!("warning: {conflict}");
+    }
+    ExitCode::FAILURE
+}
@@ -0,0 +1,274 @@
+//! Regions of the `MEMORY` command of a `memory.x` linker script.
+//!
+//! Only the `MEMORY` command is read, the rest of the script is ignored. The origins and lengths
+//! are sums and differences of numbers (with an optional `K` or `M` suffix) and of
+//! `ORIGIN(<region>)` / `LENGTH(<region>)` of the regions defined before:
+//!
+//! ```text
+//! MEMORY {
+//!     FLASH : ORIGIN = 0x10000100, LENGTH = 2048K - 0x100
+//!     SRAM5 : ORIGIN = ORIGIN(SRAM4) + LENGTH(SRAM4), LENGTH = 4k
+//! }
+//! ```
+
+use core::fmt;
+
+use crate::bank::Bank;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Region {
+    pub name: String,
+    pub origin: u32,
+    pub length: u32,
+}
+
+impl Region {
+    pub fn contains(&self, address: u32) -> bool {
+        address >= self.origin && ((address - self.origin) as u64) < self.length as u64
+    }
+
+    /// SRAM banks of the region, empty for the flash
+    pub fn banks(&self) -> Vec<Bank> {
+        if self.length == 0 {
+            return Vec::new();
+        }
+        Bank::of_range(self.origin, self.length)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MemoryError {
+    /// The script has no `MEMORY { .. }` command
+    NoMemory,
+    /// A line of the `MEMORY` command is not `<name> : ORIGIN = <expr>, LENGTH = <expr>`
+    InvalidRegion(String),
+    InvalidExpression {
+        region: String,
+        expression: String,
+    },
+    UnknownRegion {
+        region: String,
+        reference: String,
+    },
+}
+
+impl fmt::Display for MemoryError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MemoryError::NoMemory => write!(f, "no `MEMORY` command"),
+            MemoryError::InvalidRegion(line) => write!(
+                f,
+                "`{line}` is not a region `<name> : ORIGIN = <origin>, LENGTH = <length>`"
+            ),
+            MemoryError::InvalidExpression { region, expression } => {
+                write!(f, "region `{region}`: invalid expression `{expression}`")
+            }
+            MemoryError::UnknownRegion { region, reference } => write!(
+                f,
+                "region `{region}` refers to `{reference}`, which is not defined before it"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for MemoryError {}
+
+/// Removes the `/* */` and `//` comments
+fn strip_comments(src: &str) -> String {
+    let mut out = String::with_capacity(src.len());
+    let mut rest = src;
+    loop {
+        let block = rest.find("/*");
+        let line = rest.find("//");
+        match (block, line) {
+            (Some(b), l) if l.is_none_or(|l| b < l) => {
+                out.push_str(&rest[..b]);
+                out.push(' ');
+                rest = rest[b + 2..]
+                    .find("*/")
+                    .map_or("", |e| &rest[b + 2 + e + 2..]);
+            }
+            (_, Some(l)) => {
+                out.push_str(&rest[..l]);
+                rest = rest[l..].find('\n').map_or("", |e| &rest[l + e..]);
+            }
+            _ => {
+                out.push_str(rest);
+                return out;
+            }
+        }
+    }
+}
+
+fn number(s: &str) -> Option<u32> {
+    let (digits, multiplier) = match s.as_bytes().last()? {
+        b'k' | b'K' => (&s[..s.len() - 1], 1024),
+        b'm' | b'M' => (&s[..s.len() - 1], 1024 * 1024),
+        _ => (s, 1),
+    };
+    let value = match digits
+        .strip_prefix("0x")
+        .or_else(|| digits.strip_prefix("0X"))
+    {
+        Some(hex) => u32::from_str_radix(hex, 16).ok()?,
+        None => digits.parse().ok()?,
+    };
+    value.checked_mul(multiplier)
+}
+
+fn evaluate(region: &str, expression: &str, regions: &[Region]) -> Result<u32, MemoryError> {
+    let invalid = || MemoryError::InvalidExpression {
+        region: region.to_string(),
+        expression: expression.to_string(),
+    };
+    let mut value: i64 = 0;
+    let mut sign = 1;
+    let mut expect_term = true;
+    let mut rest = expression.trim();
+    while !rest.is_empty() {
+        if !expect_term {
+            sign = match rest.as_bytes()[0] {
+                b'+' => 1,
+                b'-' => -1,
+                _ => return Err(invalid()),
+            };
+            rest = rest[1..].trim_start();
+            expect_term = true;
+            continue;
+        }
+        let end = rest.find(['+', '-']).unwrap_or(rest.len());
+        // the parenthesis of `ORIGIN(..)` may not contain an operator
+        let term = rest[..end].trim();
+        let term_value =
+            if let Some(reference) = term.strip_suffix(')').and_then(|t| t.split_once('(')) {
+                let (function, name) = (reference.0.trim(), reference.1.trim());
+                let referenced = regions.iter().find(|r| r.name == name).ok_or_else(|| {
+                    MemoryError::UnknownRegion {
+                        region: region.to_string(),
+                        reference: name.to_string(),
+                    }
+                })?;
+                match function {
+                    "ORIGIN" | "org" | "o" => referenced.origin,
+                    "LENGTH" | "len" | "l" => referenced.length,
+                    _ => return Err(invalid()),
+                }
+            } else {
+                number(term).ok_or_else(invalid)?
+            };
+        value += sign * term_value as i64;
+        rest = rest[end..].trim_start();
+        expect_term = false;
+    }
+    if expect_term {
+        return Err(invalid());
+    }
+    u32::try_from(value).map_err(|_| invalid())
+}
+
+/// Regions of the `MEMORY` command of `src`, in their order of definition
+pub fn parse_memory(src: &str) -> Result<Vec<Region>, MemoryError> {
+    let src = strip_comments(src);
+    let start = src
+        .match_indices("MEMORY")
+        .map(|(i, _)| &src[i + "MEMORY".len()..])
+        .find(|rest| rest.trim_start().starts_with('{'))
+        .ok_or(MemoryError::NoMemory)?;
+    let body = start.trim_start()[1..]
+        .split('}')
+        .next()
+        .unwrap_or_default();
+
+    let mut regions: Vec<Region> = Vec::new();
+    for line in body.lines().map(str::trim).filter(|l| !l.is_empty()) {
+        let invalid = || MemoryError::InvalidRegion(line.to_string());
+        let (name, attributes) = line.split_once(':').ok_or_else(invalid)?;
+        // `RAM (rwx) : ...`
+        let name = name.split('(').next().unwrap_or_default().trim();
+        let mut origin = None;
+        let mut length = None;
+        for assignment in attributes.split(',') {
+            let (key, expression) = assignment.split_once('=').ok_or_else(invalid)?;
+            let value = evaluate(name, expression, &regions)?;
+            match key.trim() {
+                "ORIGIN" | "org" | "o" => origin = Some(value),
+                "LENGTH" | "len" | "l" => length = Some(value),
+                _ => return Err(invalid()),
+            }
+        }
+        let (Some(origin), Some(length)) = (origin, length) else {
+            return Err(invalid());
+        };
+        regions.push(Region {
+            name: name.to_string(),
+            origin,
+            length,
+        });
+    }
+    Ok(regions)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_memory, MemoryError, Region};
+    use crate::bank::Bank;
+
+    #[test]
+    fn test_generated() {
+        // the regions that bus_behavior/build.rs generates, with a comment per region
+        let regions = parse_memory(include_str!("../fixtures/memory.x")).unwrap();
+        let summary: Vec<_> = regions
+            .iter()
+            .map(|r| (r.name.as_str(), r.origin, r.length))
+            .collect();
+        assert_eq!(
+            summary,
+            [
+                ("BOOT2", 0x1000_0000, 0x100),
+                ("FLASH", 0x1000_0100, 2048 * 1024 - 0x100),
+                ("RAM", 0x2100_0000, 128 * 1024),
+                ("SRAM2", 0x2102_0000, 64 * 1024),
+                ("SRAM3", 0x2103_0000, 64 * 1024),
+                ("SRAM4", 0x2004_0000, 4096),
+                ("SRAM5", 0x2004_1000, 4096),
+            ]
+        );
+        assert_eq!(regions[1].banks(), []);
+        assert_eq!(regions[2].banks(), [Bank::Sram0, Bank::Sram1]);
+    }
+
+    #[test]
+    fn test_references() {
+        let src = "MEMORY {
+            RAM (rwx) : ORIGIN = 0x20000000, LENGTH = 256K
+            SRAM4 : ORIGIN = ORIGIN(RAM) + LENGTH(RAM), LENGTH = 4k
+        }";
+        assert_eq!(
+            parse_memory(src).unwrap()[1],
+            Region {
+                name: "SRAM4".into(),
+                origin: 0x2004_0000,
+                length: 4096
+            }
+        );
+    }
+
+    #[test]
+    fn test_errors() {
+        assert_eq!(parse_memory("SECTIONS { }"), Err(MemoryError::NoMemory));
+        assert_eq!(
+            parse_memory("MEMORY { RAM : ORIGIN = ORIGIN(SRAM), LENGTH = 4k }"),
+            Err(MemoryError::UnknownRegion {
+                region: "RAM".into(),
+                reference: "SRAM".into()
+            })
+        );
+        assert_eq!(
+            parse_memory("MEMORY { RAM : ORIGIN = 0x20000000 + , LENGTH = 4k }"),
+            Err(MemoryError::InvalidExpression {
+                region: "RAM".into(),
+                expression: " 0x20000000 + ".into()
+            })
+        );
+    }
+}
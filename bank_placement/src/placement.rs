@@ -0,0 +1,461 @@
+//! Attribution of the symbols to the cores and to the SRAM banks.
+//!
+//! Which core uses a symbol can't be read from the ELF, it is declared in a `.toml` file with
+//! patterns of demangled names, where `*` matches any characters:
+//!
+//! ```toml
+//! [core0]
+//! symbols = ["*CORE0_*", "*::core0_*"]
+//! # bytes of the stack of cortex-m-rt, which ends at `_stack_start`, for a firmware without the
+//! # `.core0_stack` section of `bus_behavior`
+//! stack = 2048
+//!
+//! [core1]
+//! symbols = ["*CORE1_*", "*::core1_*"]
+//!
+//! # accessed by both cores on purpose, never reported
+//! [shared]
+//! symbols = ["*::DONE"]
+//! ```
+//!
+//! The `.core0_stack` and `.core1_stack` sections of the ELF are attributed to their core. Two
+//! symbols of different cores in the same bank are a conflict: the cores contest the bank when
+//! they access them at the same time, as in experiment 7 of `bus_behavior/README.md`.
+
+use core::fmt;
+
+use serde::Deserialize;
+
+use crate::bank::Bank;
+use crate::elf::Firmware;
+use crate::memory::Region;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Owner {
+    Core0,
+    Core1,
+    Shared,
+}
+
+impl Owner {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Owner::Core0 => "core0",
+            Owner::Core1 => "core1",
+            Owner::Shared => "shared",
+        }
+    }
+}
+
+impl fmt::Display for Owner {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Kind {
+    Code,
+    Data,
+    Stack,
+}
+
+impl Kind {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Kind::Code => "code",
+            Kind::Data => "data",
+            Kind::Stack => "stack",
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum PlacementError {
+    Parse(toml::de::Error),
+    /// A symbol matches the patterns of two owners
+    AmbiguousOwner {
+        symbol: String,
+        owners: [Owner; 2],
+    },
+    /// The stack of core0 is declared, but neither the ELF nor `memory.x` tells where it ends
+    NoStackStart,
+}
+
+impl fmt::Display for PlacementError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PlacementError::Parse(e) => write!(f, "invalid declaration of the cores: {e}"),
+            PlacementError::AmbiguousOwner { symbol, owners } => write!(
+                f,
+                "`{symbol}` matches the symbols of both {} and {}",
+                owners[0], owners[1]
+            ),
+            PlacementError::NoStackStart => write!(
+                f,
+                "the stack of core0 is declared, but the ELF has no `_stack_start` and \
+                 `memory.x` no `RAM` region"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for PlacementError {}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawDeclaration {
+    #[serde(default)]
+    core0: RawCore0,
+    #[serde(default)]
+    core1: RawSymbols,
+    #[serde(default)]
+    shared: RawSymbols,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(deny_unknown_fields)]
+struct RawCore0 {
+    #[serde(default)]
+    symbols: Vec<String>,
+    stack: Option<u32>,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(deny_unknown_fields)]
+struct RawSymbols {
+    #[serde(default)]
+    symbols: Vec<String>,
+}
+
+/// Symbols of each core, as patterns
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Declaration {
+    pub patterns: Vec<(Owner, String)>,
+    /// Size of the stack of core0, when the ELF has no `.core0_stack` section
+    pub core0_stack: Option<u32>,
+}
+
+impl Declaration {
+    pub fn from_toml(src: &str) -> Result<Self, PlacementError> {
+        let raw: RawDeclaration = toml::from_str(src).map_err(PlacementError::Parse)?;
+        let patterns = [
+            (Owner::Core0, raw.core0.symbols),
+            (Owner::Core1, raw.core1.symbols),
+            (Owner::Shared, raw.shared.symbols),
+        ]
+        .into_iter()
+        .flat_map(|(owner, patterns)| patterns.into_iter().map(move |p| (owner, p)))
+        .collect();
+        Ok(Declaration {
+            patterns,
+            core0_stack: raw.core0.stack,
+        })
+    }
+}
+
+/// Whether `name` matches `pattern`, where `*` matches any characters
+fn matches(pattern: &str, name: &str) -> bool {
+    let mut parts = pattern.split('*');
+    let first = parts.next().unwrap_or_default();
+    let Some(mut rest) = name.strip_prefix(first) else {
+        return false;
+    };
+    let parts: Vec<&str> = parts.collect();
+    let Some((last, middle)) = parts.split_last() else {
+        // no `*`
+        return rest.is_empty();
+    };
+    for part in middle {
+        match rest.find(part) {
+            Some(i) => rest = &rest[i + part.len()..],
+            None => return false,
+        }
+    }
+    rest.ends_with(last)
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Entry {
+    pub name: String,
+    pub address: u32,
+    pub size: u32,
+    pub kind: Kind,
+    /// `None` for the symbols that are not declared
+    pub owner: Option<Owner>,
+    /// Region of `memory.x` containing the symbol
+    pub region: Option<String>,
+    pub banks: Vec<Bank>,
+}
+
+/// A bank used by both cores
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Conflict {
+    pub bank: Bank,
+    pub core0: Vec<String>,
+    pub core1: Vec<String>,
+}
+
+impl fmt::Display for Conflict {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} is shared by core0 ({}) and core1 ({})",
+            self.bank,
+            self.core0.join(", "),
+            self.core1.join(", ")
+        )
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Placement {
+    /// In address order
+    pub entries: Vec<Entry>,
+    /// In bank order
+    pub conflicts: Vec<Conflict>,
+    /// Patterns of the declaration that match no symbol in the SRAM
+    pub unmatched: Vec<String>,
+}
+
+/// Attributes the symbols of `firmware` in the SRAM to their owner and banks
+pub fn place(
+    firmware: &Firmware,
+    regions: &[Region],
+    declaration: &Declaration,
+) -> Result<Placement, PlacementError> {
+    let region_of = |address: u32| {
+        regions
+            .iter()
+            .find(|r| r.contains(address))
+            .map(|r| r.name.clone())
+    };
+
+    let mut entries = Vec::new();
+    let mut matched = vec![false; declaration.patterns.len()];
+    for symbol in &firmware.symbols {
+        let mut owner: Option<Owner> = None;
+        for (i, (pattern_owner, pattern)) in declaration.patterns.iter().enumerate() {
+            if !matches(pattern, &symbol.name) {
+                continue;
+            }
+            matched[i] = true;
+            match owner {
+                Some(o) if o != *pattern_owner => {
+                    return Err(PlacementError::AmbiguousOwner {
+                        symbol: symbol.name.clone(),
+                        owners: [o, *pattern_owner],
+                    })
+                }
+                _ => owner = Some(*pattern_owner),
+            }
+        }
+        entries.push(Entry {
+            name: symbol.name.clone(),
+            address: symbol.address,
+            size: symbol.size,
+            kind: if symbol.code { Kind::Code } else { Kind::Data },
+            owner,
+            region: region_of(symbol.address),
+            banks: Bank::of_range(symbol.address, symbol.size),
+        });
+    }
+
+    let mut stacks = firmware.stacks;
+    if let (None, Some(size)) = (stacks[0], declaration.core0_stack) {
+        // cortex-m-rt places the stack at the end of `RAM` unless `_stack_start` is defined
+        let end = firmware
+            .stack_start
+            .or_else(|| {
+                regions
+                    .iter()
+                    .find(|r| r.name == "RAM")
+                    .map(|r| r.origin + r.length)
+            })
+            .ok_or(PlacementError::NoStackStart)?;
+        stacks[0] = Some((end - size, size));
+    }
+    for (core, stack) in stacks.into_iter().enumerate() {
+        if let Some((address, size)) = stack {
+            entries.push(Entry {
+                name: format!("core{core} stack"),
+                address,
+                size,
+                kind: Kind::Stack,
+                owner: Some([Owner::Core0, Owner::Core1][core]),
+                region: region_of(address),
+                banks: Bank::of_range(address, size),
+            });
+        }
+    }
+    entries.sort_by_key(|e| e.address);
+
+    let conflicts = Bank::ALL
+        .iter()
+        .filter_map(|&bank| {
+            let names = |owner: Owner| -> Vec<String> {
+                entries
+                    .iter()
+                    .filter(|e| e.owner == Some(owner) && e.banks.contains(&bank))
+                    .map(|e| e.name.clone())
+                    .collect()
+            };
+            let (core0, core1) = (names(Owner::Core0), names(Owner::Core1));
+            (!core0.is_empty() && !core1.is_empty()).then_some(Conflict { bank, core0, core1 })
+        })
+        .collect();
+
+    let unmatched = declaration
+        .patterns
+        .iter()
+        .zip(matched)
+        .filter(|(_, matched)| !matched)
+        .map(|((_, pattern), _)| pattern.clone())
+        .collect();
+
+    Ok(Placement {
+        entries,
+        conflicts,
+        unmatched,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{matches, place, Declaration, Kind, Owner, PlacementError};
+    use crate::bank::Bank;
+    use crate::elf::Firmware;
+    use crate::memory::parse_memory;
+
+    fn fixture(cores: &str) -> Result<super::Placement, PlacementError> {
+        let firmware = Firmware::parse(include_bytes!("../fixtures/placement.elf")).unwrap();
+        let regions = parse_memory(include_str!("../fixtures/memory.x")).unwrap();
+        place(&firmware, &regions, &Declaration::from_toml(cores)?)
+    }
+
+    #[test]
+    fn test_matches() {
+        assert!(matches("*CORE1_*", "bus_behavior::lockstep::CORE1_STACK"));
+        assert!(matches("bus_behavior::*", "bus_behavior::lockstep::DONE"));
+        assert!(matches("*::DONE", "bus_behavior::lockstep::DONE"));
+        assert!(matches("DONE", "DONE"));
+        assert!(!matches("DONE", "bus_behavior::lockstep::DONE"));
+        assert!(!matches("*::DONE", "bus_behavior::lockstep::DONE_FLAG"));
+        assert!(!matches("*core1_*isr", "core1_irq"));
+    }
+
+    #[test]
+    fn test_exp7_mistake() {
+        let placement = fixture(include_str!("../fixtures/cores.toml")).unwrap();
+        assert_eq!(placement.conflicts.len(), 1);
+        let conflict = &placement.conflicts[0];
+        assert_eq!(conflict.bank, Bank::Sram2);
+        assert_eq!(conflict.core1, ["experiments::exp7_core1_isr"]);
+        assert_eq!(conflict.core0.len(), 3);
+        assert!(placement.unmatched.is_empty());
+
+        // the stacks with their guard at the end of their region, core0 in SRAM1 with the direct
+        // mapping of RAM and core1 in SRAM5
+        let stacks: Vec<_> = placement
+            .entries
+            .iter()
+            .filter(|e| e.kind == Kind::Stack)
+            .map(|e| {
+                (
+                    e.name.as_str(),
+                    e.owner,
+                    e.address,
+                    e.size,
+                    e.region.as_deref(),
+                    e.banks.as_slice(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            stacks,
+            [
+                (
+                    "core1 stack",
+                    Some(Owner::Core1),
+                    0x2004_1700,
+                    2304,
+                    Some("SRAM5"),
+                    &[Bank::Sram5][..]
+                ),
+                (
+                    "core0 stack",
+                    Some(Owner::Core0),
+                    0x2101_df00,
+                    8448,
+                    Some("RAM"),
+                    &[Bank::Sram1][..]
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_declared_stack() {
+        // without `.core0_stack`, the stack of cortex-m-rt is below `_stack_start`
+        let firmware = Firmware {
+            symbols: Vec::new(),
+            stack_start: Some(0x2004_2000),
+            stacks: [None, None],
+        };
+        let regions = parse_memory(include_str!("../fixtures/memory.x")).unwrap();
+        let declaration = Declaration::from_toml("[core0]\nstack = 2048").unwrap();
+        let placement = place(&firmware, &regions, &declaration).unwrap();
+        let stack = &placement.entries[0];
+        assert_eq!(
+            (stack.name.as_str(), stack.address, stack.banks.as_slice()),
+            ("core0 stack", 0x2004_1800, &[Bank::Sram5][..])
+        );
+
+        let firmware = Firmware {
+            stack_start: None,
+            ..firmware
+        };
+        let regions = parse_memory("MEMORY { FLASH : ORIGIN = 0x10000000, LENGTH = 2M }").unwrap();
+        assert!(matches!(
+            place(&firmware, &regions, &declaration),
+            Err(PlacementError::NoStackStart)
+        ));
+    }
+
+    #[test]
+    fn test_partitioned() {
+        // without the ISR of core1 in SRAM2, nor the stack of core0 and the shared data
+        let cores = r#"
+            [core0]
+            symbols = ["*CORE0_*", "*core0_*"]
+            [core1]
+            symbols = ["*CORE1_*", "*::lockstep::core1_*", "*::NOT_LINKED"]
+        "#;
+        let placement = fixture(cores).unwrap();
+        assert!(placement.conflicts.is_empty());
+        assert_eq!(placement.unmatched, ["*::NOT_LINKED"]);
+        let isr = placement
+            .entries
+            .iter()
+            .find(|e| e.name == "experiments::exp7_core1_isr")
+            .unwrap();
+        assert_eq!((isr.owner, isr.kind), (None, Kind::Code));
+    }
+
+    #[test]
+    fn test_ambiguous_owner() {
+        let cores = r#"
+            [core0]
+            symbols = ["*::lockstep::*"]
+            [core1]
+            symbols = ["*CORE1_*"]
+        "#;
+        assert!(matches!(
+            fixture(cores),
+            Err(PlacementError::AmbiguousOwner {
+                owners: [Owner::Core0, Owner::Core1],
+                ..
+            })
+        ));
+    }
+}
@@ -1,36 +1,512 @@
-//! This build script copies the `memory.x` file from the crate root into
-//! a directory where the linker can always find it at build time.
-//! For many projects this is optional, as the linker always searches the
-//! project root directory -- wherever `Cargo.toml` is. However, if you
-//! are using a workspace or have a more complicated build setup, this
-//! build script becomes required. Additionally, by requesting that
-//! Cargo re-run the build script whenever `memory.x` is changed,
-//! updating `memory.x` ensures a rebuild of the application with the
-//! new memory settings.
+//! This build script generates `memory.x` from the memory layout of `memory.toml` into a
+//! directory where the linker can always find it at build time, and requests Cargo to re-run it
+//! whenever `memory.toml` is changed.
+//!
+//! The regions of `memory.toml` are made of SRAM banks, the script computes their addresses from
+//! the mapping of the banks and rejects the layouts where two regions overlap, or alias the same
+//! bank through the striped and the direct mappings of SRAM0-3.
+//!
+//! The sections of the regions are loaded from flash: `.ram_code` with `.data` by cortex-m-rt, the
+//! others by `__pre_init` from a table of their addresses (see `src/sram_load.rs`).
+//!
+//! The stack of each core is a section at the end of its region, `RAM` by default, above a guard
+//! of 256 bytes that the MPU makes inaccessible (see `src/stack.rs`). When both stacks are in the
+//! same region, the stack of core1 is below the guard of core0.
 
+use std::collections::BTreeMap;
 use std::env;
-use std::fs::File;
-use std::io::Write;
+use std::fmt::{self, Write as _};
+use std::fs;
 use std::path::PathBuf;
+use std::process;
+
+use serde::Deserialize;
+
+const BANK_NAMES: [&str; 6] = ["sram0", "sram1", "sram2", "sram3", "sram4", "sram5"];
+
+/// Size and alignment of the smallest MPU region
+const STACK_GUARD: u32 = 256;
+const DEFAULT_STACK_SIZE: u32 = 2048;
+
+#[derive(Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+enum Mapping {
+    Striped,
+    #[default]
+    Direct,
+}
+
+impl fmt::Display for Mapping {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Mapping::Striped => "striped",
+            Mapping::Direct => "direct",
+        })
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawLayout {
+    ram: RawBanks,
+    #[serde(default, rename = "region")]
+    regions: Vec<RawRegion>,
+    #[serde(default)]
+    core0: RawCore,
+    #[serde(default)]
+    core1: RawCore,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawBanks {
+    #[serde(default)]
+    mapping: Mapping,
+    banks: Vec<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawRegion {
+    name: String,
+    #[serde(default)]
+    mapping: Mapping,
+    banks: Vec<String>,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(deny_unknown_fields)]
+struct RawCore {
+    code: Option<String>,
+    data: Option<String>,
+    stack: Option<String>,
+    stack_size: Option<u32>,
+}
+
+struct Region {
+    name: String,
+    mapping: Mapping,
+    banks: Vec<usize>,
+    origin: u32,
+    length: u32,
+}
+
+enum LayoutError {
+    Parse(toml::de::Error),
+    InvalidName(String),
+    DuplicateRegion(String),
+    UnknownBank {
+        region: String,
+        bank: String,
+    },
+    NoBank(String),
+    /// The striped mapping of a region doesn't have all of SRAM0-3
+    PartialStriping(String),
+    /// The banks of a region are not consecutive in its mapping
+    NotContiguous {
+        region: String,
+        banks: [usize; 2],
+    },
+    /// Two regions with the same mapping of a bank
+    Overlap {
+        regions: [String; 2],
+        bank: usize,
+    },
+    /// Two regions with different mappings of a bank
+    Alias {
+        regions: [String; 2],
+        bank: usize,
+    },
+    UnknownRegion {
+        core: usize,
+        role: &'static str,
+        region: String,
+    },
+    /// A stack size that is not a non-zero multiple of `STACK_GUARD`
+    InvalidStackSize {
+        core: usize,
+        size: u32,
+    },
+    /// The stacks of a region and their guards don't fit in it
+    StacksTooLarge {
+        region: String,
+        size: u32,
+        length: u32,
+    },
+}
+
+impl fmt::Display for LayoutError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LayoutError::Parse(e) => write!(f, "invalid memory layout: {e}"),
+            LayoutError::InvalidName(name) => write!(
+                f,
+                "`{name}` is not a valid region name, it must be an identifier other than \
+                 BOOT2 and FLASH"
+            ),
+            LayoutError::DuplicateRegion(name) => write!(f, "region `{name}` is defined twice"),
+            LayoutError::UnknownBank { region, bank } => write!(
+                f,
+                "region `{region}` uses unknown bank `{bank}`, the banks are sram0 to sram5"
+            ),
+            LayoutError::NoBank(region) => write!(f, "region `{region}` has no bank"),
+            LayoutError::PartialStriping(region) => write!(
+                f,
+                "region `{region}` is striped, it must start with all of sram0 to sram3"
+            ),
+            LayoutError::NotContiguous { region, banks } => write!(
+                f,
+                "the banks of region `{region}` are not contiguous: {} doesn't follow {}",
+                BANK_NAMES[banks[1]], BANK_NAMES[banks[0]]
+            ),
+            LayoutError::Overlap { regions, bank } => write!(
+                f,
+                "regions `{}` and `{}` overlap, both use {}",
+                regions[0], regions[1], BANK_NAMES[*bank]
+            ),
+            LayoutError::Alias { regions, bank } => write!(
+                f,
+                "regions `{}` and `{}` alias {} through its striped and direct mappings, \
+                 they can't be used at the same time",
+                regions[0], regions[1], BANK_NAMES[*bank]
+            ),
+            LayoutError::UnknownRegion { core, role, region } => {
+                write!(f, "core{core}.{role} is `{region}`, which is not a region")
+            }
+            LayoutError::InvalidStackSize { core, size } => write!(
+                f,
+                "core{core}.stack_size is {size}, it must be a non-zero multiple of \
+                 {STACK_GUARD} bytes"
+            ),
+            LayoutError::StacksTooLarge {
+                region,
+                size,
+                length,
+            } => write!(
+                f,
+                "the stacks in region `{region}` take {size} bytes with their guards, more than \
+                 its {length} bytes"
+            ),
+        }
+    }
+}
+
+/// Address range of `bank` in `mapping`, for SRAM0-3 in the striped mapping the range of the
+/// four banks together
+fn bank_range(bank: usize, mapping: Mapping) -> (u32, u32) {
+    match (bank, mapping) {
+        (0..=3, Mapping::Striped) => (0x2000_0000, 0x2004_0000),
+        (0..=3, Mapping::Direct) => {
+            let origin = 0x2100_0000 + bank as u32 * 0x1_0000;
+            (origin, origin + 0x1_0000)
+        }
+        _ => {
+            let origin = 0x2004_0000 + (bank as u32 - 4) * 0x1000;
+            (origin, origin + 0x1000)
+        }
+    }
+}
+
+fn region(name: &str, mapping: Mapping, banks: &[String]) -> Result<Region, LayoutError> {
+    let valid = name.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+    if !valid || name == "BOOT2" || name == "FLASH" {
+        return Err(LayoutError::InvalidName(name.to_string()));
+    }
+    let banks = banks
+        .iter()
+        .map(|bank| {
+            BANK_NAMES
+                .iter()
+                .position(|b| b == bank)
+                .ok_or_else(|| LayoutError::UnknownBank {
+                    region: name.to_string(),
+                    bank: bank.clone(),
+                })
+        })
+        .collect::<Result<Vec<usize>, _>>()?;
+    let Some(&first) = banks.first() else {
+        return Err(LayoutError::NoBank(name.to_string()));
+    };
+    if mapping == Mapping::Striped && banks.get(..4) != Some(&[0, 1, 2, 3][..]) {
+        return Err(LayoutError::PartialStriping(name.to_string()));
+    }
+
+    let (origin, mut end) = bank_range(first, mapping);
+    for pair in banks.windows(2) {
+        let (start, next_end) = bank_range(pair[1], mapping);
+        // the striped banks share their range
+        if (start, next_end) == bank_range(pair[0], mapping) && pair[1] == pair[0] + 1 {
+            continue;
+        }
+        if start != end {
+            return Err(LayoutError::NotContiguous {
+                region: name.to_string(),
+                banks: [pair[0], pair[1]],
+            });
+        }
+        end = next_end;
+    }
+    Ok(Region {
+        name: name.to_string(),
+        mapping,
+        banks,
+        origin,
+        length: end - origin,
+    })
+}
+
+struct Layout {
+    /// RAM first
+    regions: Vec<Region>,
+    /// Region of the code, data and stack of each core, the stack is in `RAM` unless configured
+    cores: [[Option<String>; 3]; 2],
+    stack_sizes: [u32; 2],
+}
+
+const ROLES: [&str; 3] = ["code", "data", "stack"];
+
+impl Layout {
+    fn from_toml(src: &str) -> Result<Self, LayoutError> {
+        let raw: RawLayout = toml::from_str(src).map_err(LayoutError::Parse)?;
+
+        let mut regions = vec![region("RAM", raw.ram.mapping, &raw.ram.banks)?];
+        for raw_region in &raw.regions {
+            if regions.iter().any(|r| r.name == raw_region.name) {
+                return Err(LayoutError::DuplicateRegion(raw_region.name.clone()));
+            }
+            regions.push(region(
+                &raw_region.name,
+                raw_region.mapping,
+                &raw_region.banks,
+            )?);
+        }
+
+        // region and mapping using each bank
+        let mut users: BTreeMap<usize, (&Region, Mapping)> = BTreeMap::new();
+        for region in &regions {
+            for &bank in &region.banks {
+                if let Some((other, mapping)) = users.insert(bank, (region, region.mapping)) {
+                    let regions = [other.name.clone(), region.name.clone()];
+                    return Err(if mapping == region.mapping {
+                        LayoutError::Overlap { regions, bank }
+                    } else {
+                        LayoutError::Alias { regions, bank }
+                    });
+                }
+            }
+        }
+
+        let mut cores: [[Option<String>; 3]; 2] = Default::default();
+        let mut stack_sizes = [0; 2];
+        for (core, raw_core) in [raw.core0, raw.core1].into_iter().enumerate() {
+            let size = raw_core.stack_size.unwrap_or(DEFAULT_STACK_SIZE);
+            if size == 0 || size % STACK_GUARD != 0 {
+                return Err(LayoutError::InvalidStackSize { core, size });
+            }
+            stack_sizes[core] = size;
+            let stack = raw_core.stack.unwrap_or_else(|| "RAM".to_string());
+            let roles = [raw_core.code, raw_core.data, Some(stack)];
+            for (role, region) in roles.into_iter().enumerate() {
+                if let Some(region) = &region {
+                    if !regions.iter().any(|r| &r.name == region) {
+                        return Err(LayoutError::UnknownRegion {
+                            core,
+                            role: ROLES[role],
+                            region: region.clone(),
+                        });
+                    }
+                }
+                cores[core][role] = region;
+            }
+        }
+
+        let layout = Layout {
+            regions,
+            cores,
+            stack_sizes,
+        };
+        for region in &layout.regions {
+            let size: u32 = layout
+                .stacks()
+                .filter(|(_, name, _)| name == &region.name)
+                .map(|(core, _, _)| stack_sizes[core] + STACK_GUARD)
+                .sum();
+            if size > region.length {
+                return Err(LayoutError::StacksTooLarge {
+                    region: region.name.clone(),
+                    size,
+                    length: region.length,
+                });
+            }
+        }
+        Ok(layout)
+    }
+
+    /// Core, region and offset from the end of the region of the guard of each stack
+    fn stacks(&self) -> impl Iterator<Item = (usize, &str, u32)> {
+        let mut offsets: BTreeMap<&str, u32> = BTreeMap::new();
+        self.cores.iter().enumerate().map(move |(core, roles)| {
+            let region = roles[2].as_deref().unwrap_or("RAM");
+            let offset = offsets.entry(region).or_default();
+            *offset += self.stack_sizes[core] + STACK_GUARD;
+            (core, region, *offset)
+        })
+    }
+
+    /// Roles of the regions used by both cores, for a warning
+    fn shared(&self) -> Vec<String> {
+        let mut shared = Vec::new();
+        for (role0, region0) in ROLES.iter().zip(&self.cores[0]) {
+            for (role1, region1) in ROLES.iter().zip(&self.cores[1]) {
+                if let (Some(region0), Some(region1)) = (region0, region1) {
+                    if region0 == region1 {
+                        shared.push(format!(
+                            "core0.{role0} and core1.{role1} are both in `{region0}`"
+                        ));
+                    }
+                }
+            }
+        }
+        shared
+    }
+
+    fn memory_x(&self) -> String {
+        let mut out = String::new();
+        out.push_str("/* Generated by build.rs from memory.toml */\n\n");
+        out.push_str("MEMORY {\n");
+        out.push_str("    BOOT2 : ORIGIN = 0x10000000, LENGTH = 0x100\n");
+        out.push_str("    FLASH : ORIGIN = 0x10000100, LENGTH = 2048K - 0x100\n");
+        for region in &self.regions {
+            let banks: Vec<&str> = region.banks.iter().map(|&b| BANK_NAMES[b]).collect();
+            writeln!(out, "    /* {}, {} */", banks.join(", "), region.mapping).unwrap();
+            writeln!(
+                out,
+                "    {} : ORIGIN = {:#010x}, LENGTH = {}K",
+                region.name,
+                region.origin,
+                region.length / 1024
+            )
+            .unwrap();
+        }
+        out.push_str("}\n\n");
+
+        for (core, roles) in self.cores.iter().enumerate() {
+            for (role, region) in ROLES.iter().zip(roles) {
+                if let Some(region) = region {
+                    writeln!(
+                        out,
+                        "REGION_ALIAS(\"CORE{core}_{}\", {region});",
+                        role.to_uppercase()
+                    )
+                    .unwrap();
+                }
+            }
+        }
+        // cortex-m-rt places the stack of core0 at the end of RAM unless defined here
+        out.push_str("_stack_start = __core0_stack_top;\n");
+
+        out.push_str(
+            "\nEXTERN(BOOT2_FIRMWARE)\n\n\
+             SECTIONS {\n    \
+                 /* ### Boot loader */\n    \
+                 .boot2 ORIGIN(BOOT2) :\n    \
+                 {\n        \
+                     KEEP(*(.boot2));\n    \
+                 } > BOOT2\n\
+             } INSERT BEFORE .text;\n\n",
+        );
+
+        // right after .data in RAM, the .data initialization of cortex-m-rt copies it
+        out.push_str(
+            "SECTIONS {\n    \
+                 .ram_code : ALIGN(4)\n    \
+                 {\n        \
+                     *(.ram_code .ram_code.*);\n        \
+                     . = ALIGN(4);\n    \
+                 } > RAM AT>FLASH\n\
+             } INSERT AFTER .data;\n\n",
+        );
+
+        // the other regions are copied by `__pre_init` (see src/sram_load.rs), after .uninit so
+        // that the symbols of cortex-m-rt for RAM don't move
+        out.push_str("SECTIONS {\n");
+        let mut table = String::new();
+        for region in self.regions.iter().filter(|r| r.name != "RAM") {
+            let section = format!(".{}_code", region.name.to_lowercase());
+            writeln!(
+                out,
+                "    {section} ORIGIN({0}) : ALIGN(4)\n    \
+                 {{\n        \
+                     *({section} {section}.*);\n        \
+                     . = ALIGN(4);\n    \
+                 }} > {0} AT>FLASH\n",
+                region.name
+            )
+            .unwrap();
+            writeln!(
+                table,
+                "        LONG(ADDR({section}));\n        \
+                 LONG(ADDR({section}) + SIZEOF({section}));\n        \
+                 LONG(LOADADDR({section}));"
+            )
+            .unwrap();
+        }
+        for (core, region, offset) in self.stacks() {
+            writeln!(
+                out,
+                "    /* stack of core{core}, above a guard that `stack::install_guard()` makes \
+                 inaccessible */\n    \
+                 .core{core}_stack ORIGIN({region}) + LENGTH({region}) - {offset} (NOLOAD) :\n    \
+                 {{\n        \
+                     __core{core}_stack_guard = .;\n        \
+                     . += {STACK_GUARD};\n        \
+                     __core{core}_stack_bottom = .;\n        \
+                     . += {};\n        \
+                     __core{core}_stack_top = .;\n    \
+                 }} > {region}\n",
+                self.stack_sizes[core]
+            )
+            .unwrap();
+        }
+        out.push_str(
+            "    /* start, end and load address of each section copied by `__pre_init` */\n    \
+             .sram_load_table : ALIGN(4)\n    \
+             {\n        \
+                 __sram_load_table_start = .;\n",
+        );
+        out.push_str(&table);
+        out.push_str(
+            "        __sram_load_table_end = .;\n    \
+             } > FLASH\n\
+             } INSERT AFTER .uninit;\n",
+        );
+        out
+    }
+}
 
 fn main() {
+    let src = fs::read_to_string("memory.toml").unwrap_or_else(|e| {
+        eprintln!("error: could not read memory.toml: {e}");
+        process::exit(1)
+    });
+    let layout = Layout::from_toml(&src).unwrap_or_else(|e| {
+        eprintln!("error: memory.toml: {e}");
+        process::exit(1)
+    });
+    for shared in layout.shared() {
+        println!("cargo:warning=memory.toml: {shared}, the cores contest the region");
+    }
+
     // Put `memory.x` in our output directory and ensure it's
     // on the linker search path.
     let out = &PathBuf::from(env::var_os("OUT_DIR").unwrap());
-    File::create(out.join("memory.x"))
-        .unwrap()
-        .write_all(include_bytes!("memory.x"))
-        .unwrap();
-    // File::create(out.join("link.ram.x"))
-    //     .unwrap()
-    //     .write_all(include_bytes!("link.ram.x"))
-    //     .unwrap();
+    fs::write(out.join("memory.x"), layout.memory_x()).unwrap();
     println!("cargo:rustc-link-search={}", out.display());
 
     // By default, Cargo will re-run a build script whenever
-    // any file in the project changes. By specifying `memory.x`
+    // any file in the project changes. By specifying `memory.toml`
     // here, we ensure the build script is only re-run when
-    // `memory.x` is changed.
-    println!("cargo:rerun-if-changed=memory.x");
-    // println!("cargo:rerun-if-changed=link.ram.x");
+    // the memory layout is changed.
+    println!("cargo:rerun-if-changed=memory.toml");
 }
@@ -0,0 +1,257 @@
+// DMA as a bus master
+// The conclusion of the README assumes that with core0, core1, DMA read and DMA write all accessing
+// the same bank, a LDR can take up to 5 cycles. This example measures it: a DMA channel hammers
+// SRAM4 while both cores execute 10 LDRs and then 10 STRs to SRAM4 in lockstep (`lockstep::run`,
+// same TIMER_IRQ_0 technique as the previous experiments).
+//
+// The DMA channel is unpaced (TREQ_SEL permanent) and does not increment its addresses, in one of
+// the following modes:
+// - off: no DMA transfer, reference measurement
+// - read: reads SRAM4, writes to SRAM0, so only the DMA read master accesses SRAM4
+// - write: reads SRAM0, writes to SRAM4, so only the DMA write master accesses SRAM4
+// - copy: reads SRAM4 and writes SRAM4, both DMA masters access SRAM4
+//
+// Each mode is measured under the 16 combinations of the BUS_PRIORITY bits (proc0, proc1, dma_r,
+// dma_w). Bus performance counters count sram4_contested and sram4 from the interrupt being forced
+// until both cores are done, this includes the accesses of the DMA masters that were delayed by a core, and in
+// copy mode the DMA read and write masters contesting with each other.
+// Each combination is printed as a `dma_contention` record, `experiment_report` turns the records
+// into the table of the README. All the modes are measured at each system clock of
+// `CLOCK_CONFIGS`.
+//
+// # Assumption/Expected result
+// - with the same priority for all masters, the round robin tie break delays each access by at most
+//   one cycle per other master, so at most 5 cycles per LDR/STR with both DMA masters in copy mode
+// - a core with a lower priority than the DMA waits until the DMA transfer is over
+
+#![no_std]
+#![no_main]
+
+use bus_behavior::{
+    clock_config::{SystemClock, CLOCK_CONFIGS},
+    lockstep, measure_cycles,
+    record::Counts,
+    CycleCounter, PerfCounters, PerfCounts, PerfEvent,
+};
+use cortex_m::asm;
+use defmt::*;
+use defmt_rtt as _;
+
+use embedded_hal::digital::v2::ToggleableOutputPin;
+use hal::pac;
+use panic_probe as _;
+use rp2040_hal as hal;
+
+#[link_section = ".boot2"]
+#[used]
+pub static BOOT2: [u8; 256] = rp2040_boot2::BOOT_LOADER_GENERIC_03H;
+
+/// Word of SRAM4 accessed by both cores, and read by the DMA in read and copy modes
+const SRAM4_WORD: *mut u32 = 0x2004_0000 as *mut u32;
+/// Word of SRAM4 written by the DMA in write and copy modes, same bank as `SRAM4_WORD`
+const SRAM4_DMA_WORD: *mut u32 = 0x2004_0100 as *mut u32;
+
+/// Number of loads (and stores) measured by each core, this must match the count given to
+/// `measure_cycles!` in `measure_sram4()`
+const REPEAT: u32 = 10;
+
+// DMA channel 0 registers
+const DMA_CH0_READ_ADDR: *mut u32 = 0x5000_0000 as *mut u32;
+const DMA_CH0_WRITE_ADDR: *mut u32 = 0x5000_0004 as *mut u32;
+const DMA_CH0_TRANS_COUNT: *mut u32 = 0x5000_0008 as *mut u32;
+const DMA_CH0_CTRL_TRIG: *mut u32 = 0x5000_000c as *mut u32;
+const DMA_CHAN_ABORT: *mut u32 = 0x5000_0444 as *mut u32;
+/// EN, DATA_SIZE = word, no address increment, TREQ_SEL = 0x3f (permanent request, unpaced).
+/// CHAIN_TO is left at 0, the channel itself, which disables chaining.
+const DMA_CTRL: u32 = 1 | (2 << 2) | (0x3f << 15);
+/// Long enough to cover both measurements, short enough for a starved core to finish within
+/// one Systick wraparound
+const DMA_TRANSFERS: u32 = 1_000_000;
+
+/// Source and sink of the DMA transfers that don't involve SRAM4, they live in SRAM0
+static mut DMA_SOURCE: u32 = 77;
+static mut DMA_SINK: u32 = 0;
+
+#[derive(Clone, Copy)]
+enum DmaMode {
+    Off,
+    Read,
+    Write,
+    Copy,
+}
+
+impl DmaMode {
+    fn name(&self) -> &'static str {
+        match self {
+            DmaMode::Off => "off",
+            DmaMode::Read => "read",
+            DmaMode::Write => "write",
+            DmaMode::Copy => "copy",
+        }
+    }
+
+    /// Read and write addresses of the DMA channel
+    fn addresses(&self) -> Option<(*const u32, *mut u32)> {
+        match self {
+            DmaMode::Off => None,
+            DmaMode::Read => Some((SRAM4_WORD, core::ptr::addr_of_mut!(DMA_SINK))),
+            DmaMode::Write => Some((core::ptr::addr_of!(DMA_SOURCE), SRAM4_DMA_WORD)),
+            DmaMode::Copy => Some((SRAM4_WORD, SRAM4_DMA_WORD)),
+        }
+    }
+}
+
+const DMA_MODES: [DmaMode; 4] = [DmaMode::Off, DmaMode::Read, DmaMode::Write, DmaMode::Copy];
+
+#[rp2040_hal::entry]
+#[link_section = ".sram2_code"]
+fn main1() -> ! {
+    // Grab our singleton objects
+    let mut pac = pac::Peripherals::take().unwrap();
+    let mut sio = hal::Sio::new(pac.SIO);
+
+    // configure systic to prepare for measurements
+    let core = pac::CorePeripherals::take().unwrap();
+    let _cycle_counter = CycleCounter::new(core.SYST);
+
+    // configure performance counters, this also takes BUSCTRL out of reset mode
+    let mut perf_counters = PerfCounters::new(
+        pac.BUSCTRL,
+        &mut pac.RESETS,
+        [
+            PerfEvent::Sram4Contested,
+            PerfEvent::Sram4,
+            PerfEvent::Sram0Contested,
+            PerfEvent::Sram5Contested,
+        ],
+    );
+
+    // take DMA out of reset mode
+    pac.RESETS.reset.modify(|_, w| w.dma().clear_bit());
+    while pac.RESETS.reset_done.read().dma().bit_is_clear() {}
+
+    // Set up the watchdog driver - needed by the clock setup code
+    let mut watchdog = hal::Watchdog::new(pac.WATCHDOG);
+
+    // Configure the clocks, they are switched to each of `CLOCK_CONFIGS` below
+    let mut clock = SystemClock::init(
+        pac.XOSC,
+        pac.CLOCKS,
+        pac.PLL_SYS,
+        pac.PLL_USB,
+        &mut pac.RESETS,
+        &mut watchdog,
+    );
+
+    // start the second core
+    lockstep::init(&mut pac.PSM, &mut pac.PPB, &mut sio.fifo);
+
+    // write something to the shared data
+    unsafe { SRAM4_WORD.write_volatile(77) };
+
+    for config in &CLOCK_CONFIGS {
+        // core1 must not execute from flash while the flash divisor changes
+        lockstep::with_core1_parked(|| clock.set(config, &mut pac.RESETS));
+        let sys_mhz = config.sys_mhz;
+
+        for mode in DMA_MODES {
+            for priorities in 0..16u32 {
+                // BUS_PRIORITY has the proc0, proc1, dma_r and dma_w bits at 0, 4, 8 and 12
+                let bus_priority =
+                    (0..4).fold(0, |acc, i| acc | (((priorities >> i) & 1) << (4 * i)));
+                let busctrl = perf_counters.busctrl();
+                busctrl
+                    .bus_priority
+                    .write(|w| unsafe { w.bits(bus_priority) });
+                while busctrl.bus_priority_ack.read().bits() == 0 {}
+
+                let ([(load0, store0), (load1, store1)], counts) =
+                    run_with_dma(&mut perf_counters, mode);
+                let per_access = |cycles: u32| cycles as f32 / REPEAT as f32;
+                // one `dma_contention` record per row of the table, see `bus_behavior::record`
+                println!(
+                    "@dma_contention sys_mhz={=u32} dma={=str} proc0={} proc1={} dma_r={} dma_w={} core0_ldr={} core0_str={} core1_ldr={} core1_str={} {}",
+                    sys_mhz,
+                    mode.name(),
+                    priorities & 1,
+                    (priorities >> 1) & 1,
+                    (priorities >> 2) & 1,
+                    (priorities >> 3) & 1,
+                    per_access(load0),
+                    per_access(store0),
+                    per_access(load1),
+                    per_access(store1),
+                    Counts(&counts)
+                );
+                if counts.saturated() {
+                    println!("performance counters saturated: {}", counts);
+                }
+            }
+        }
+    }
+
+    // config led pin
+    let pins = hal::gpio::Pins::new(
+        pac.IO_BANK0,
+        pac.PADS_BANK0,
+        sio.gpio_bank0,
+        &mut pac.RESETS,
+    );
+    let mut led_pin = pins.gpio25.into_push_pull_output();
+    loop {
+        // sign of life
+        led_pin.toggle().unwrap();
+        asm::delay(12_500_000u32);
+    }
+}
+
+/// Starts the DMA channel in `mode`, then measures the loads and stores of both cores in lockstep.
+/// Returns the (loads, stores) cycles of each core and the bus performance counts.
+#[link_section = ".sram2_code"]
+fn run_with_dma(perf_counters: &mut PerfCounters, mode: DmaMode) -> ([(u32, u32); 2], PerfCounts) {
+    if let Some((read_addr, write_addr)) = mode.addresses() {
+        unsafe {
+            DMA_CH0_READ_ADDR.write_volatile(read_addr as u32);
+            DMA_CH0_WRITE_ADDR.write_volatile(write_addr as u32);
+            DMA_CH0_TRANS_COUNT.write_volatile(DMA_TRANSFERS);
+            DMA_CH0_CTRL_TRIG.write_volatile(DMA_CTRL);
+        }
+    }
+
+    let (results, counts) = lockstep::run_counting(core0_sram4, core1_sram4, perf_counters);
+
+    // stop the channel, ABORT reads back as 1 until the in-flight transfers are done
+    unsafe {
+        DMA_CHAN_ABORT.write_volatile(1);
+        while DMA_CHAN_ABORT.read_volatile() != 0 {}
+    }
+    (results, counts)
+}
+
+/// Measures `REPEAT` loads and then `REPEAT` stores to `SRAM4_WORD`, returns (loads, stores)
+#[inline(always)]
+unsafe fn measure_sram4() -> (u32, u32) {
+    let loads = measure_cycles!(
+        10,
+        "ldr {value}, [{ptr}]", // read from sram4
+        ptr = in(reg) SRAM4_WORD,
+        value = out(reg) _,
+    );
+    let stores = measure_cycles!(
+        10,
+        "str {value}, [{ptr}]", // write to sram4
+        ptr = in(reg) SRAM4_WORD,
+        value = in(reg) 77u32,
+    );
+    (loads, stores)
+}
+
+#[link_section = ".sram2_code"]
+fn core0_sram4() -> (u32, u32) {
+    unsafe { measure_sram4() }
+}
+
+#[link_section = ".sram3_code"]
+fn core1_sram4() -> (u32, u32) {
+    unsafe { measure_sram4() }
+}
@@ -0,0 +1,215 @@
+// ISR timing of both cores on a common timebase
+// The Systick of each core only measures what happens on that core, so the experiments compare
+// the cycles measured by each core and assume that the two cores start in lockstep. This example
+// measures both cores from outside: in the handlers of `lockstep::run`, core0 raises GPIO2 and core1
+// raises GPIO3 through SIO, both execute 10 LDRs and lower their pin. PIO0 timestamps the edges of
+// both pins at the resolution of the system clock (`bus_behavior::edge_capture`), which gives the
+// cycle at which each core starts and ends its sequence, on the same timebase.
+//
+// Two scenarios are measured:
+// - shared: both cores read the same SRAM4 word, as in experiment 5
+// - local: core0 reads from SRAM2 and core1 from SRAM3, as in experiment 1.1
+// Each scenario is printed as an `edge_capture` record.
+//
+// # Assumption/Expected result
+// - both cores raise their pin in the same cycle, the store to SIO is not contested
+// - a pulse lasts 21 cycles, the store raising the pin and 10 LDRs of 2 cycles
+// - shared: core1 wins the first contested access (experiment 1), so the pulse of core0 lasts 22
+//   cycles and its falling edge is one cycle after the one of core1
+// - local: both pulses last 21 cycles and fall in the same cycle
+
+#![no_std]
+#![no_main]
+
+use bus_behavior::{edge_capture::EdgeCapture, lockstep, CycleCounter};
+use cortex_m::asm;
+use defmt::*;
+use defmt_rtt as _;
+
+use embedded_hal::digital::v2::ToggleableOutputPin;
+use hal::pac;
+use panic_probe as _;
+use rp2040_hal as hal;
+
+#[link_section = ".boot2"]
+#[used]
+pub static BOOT2: [u8; 256] = rp2040_boot2::BOOT_LOADER_GENERIC_03H;
+/// External high-speed crystal on the Raspberry Pi Pico board is 12 MHz.
+const XTAL_FREQ_HZ: u32 = 12_000_000u32;
+
+/// Pins pulsed by core0 and core1
+const CORE0_GPIO: u8 = 2;
+const CORE1_GPIO: u8 = 3;
+
+/// SIO base, GPIO_OUT_SET is at offset 0x14 and GPIO_OUT_CLR at offset 0x18
+const SIO_BASE: u32 = 0xd000_0000;
+
+// data read by the cores, in their own bank or shared in SRAM4
+#[link_section = ".sram2_code"]
+static CORE0_DATA: u32 = 77;
+#[link_section = ".sram3_code"]
+static CORE1_DATA: u32 = 77;
+#[link_section = ".sram4_code"]
+static SHARED_DATA: u32 = 77;
+
+struct Scenario {
+    name: &'static str,
+    description: &'static str,
+    /// Executed by the handlers of core0 and core1
+    core0: fn(),
+    core1: fn(),
+}
+
+const SCENARIOS: [Scenario; 2] = [
+    Scenario {
+        name: "shared",
+        description: "both cores read the same sram4 word",
+        core0: core0_shared,
+        core1: core1_shared,
+    },
+    Scenario {
+        name: "local",
+        description: "core0 reads sram2, core1 reads sram3",
+        core0: core0_local,
+        core1: core1_local,
+    },
+];
+
+/// Raises the pin `gpio`, reads `data` 10 times and lowers the pin, the pin is high from the first
+/// store to the second one
+#[inline(always)]
+fn pulse(gpio: u8, data: &u32) {
+    unsafe {
+        core::arch::asm!(
+            "str {mask}, [{sio}, #0x14]", // GPIO_OUT_SET
+            ".rept 10",
+            "ldr {value}, [{data}]",
+            ".endr",
+            "str {mask}, [{sio}, #0x18]", // GPIO_OUT_CLR
+            sio = in(reg) SIO_BASE,
+            mask = in(reg) 1u32 << gpio,
+            data = in(reg) data as *const u32,
+            value = out(reg) _,
+        );
+    }
+}
+
+#[link_section = ".sram2_code"]
+fn core0_shared() {
+    pulse(CORE0_GPIO, &SHARED_DATA);
+}
+
+#[link_section = ".sram3_code"]
+fn core1_shared() {
+    pulse(CORE1_GPIO, &SHARED_DATA);
+}
+
+#[link_section = ".sram2_code"]
+fn core0_local() {
+    pulse(CORE0_GPIO, &CORE0_DATA);
+}
+
+#[link_section = ".sram3_code"]
+fn core1_local() {
+    pulse(CORE1_GPIO, &CORE1_DATA);
+}
+
+/// Rising and falling edge of the pulse captured on the `pin`-th pin, if both were captured
+fn captured_pulse(capture: &EdgeCapture, pin: usize) -> Option<(u32, u32)> {
+    let mut edges = capture.edges(pin);
+    match (edges.next(), edges.next()) {
+        (Some(rise), Some(fall)) if rise.rising && !fall.rising => Some((rise.cycle, fall.cycle)),
+        _ => None,
+    }
+}
+
+#[rp2040_hal::entry]
+#[link_section = ".sram2_code"]
+fn main1() -> ! {
+    // Grab our singleton objects
+    let mut pac = pac::Peripherals::take().unwrap();
+    let mut sio = hal::Sio::new(pac.SIO);
+
+    // configure systic, needed by lockstep
+    let core = pac::CorePeripherals::take().unwrap();
+    let _cycle_counter = CycleCounter::new(core.SYST);
+
+    // Set up the watchdog driver - needed by the clock setup code
+    let mut watchdog = hal::Watchdog::new(pac.WATCHDOG);
+
+    // Configure the clocks
+    let _clocks = hal::clocks::init_clocks_and_plls(
+        XTAL_FREQ_HZ,
+        pac.XOSC,
+        pac.CLOCKS,
+        pac.PLL_SYS,
+        pac.PLL_USB,
+        &mut pac.RESETS,
+        &mut watchdog,
+    )
+    .ok()
+    .unwrap();
+
+    // the pulsed pins are SIO outputs, low until the handlers raise them
+    let pins = hal::gpio::Pins::new(
+        pac.IO_BANK0,
+        pac.PADS_BANK0,
+        sio.gpio_bank0,
+        &mut pac.RESETS,
+    );
+    let _core0_pin = pins.gpio2.into_push_pull_output();
+    let _core1_pin = pins.gpio3.into_push_pull_output();
+    let mut led_pin = pins.gpio25.into_push_pull_output();
+
+    let mut capture = EdgeCapture::new(pac.PIO0, &mut pac.RESETS, &[CORE0_GPIO, CORE1_GPIO]);
+
+    // start the second core
+    lockstep::init(&mut pac.PSM, &mut pac.PPB, &mut sio.fifo);
+
+    for scenario in &SCENARIOS {
+        capture.arm();
+        lockstep::run(scenario.core0, scenario.core1);
+        capture.disarm();
+
+        println!("== {}: {}", scenario.name, scenario.description);
+        let (Some(core0), Some(core1)) = (captured_pulse(&capture, 0), captured_pulse(&capture, 1))
+        else {
+            println!("a pulse was not captured");
+            continue;
+        };
+        for (core, (rise, fall)) in [core0, core1].into_iter().enumerate() {
+            println!(
+                "core{} pulse: cycles {} to {}, {} clock cycles",
+                core,
+                rise,
+                fall,
+                fall - rise
+            );
+        }
+        // positive when core0 is late
+        let rise_skew = core0.0 as i32 - core1.0 as i32;
+        let fall_skew = core0.1 as i32 - core1.1 as i32;
+        println!(
+            "core0 - core1: {} clock cycles at the start, {} at the end",
+            rise_skew, fall_skew
+        );
+        println!(
+            "@edge_capture scenario={=str} core0_rise={=u32} core0_fall={=u32} core0_width={=u32} core1_rise={=u32} core1_fall={=u32} core1_width={=u32} rise_skew={=i32} fall_skew={=i32}",
+            scenario.name,
+            core0.0,
+            core0.1,
+            core0.1 - core0.0,
+            core1.0,
+            core1.1,
+            core1.1 - core1.0,
+            rise_skew,
+            fall_skew
+        );
+    }
+
+    loop {
+        // sign of life
+        led_pin.toggle().unwrap();
+        asm::delay(12_500_000u32);
+    }
+}
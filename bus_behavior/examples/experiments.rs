@@ -0,0 +1,240 @@
+// Lockstep experiments 1 to 7
+// Each experiment is described by an `Experiment` struct literal and executed by
+// `bus_behavior::experiment::run()`, which forces the TIMER_IRQ_0 interrupt on both cores, measures
+// the operations of each core with the Systick and counts the bus events with the performance
+// counters. Adding an experiment means adding an entry to `EXPERIMENTS`.
+//
+// The expected results of each experiment are the ones documented in the README, each run ends
+// with a PASS, FAIL (with the differences) or INCONCLUSIVE (the counts can't be checked) verdict.
+//
+// By default both cores have a high bus priority, core0 executes from SRAM2 with its vector table
+// in SRAM2, core1 executes from SRAM3 with its vector table in SRAM3, and both access the shared
+// data in SRAM4 (see the memory partitioning section of the README).
+//
+// The experiments are repeated at each system clock of `CLOCK_CONFIGS`, from 48 MHz to 300 MHz,
+// and each record gives the frequency it was measured at.
+//
+// At each clock every experiment is measured `TRIALS.count` times, with a random delay and a flush
+// of the XIP cache before each trial. Besides the results of the first trial, the runner prints
+// how many trials passed and the min/max/mean/percentiles of the cycles and of the event counts,
+// so the expected results are backed by a distribution instead of a single sample.
+
+#![no_std]
+#![no_main]
+
+use bus_behavior::clock_config::CLOCK_CONFIGS;
+use bus_behavior::experiment::{self, Bank, BusPriority, CoreSetup, Experiment, Op};
+use bus_behavior::trials::{Perturbation, Trials};
+use bus_behavior::verdict::Expectation;
+use bus_behavior::PerfEvent;
+use defmt_rtt as _;
+use panic_probe as _;
+
+#[link_section = ".boot2"]
+#[used]
+pub static BOOT2: [u8; 256] = rp2040_boot2::BOOT_LOADER_GENERIC_03H;
+
+const TRIALS: Trials = Trials {
+    count: 1000,
+    perturbation: Perturbation {
+        max_delay: 64,
+        flush_xip_cache: true,
+    },
+};
+
+const CORE0: CoreSetup = CoreSetup {
+    ops: &[Op::Ldr],
+    value: 0,
+    data: Bank::Sram4,
+    code: Bank::Sram2,
+    vector_table: Bank::Sram2,
+    padding: 0,
+};
+
+const CORE1: CoreSetup = CoreSetup {
+    code: Bank::Sram3,
+    vector_table: Bank::Sram3,
+    ..CORE0
+};
+
+// events counted by the bus performance counters during the concurrent accesses
+const PERF_EVENTS: [PerfEvent; 4] = [
+    PerfEvent::Sram4Contested,
+    PerfEvent::XipMainContested,
+    PerfEvent::ApbContested,
+    PerfEvent::RomContested,
+];
+
+const EXP1: Experiment = Experiment {
+    name: "exp1",
+    description: "both cores read the same sram4 word at the same time",
+    bus_priority: BusPriority::CORES_HIGH,
+    initial_value: 77,
+    perf_events: PERF_EVENTS,
+    cores: [CORE0, CORE1],
+    // core1 is granted access first, core0 waits one clock cycle
+    expected: Expectation {
+        cycles: [Some(3), Some(2)],
+        values: [Some(77), Some(77)],
+        counts: &[(PerfEvent::Sram4Contested, 1)],
+    },
+};
+
+/// Expectation of `ops` loads on each core when core `winner` is granted the contested access first
+const fn one_contested_access(winner: usize, ops: u32) -> Expectation {
+    let mut cycles = [Some(2 * ops + 1); 2];
+    cycles[winner] = Some(2 * ops);
+    Expectation {
+        cycles,
+        ..EXP1.expected
+    }
+}
+
+const EXPERIMENTS: [Experiment; 9] = [
+    EXP1,
+    Experiment {
+        name: "exp1_1",
+        description: "each core reads local data, core0 in sram2 and core1 in sram3",
+        perf_events: [
+            PerfEvent::Sram2Contested,
+            PerfEvent::Sram3Contested,
+            PerfEvent::XipMainContested,
+            PerfEvent::ApbContested,
+        ],
+        cores: [
+            CoreSetup {
+                data: Bank::Sram2,
+                ..CORE0
+            },
+            CoreSetup {
+                data: Bank::Sram3,
+                ..CORE1
+            },
+        ],
+        expected: Expectation {
+            cycles: [Some(2), Some(2)],
+            values: [Some(77), Some(77)],
+            counts: &[
+                (PerfEvent::Sram2Contested, 0),
+                (PerfEvent::Sram3Contested, 0),
+            ],
+        },
+        ..EXP1
+    },
+    Experiment {
+        name: "exp2",
+        description: "core1 has a higher bus priority than core0",
+        bus_priority: BusPriority {
+            proc0: false,
+            ..BusPriority::CORES_HIGH
+        },
+        ..EXP1
+    },
+    Experiment {
+        name: "exp3",
+        description: "core0 has a higher bus priority than core1",
+        bus_priority: BusPriority {
+            proc1: false,
+            ..BusPriority::CORES_HIGH
+        },
+        expected: one_contested_access(0, 1),
+        ..EXP1
+    },
+    Experiment {
+        name: "exp4",
+        description: "core0 reads while core1 writes 7 to the same sram4 word",
+        cores: [
+            // no padding: the generated handlers only differ by the measured operation, the 3
+            // NOPs of the former exp4.rs compensated for the longer compiled handler of core1
+            CoreSetup {
+                padding: 0,
+                ..CORE0
+            },
+            CoreSetup {
+                ops: &[Op::Str],
+                value: 7,
+                ..CORE1
+            },
+        ],
+        // core1 writes 7 before core0 reads
+        expected: Expectation {
+            values: [Some(7), None],
+            ..EXP1.expected
+        },
+        ..EXP1
+    },
+    Experiment {
+        name: "exp5",
+        description: "100 reads on both cores",
+        cores: [
+            CoreSetup {
+                ops: &[Op::Ldr; 100],
+                ..CORE0
+            },
+            CoreSetup {
+                ops: &[Op::Ldr; 100],
+                ..CORE1
+            },
+        ],
+        // core1 stays one clock cycle ahead after the first contested access
+        expected: one_contested_access(1, 100),
+        ..EXP1
+    },
+    Experiment {
+        name: "exp6",
+        description: "100 reads on both cores, core0 has a higher bus priority than core1",
+        bus_priority: BusPriority {
+            proc1: false,
+            ..BusPriority::CORES_HIGH
+        },
+        cores: [
+            CoreSetup {
+                ops: &[Op::Ldr; 100],
+                ..CORE0
+            },
+            CoreSetup {
+                ops: &[Op::Ldr; 100],
+                ..CORE1
+            },
+        ],
+        expected: one_contested_access(0, 100),
+        ..EXP1
+    },
+    Experiment {
+        name: "exp7",
+        description: "the ISRs of both cores are in sram2",
+        cores: [
+            CORE0,
+            CoreSetup {
+                code: Bank::Sram2,
+                ..CORE1
+            },
+        ],
+        // core1 is one clock cycle ahead from the instruction fetch of the ISRs
+        expected: Expectation {
+            cycles: [Some(2), Some(2)],
+            counts: &[(PerfEvent::Sram4Contested, 0)],
+            ..EXP1.expected
+        },
+        ..EXP1
+    },
+    Experiment {
+        name: "exp7_1",
+        description: "the ISRs of both cores are in sram2, one NOP delays core1",
+        cores: [
+            CORE0,
+            CoreSetup {
+                code: Bank::Sram2,
+                // compensate for core1 winning the arbitration of the first ISR instruction fetch
+                padding: 1,
+                ..CORE1
+            },
+        ],
+        ..EXP1
+    },
+];
+
+#[rp2040_hal::entry]
+fn main() -> ! {
+    experiment::run_trials(&EXPERIMENTS, &CLOCK_CONFIGS, &TRIALS)
+}
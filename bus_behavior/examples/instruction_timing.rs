@@ -0,0 +1,221 @@
+// Extended instruction timing table
+// Measure every Thumb-v6m instruction class used in this workspace on both cores, and print the
+// results as a markdown table that extends Table1 in the README.
+//
+// Each instruction (or short instruction sequence when the instruction can not be executed on its
+// own, like a conditional branch that needs the flags to be set) is repeated 10 times between the
+// two reads of the Systick CVR. The code runs from SRAM2 and the data is in SRAM4, the cores measure
+// one after the other, so no other master competes for these banks while a core is measuring. The
+// unrolled measurements are too large for the 4 KiB of SRAM4.
+//
+// # Assumption/Expected result
+// Cycle counts from the Cortex-M0+ technical reference manual for a system with zero wait-states:
+// - data processing and multiply instructions take 1 cycle (the rp2040 has the single cycle multiplier)
+// - all single loads and stores take 2 cycles regardless of the access size
+// - LDM/STM/PUSH/POP take 1 + N cycles for N registers
+// - taken branches take 2 cycles, untaken branches take 1 cycle, BL takes 3 cycles and BX takes 2 cycles
+// - barriers take 3 cycles
+
+#![no_std]
+#![no_main]
+
+use bus_behavior::{measure_cycles, stack, CycleCounter};
+use cortex_m::asm;
+use defmt::*;
+use defmt_rtt as _;
+
+use embedded_hal::digital::v2::ToggleableOutputPin;
+use hal::{
+    multicore::Multicore,
+    pac::{self},
+    Clock, Sio,
+};
+use panic_probe as _;
+use rp2040_hal as hal;
+
+const XTAL_FREQ_HZ: u32 = 12_000_000u32;
+
+#[link_section = ".boot2"]
+#[used]
+pub static BOOT2: [u8; 256] = rp2040_boot2::BOOT_LOADER_GENERIC_03H;
+
+const CORE1_DONE: u32 = 7;
+
+/// Number of times each instruction is repeated in a measurement, this must match the count given
+/// to `measure_cycles!` in `measure_instructions()`
+const REPEAT: u32 = 10;
+
+/// Instruction (sequence) measured and its expected cycle count per repetition
+const INSTRUCTIONS: [(&str, u32); 20] = [
+    ("`nop`", 1),
+    ("`adds Rd, #imm`", 1),
+    ("`muls Rd, Rm, Rd`", 1),
+    ("`ldr Rd, [Rn]`", 2),
+    ("`ldrb Rd, [Rn]`", 2),
+    ("`ldrh Rd, [Rn]`", 2),
+    ("`str Rd, [Rn]`", 2),
+    ("`strb Rd, [Rn]`", 2),
+    ("`strh Rd, [Rn]`", 2),
+    ("`ldm Rn, {r0, r1}`", 3),
+    ("`stm Rn!, {r1, r2, r3, r4}`", 5),
+    ("`push {r0, r1, r2, r3}` + `pop {r0, r1, r2, r3}`", 10),
+    ("`b` (taken)", 2),
+    ("`cmp` + `beq` (taken)", 3),
+    ("`cmp` + `bne` (not taken)", 2),
+    ("`bl`", 3),
+    ("`bl` + `bx lr` + `b`", 7),
+    ("`dmb`", 3),
+    ("`dsb`", 3),
+    ("`isb`", 3),
+];
+
+/// Measurements of each core, in the same order as `INSTRUCTIONS`
+static mut RESULTS: [[u32; INSTRUCTIONS.len()]; 2] = [[0; INSTRUCTIONS.len()]; 2];
+
+/// Data accessed by the measured instructions, `stm` needs 16 bytes per repetition
+#[link_section = ".sram4_code"]
+static mut SCRATCH: [u32; 4 * REPEAT as usize] = [0; 4 * REPEAT as usize];
+
+#[rp2040_hal::entry]
+fn main1() -> ! {
+    // Grab our singleton objects
+    let mut pac = pac::Peripherals::take().unwrap();
+    let mut sio = hal::Sio::new(pac.SIO);
+
+    // Set up the watchdog driver - needed by the clock setup code
+    let mut watchdog = hal::Watchdog::new(pac.WATCHDOG);
+
+    // Configure the clocks
+    let clocks = hal::clocks::init_clocks_and_plls(
+        XTAL_FREQ_HZ,
+        pac.XOSC,
+        pac.CLOCKS,
+        pac.PLL_SYS,
+        pac.PLL_USB,
+        &mut pac.RESETS,
+        &mut watchdog,
+    )
+    .ok()
+    .unwrap();
+
+    println!("Running at {} MHz", clocks.system_clock.freq().to_MHz());
+    // configure systic to prepare for measurements
+    let core = pac::CorePeripherals::take().unwrap();
+    let _cycle_counter = CycleCounter::new(core.SYST);
+
+    // measure on core 0 before waking up core 1
+    measure_instructions(unsafe { &mut RESULTS[0] });
+
+    stack::install_guard();
+    let mut mc = Multicore::new(&mut pac.PSM, &mut pac.PPB, &mut sio.fifo);
+    let cores = mc.cores();
+    let core1 = &mut cores[1];
+    let _ = core1.spawn(unsafe { stack::core1_stack() }, || main2());
+
+    // wait for Core 1 to finish its measurements
+    while sio.fifo.read_blocking() != CORE1_DONE {}
+
+    println!("| Instruction | Expected | Core 0 | Core 1 |");
+    println!("| ----------- | -------- | ------ | ------ |");
+    for (i, (name, expected)) in INSTRUCTIONS.iter().enumerate() {
+        let (core0, core1) = unsafe { (RESULTS[0][i], RESULTS[1][i]) };
+        println!(
+            "| {} | {} | {} | {} |",
+            name,
+            expected,
+            core0 / REPEAT,
+            core1 / REPEAT
+        );
+        if core0 % REPEAT != 0 || core1 % REPEAT != 0 {
+            println!(
+                "{} is not a multiple of {} repetitions: core0 {} / core1 {} total cycles",
+                name, REPEAT, core0, core1
+            );
+        }
+    }
+
+    // Set the pins to their default state
+    let pins = hal::gpio::Pins::new(
+        pac.IO_BANK0,
+        pac.PADS_BANK0,
+        sio.gpio_bank0,
+        &mut pac.RESETS,
+    );
+    // Configure GPIO25 as an output
+    let mut led_pin = pins.gpio25.into_push_pull_output();
+
+    // sign of life
+    loop {
+        led_pin.toggle().unwrap();
+        asm::delay(12_500_000u32);
+    }
+}
+
+fn main2() -> ! {
+    stack::install_guard();
+    let pac = unsafe { pac::Peripherals::steal() };
+    let mut sio = Sio::new(pac.SIO);
+
+    // configure systic to prepare for measurements
+    let core = unsafe { pac::CorePeripherals::steal() };
+    let _cycle_counter = CycleCounter::new(core.SYST);
+
+    measure_instructions(unsafe { &mut RESULTS[1] });
+    sio.fifo.write_blocking(CORE1_DONE);
+
+    loop {
+        asm::nop();
+    }
+}
+
+/// Measures the total clock cycles taken by `REPEAT` copies of every entry of `INSTRUCTIONS`
+#[link_section = ".sram2_code"]
+#[inline(never)]
+fn measure_instructions(results: &mut [u32; INSTRUCTIONS.len()]) {
+    unsafe {
+        let data = core::ptr::addr_of_mut!(SCRATCH).cast::<u32>();
+
+        results[0] = measure_cycles!(10, "nop");
+        results[1] = measure_cycles!(10, "adds {a}, #1", a = inout(reg) 0u32 => _);
+        results[2] = measure_cycles!(
+            10,
+            "muls {a}, {b}, {a}",
+            a = inout(reg) 3u32 => _,
+            b = in(reg) 5u32,
+        );
+        results[3] = measure_cycles!(10, "ldr {v}, [{p}]", p = in(reg) data, v = out(reg) _);
+        results[4] = measure_cycles!(10, "ldrb {v}, [{p}]", p = in(reg) data, v = out(reg) _);
+        results[5] = measure_cycles!(10, "ldrh {v}, [{p}]", p = in(reg) data, v = out(reg) _);
+        results[6] = measure_cycles!(10, "str {v}, [{p}]", p = in(reg) data, v = in(reg) 77u32);
+        results[7] = measure_cycles!(10, "strb {v}, [{p}]", p = in(reg) data, v = in(reg) 77u32);
+        results[8] = measure_cycles!(10, "strh {v}, [{p}]", p = in(reg) data, v = in(reg) 77u32);
+
+        // the first word points to the buffer itself, so `ldm` can reload its base register
+        // without writeback and every repetition reads the same address
+        data.write_volatile(data as u32);
+        results[9] = measure_cycles!(
+            10,
+            "ldm r0, {{r0, r1}}",
+            inout("r0") data => _,
+            out("r1") _,
+        );
+        // stores whatever is in r1-r4, the writeback moves through SCRATCH
+        results[10] = measure_cycles!(
+            10,
+            "stm r0!, {{r1, r2, r3, r4}}",
+            inout("r0") data => _,
+        );
+        // pop restores the values pushed by push, so no register is modified
+        results[11] = measure_cycles!(10, "push {{r0, r1, r2, r3}}\npop {{r0, r1, r2, r3}}");
+
+        results[12] = measure_cycles!(10, "b 1f\n1:");
+        results[13] = measure_cycles!(10, "cmp {a}, {a}\nbeq 1f\n1:", a = in(reg) 0u32);
+        results[14] = measure_cycles!(10, "cmp {a}, {a}\nbne 1f\n1:", a = in(reg) 0u32);
+        results[15] = measure_cycles!(10, "bl 1f\n1:", out("lr") _);
+        results[16] = measure_cycles!(10, "bl 2f\nb 3f\n2:\nbx lr\n3:", out("lr") _);
+
+        results[17] = measure_cycles!(10, "dmb");
+        results[18] = measure_cycles!(10, "dsb");
+        results[19] = measure_cycles!(10, "isb");
+    }
+}
@@ -1,12 +1,59 @@
+// Interrupt latency suite
+// Experiment 8 of the README measures the latency of TIMER_IRQ_0 on core0, with the vector table in
+// SRAM2 and the handler in SRAM3. This example measures the same breakdown (total measurement
+// time, SysTick overhead, handler delay, assertion delay and latency) in the following scenarios:
+// - core0: vector table in SRAM2, handler in SRAM3, the scenario of experiment 8
+// - core1: vector table in SRAM3, handler in SRAM2, measured by core1
+// - flash_table_cold/warm: the vector table of cortex-m-rt in flash, handler in SRAM3, with a
+//   flushed and with a warm XIP cache
+// - flash_handler_cold/warm: vector table in SRAM2, handler in flash
+// - tail_chain: the TIMER_IRQ_0 handler forces TIMER_IRQ_1, of the same priority, right before it
+//   returns, so the TIMER_IRQ_1 handler is tail-chained. The latency is counted from the last
+//   SysTick read of the TIMER_IRQ_0 handler, right before its `bx lr`.
+// - late_arrival: core1 forces the high priority TIMER_IRQ_1 as soon as it sees the low priority
+//   TIMER_IRQ_0 forced by core0, while core0 is stacking for TIMER_IRQ_0. The latency of the
+//   TIMER_IRQ_1 handler is measured from the assertion of TIMER_IRQ_0, it includes the reaction
+//   time of core1.
+// - preemption: the low priority TIMER_IRQ_0 handler forces the high priority TIMER_IRQ_1, which
+//   preempts it
+// - wfi: core1 forces TIMER_IRQ_0 as soon as core0 writes to the FIFO, while core0 sleeps in WFI.
+//   The latency includes the reaction time of core1, the same measurement with core0 spinning
+//   instead of sleeping gives the cost of waking up. Core1 then forces TIMER_IRQ_1, so that core0
+//   doesn't sleep forever if TIMER_IRQ_0 was handled before WFI.
 //
+// The measured handlers are naked stubs generated by `stamp_handler!`, whose first instruction
+// reads the SysTick: the address of CVR is kept in r1 by the code forcing the interrupt, and the
+// exception entry doesn't change it. The handler delay is therefore 0 by construction instead of
+// the 7 cycles of prologue found in one disassembly, which depended on the opt-level and the
+// compiler version. Only tail_chain has a handler delay, the end of the naked TIMER_IRQ_0 handler
+// after it forces TIMER_IRQ_1, measured with a SysTick read before its `bx lr`. Each scenario is printed as an `interrupt_latency` record.
+//
+// Each scenario is measured `TRIALS.count` times with a random delay before each trial. The
+// breakdown of the first trial is printed as above, followed by the statistics of the latency (and
+// of the detail of late_arrival and wfi) over all the trials, as `interrupt_latency_stats` records.
+//
+// # Assumption/Expected result
+// - the same latency on both cores with the vector table and the handler in RAM, the Cortex-M0+
+//   takes 15 cycles with zero wait state memory
+// - a vector table or a handler in flash adds a XIP cache miss when the cache is cold, and nothing
+//   when it is warm
+// - tail-chaining is shorter than an exception entry since the registers are not unstacked and
+//   stacked again
+// - with a late arrival the TIMER_IRQ_1 handler executes first
+// - a preemption costs a full exception entry
+// - waking up from WFI adds a few cycles
 
 #![no_std]
 #![no_main]
 #![allow(non_snake_case)]
 
-use core::ops::{Add, Sub};
-
-use bus_behavior::systic_init;
+use bus_behavior::{
+    record::Summary,
+    stack,
+    stats::Stats,
+    trials::{Perturbation, Trials},
+    CycleCounter, SYST_CVR,
+};
 use cortex_m::asm;
 use defmt::*;
 use defmt_rtt as _;
@@ -22,7 +69,13 @@ use rp2040_hal as hal;
 
 // A shorter alias for the Peripheral Access Crate, which provides low-level
 // register access
-use hal::{pac, vector_table::VectorTable};
+use hal::{
+    multicore::Multicore,
+    pac::{self, Interrupt},
+    sio::SioFifo,
+    vector_table::VectorTable,
+    Sio,
+};
 
 #[link_section = ".boot2"]
 #[used]
@@ -30,34 +83,254 @@ pub static BOOT2: [u8; 256] = rp2040_boot2::BOOT_LOADER_GENERIC_03H;
 /// External high-speed crystal on the Raspberry Pi Pico board is 12 MHz.
 const XTAL_FREQ_HZ: u32 = 12_000_000u32;
 
-#[link_section = ".sram2_code"]
-static mut TIMER_IRQ_ACK_TIME: u32 = 0;
+const TRIALS: Trials = Trials {
+    count: 100,
+    perturbation: Perturbation {
+        max_delay: 64,
+        flush_xip_cache: false,
+    },
+};
+
+/// TIMER INTF register, forces the TIMER interrupts
+const TIMER_INTF_ADDR: u32 = 0x4005_403c;
+const TIMER_INTF: *mut u32 = TIMER_INTF_ADDR as *mut u32;
+/// Bits of ALARM0 (TIMER_IRQ_0) and ALARM1 (TIMER_IRQ_1) in the TIMER interrupt registers
+const ALARM0: u32 = 1 << 0;
+const ALARM1: u32 = 1 << 1;
+
+/// Address of the SysTick CVR, in r1 when a stamp handler is entered
+const SYST_CVR_ADDR: u32 = 0xE000_E018;
+
+/// SIO FIFO status (VLD is bit 0) and data registers
+const SIO_FIFO_ST: *const u32 = 0xd000_0050 as *const u32;
+const SIO_FIFO_WR: *mut u32 = 0xd000_0054 as *mut u32;
+const SIO_FIFO_RD: *const u32 = 0xd000_0058 as *const u32;
+
+/// Writing 1 flushes the XIP cache, reading it back stalls until the flush is complete
+const XIP_CTRL_FLUSH: *mut u32 = 0x1400_0004 as *mut u32;
+
+/// NVIC priorities, only the 2 upper bits are implemented
+const PRIORITY_HIGH: u8 = 0x00;
+const PRIORITY_LOW: u8 = 0x80;
+
+// commands sent by core0 to core1
+/// Measure the latency on core1 and send back the start, assertion, stamp and SysTick overhead
+const CMD_MEASURE: u32 = 1;
+/// Force TIMER_IRQ_1 as soon as TIMER_IRQ_0 is forced
+const CMD_LATE_ARRIVAL: u32 = 2;
+/// Force TIMER_IRQ_0 as soon as a word is written to the FIFO
+const CMD_WAKE: u32 = 3;
+/// Sent by core1 once it polls for the event of a command
+const CORE1_READY: u32 = 7;
+
+// handler stamps, the SysTick value read by the first instruction after the handler prologue
+const SLOT_CORE0: usize = 0;
+const SLOT_CORE1: usize = 1;
+const SLOT_FLASH_TABLE: usize = 2;
+const SLOT_FLASH_HANDLER: usize = 3;
+const SLOT_TAIL_CHAIN: usize = 4;
+const SLOT_LATE_LOW: usize = 5;
+const SLOT_LATE_HIGH: usize = 6;
+const SLOT_PREEMPTION: usize = 7;
+const SLOT_WAKE_KICK: usize = 8;
+const SLOTS: usize = 9;
+
+static mut STAMPS: [u32; SLOTS] = [0; SLOTS];
+/// SysTick values before and after forcing an interrupt from a handler (tail_chain, preemption)
+static mut TRIGGER: (u32, u32) = (0, 0);
+/// SysTick read by `tail_chaining_irq` right before it returns, stored by `tail_chained_irq`
+static mut TAIL_CHAIN_EXIT: u32 = 0;
+
+/// Address of the vector table in flash, active at boot
+static mut FLASH_VECTOR_TABLE: u32 = 0;
+
 // vector tables for the two cores stored in different memeory regions to avoid any concurrent
 // access to the same memory bank when the two cores both receive an interrupt at the same time
 #[link_section = ".sram2_code"]
 static mut CORE0_VECTOR_TABLE: VectorTable = VectorTable::new();
+#[link_section = ".sram3_code"]
+static mut CORE1_VECTOR_TABLE: VectorTable = VectorTable::new();
+
+/// Defines a naked handler whose first instruction reads the SysTick CVR, then stores the value
+/// in `STAMPS[$slot]` and stops the forced `$alarm` interrupt.
+///
+/// The address of CVR must be in r1 when the interrupt is taken: the exception entry stacks r0-r3
+/// but leaves their values in the registers, so the code forcing the interrupt keeps r1 until the
+/// handler is done (see `force_irq()`). The handler only writes r0, r2 and r3, so a handler
+/// tail-chained after it also finds CVR in r1. There is no prologue to subtract, whatever the
+/// opt-level or the compiler version.
+macro_rules! stamp_handler {
+    ($(#[$attr:meta])* $handler:ident, $section:literal, $slot:expr, $alarm:expr) => {
+        $(#[$attr])*
+        #[link_section = $section]
+        #[unsafe(naked)]
+        pub extern "C" fn $handler() {
+            core::arch::naked_asm!(
+                "ldr r0, [r1]", // read systick current value register CVR
+                "ldr r2, ={stamps}+{offset}",
+                "str r0, [r2]",
+                // stop this triggering interrupt, leave the other one forced
+                "ldr r2, ={intf}",
+                "ldr r3, [r2]",
+                "movs r0, #{alarm}",
+                "bics r3, r0",
+                "str r3, [r2]",
+                "bx lr",
+                ".ltorg",
+                stamps = sym STAMPS,
+                offset = const $slot * 4,
+                intf = const TIMER_INTF_ADDR,
+                alarm = const $alarm,
+            )
+        }
+    };
+}
+
+stamp_handler!(core0_timer_irq, ".sram3_code", SLOT_CORE0, ALARM0);
+stamp_handler!(core1_timer_irq, ".sram2_code", SLOT_CORE1, ALARM0);
+// the TIMER_IRQ_0 entry of the vector table in flash
+stamp_handler!(
+    #[no_mangle]
+    TIMER_IRQ_0,
+    ".sram3_code",
+    SLOT_FLASH_TABLE,
+    ALARM0
+);
+stamp_handler!(
+    flash_timer_irq,
+    ".text.flash_timer_irq",
+    SLOT_FLASH_HANDLER,
+    ALARM0
+);
+stamp_handler!(late_low_irq, ".sram3_code", SLOT_LATE_LOW, ALARM0);
+stamp_handler!(late_high_irq, ".sram3_code", SLOT_LATE_HIGH, ALARM1);
+stamp_handler!(preempting_irq, ".sram3_code", SLOT_PREEMPTION, ALARM1);
+// wakes core0 up if the TIMER_IRQ_0 handler of the wfi scenario ran before WFI, see `wake_core0()`
+stamp_handler!(wake_kick_irq, ".sram3_code", SLOT_WAKE_KICK, ALARM1);
+
+/// Breakdown of a latency measurement, all in clock cycles
+struct Breakdown {
+    /// From the SysTick read before the interrupt is forced to the one in the handler
+    total: u32,
+    systick_overhead: u32,
+    handler_delay: u32,
+    /// Execution of the STR forcing the interrupt
+    assertion_delay: u32,
+    latency: u32,
+    /// Additional result of the scenario
+    detail: Option<Detail>,
+}
+
+/// Result specific to a scenario, e.g. the cost of waking up from WFI
+struct Detail {
+    /// Name of the quantity in the records
+    name: &'static str,
+    value: u32,
+}
+
+impl Breakdown {
+    /// `start` and `asserted` are read right before and right after forcing the interrupt,
+    /// `stamp` by the handler, with the SysTick `overhead` of the measuring core
+    /// `handler_delay` is what the handlers execute before the stamp besides the exception entry,
+    /// 0 with the stubs of `stamp_handler!`
+    fn new(start: u32, asserted: u32, stamp: u32, overhead: u32, handler_delay: u32) -> Self {
+        let total = start.wrapping_sub(stamp);
+        let assertion_delay = start.wrapping_sub(asserted).wrapping_sub(overhead);
+        Breakdown {
+            total,
+            systick_overhead: overhead,
+            handler_delay,
+            assertion_delay,
+            latency: total
+                .wrapping_sub(assertion_delay)
+                .wrapping_sub(handler_delay)
+                .wrapping_sub(overhead),
+            detail: None,
+        }
+    }
+}
+
+struct Scenario {
+    name: &'static str,
+    description: &'static str,
+    /// Measures the scenario from core0, with the FIFO to send commands to core1
+    measure: fn(&mut SioFifo) -> Breakdown,
+}
+
+const SCENARIOS: [Scenario; 10] = [
+    Scenario {
+        name: "core0",
+        description: "core0, vector table in SRAM2, handler in SRAM3",
+        measure: measure_core0,
+    },
+    Scenario {
+        name: "core1",
+        description: "core1, vector table in SRAM3, handler in SRAM2",
+        measure: measure_core1,
+    },
+    Scenario {
+        name: "flash_table_cold",
+        description: "vector table in flash, handler in SRAM3, XIP cache flushed",
+        measure: measure_flash_table_cold,
+    },
+    Scenario {
+        name: "flash_table_warm",
+        description: "vector table in flash, handler in SRAM3, XIP cache warm",
+        measure: measure_flash_table_warm,
+    },
+    Scenario {
+        name: "flash_handler_cold",
+        description: "vector table in SRAM2, handler in flash, XIP cache flushed",
+        measure: measure_flash_handler_cold,
+    },
+    Scenario {
+        name: "flash_handler_warm",
+        description: "vector table in SRAM2, handler in flash, XIP cache warm",
+        measure: measure_flash_handler_warm,
+    },
+    Scenario {
+        name: "tail_chain",
+        description: "TIMER_IRQ_1 tail-chained after TIMER_IRQ_0, same priority",
+        measure: measure_tail_chain,
+    },
+    Scenario {
+        name: "late_arrival",
+        description: "high priority TIMER_IRQ_1 forced by core1 while core0 stacks for TIMER_IRQ_0",
+        measure: measure_late_arrival,
+    },
+    Scenario {
+        name: "preemption",
+        description: "high priority TIMER_IRQ_1 preempts the TIMER_IRQ_0 handler",
+        measure: measure_preemption,
+    },
+    Scenario {
+        name: "wfi",
+        description: "TIMER_IRQ_0 forced by core1 while core0 sleeps in WFI",
+        measure: measure_wfi,
+    },
+];
 
 #[rp2040_hal::entry]
 #[link_section = ".sram2_code"]
 fn main1() -> ! {
     // Grab our singleton objects
     let mut pac = pac::Peripherals::take().unwrap();
-    let mut core = pac::CorePeripherals::take().unwrap();
-    let sio = hal::Sio::new(pac.SIO);
+    let core = pac::CorePeripherals::take().unwrap();
+    let mut sio = Sio::new(pac.SIO);
 
     // configure priority for core 0 to be MAX
     pac.RESETS.reset.modify(|_, w| w.busctrl().clear_bit()); // take BUSCTRL out of reset mode
     pac.BUSCTRL.bus_priority.write(|w| w.proc0().set_bit());
 
     // configure systic to prepare for measurements
-    systic_init();
+    let _cycle_counter = CycleCounter::new(core.SYST);
 
-    // configure a vector table in RAM
+    // keep the address of the vector table in flash before activating the one in RAM
+    unsafe { FLASH_VECTOR_TABLE = pac.PPB.vtor.read().bits() };
     unsafe {
-        // let mut CORE0_VECTOR_TABLE: VectorTable = VectorTable::new();
-        CORE0_VECTOR_TABLE.init(&mut pac.PPB);
-        CORE0_VECTOR_TABLE.register_handler(pac::Interrupt::TIMER_IRQ_0 as usize, core0_timer_irq);
-        CORE0_VECTOR_TABLE.activate(&mut pac.PPB);
+        let table = &mut *core::ptr::addr_of_mut!(CORE0_VECTOR_TABLE);
+        table.init(&mut pac.PPB);
+        table.activate(&mut pac.PPB);
     }
 
     // Set up the watchdog driver - needed by the clock setup code
@@ -76,79 +349,85 @@ fn main1() -> ! {
     .ok()
     .unwrap();
 
-    // unpend and unmask timer interrupts
-    pac::NVIC::unpend(pac::Interrupt::TIMER_IRQ_0);
-    unsafe { core.NVIC.set_priority(pac::Interrupt::TIMER_IRQ_0, 1) };
-    unsafe { pac::NVIC::unmask(pac::Interrupt::TIMER_IRQ_0) };
+    // enable timer interrupts, each scenario unmasks the ones it uses
+    pac.TIMER
+        .inte
+        .write(|wr| wr.alarm_0().set_bit().alarm_1().set_bit());
 
-    // prepare some constants
-    const SYST_CVR: *const u32 = 0xE000_E018 as *const u32;
-    let TIMER_INTF = 0x4005_403c as *mut u32;
+    // drain the fifo from core 1 side & start the second core
+    sio.fifo.drain();
+    stack::install_guard();
+    let mut mc = Multicore::new(&mut pac.PSM, &mut pac.PPB, &mut sio.fifo);
+    let cores = mc.cores();
+    let core1 = &mut cores[1];
+    let _ = core1.spawn(unsafe { stack::core1_stack() }, || main2());
+    while sio.fifo.read_blocking() != CORE1_READY {}
 
-    // enable timer interrupts
-    pac.TIMER.inte.write(|wr| wr.alarm_0().set_bit());
+    for scenario in &SCENARIOS {
+        let mut latency = Stats::new();
+        let mut detail = Stats::new();
+        let mut first = None;
+        TRIALS.repeat(|trial| {
+            mask_all();
+            unsafe { core::ptr::addr_of_mut!(STAMPS).write_volatile([0; SLOTS]) };
 
-    // read the systick timer and immediately trigger the timer interrupt, then immediately read the systic timer again
-    // to allow us to determine the time needed to assert the ISR
-    let start_time: u32;
-    let intf_assersion_time: u32;
-    unsafe {
-        core::arch::asm!(
-            "ldr {0}, [{1}]", // read systic right before asserting the interrupt line
-            "str {2}, [{3}]", // force trigger interrupts
-            "ldr {4}, [{1}]", // read systic right after asserting the interrupt line
-            out(reg) start_time,
-            in(reg) SYST_CVR,
-            in(reg) 0x0000_0001, // enable the alarm0 interrupt line (first bit in the register)
-            in(reg) TIMER_INTF,
-            out(reg) intf_assersion_time,
+            let b = (scenario.measure)(&mut sio.fifo);
+            mask_all();
+            latency.add(b.latency);
+            if let Some(d) = &b.detail {
+                detail.add(d.value);
+            }
+            if trial == 0 {
+                first = Some(b);
+            }
+        });
+        let Some(b) = first else {
+            continue;
+        };
 
+        println!("== {}: {}", scenario.name, scenario.description);
+        println!(
+            "Total measurement time           = {} clock cycles",
+            b.total
+        );
+        println!(
+            "systick measurment delay         = {:02} clock cycles",
+            b.systick_overhead
+        );
+        println!(
+            "irq handler delay                = {:02} clock cycles",
+            b.handler_delay
+        );
+        println!(
+            "Alarm0 interrupt assertion delay = {:02} clock cycles",
+            b.assertion_delay
         );
+        println!(
+            "total measurment delay           = {} clock cycles",
+            b.assertion_delay + b.handler_delay + b.systick_overhead
+        );
+        println!(
+            "irq latency                      = {} clock cycles",
+            b.latency
+        );
+        if let Some(d) = &b.detail {
+            println!("{=str} = {}", d.name, d.value);
+        }
+        println!(
+            "@interrupt_latency scenario={=str} total={=u32} systick_overhead={=u32} handler_delay={=u32} assertion_delay={=u32} latency={=u32}",
+            scenario.name,
+            b.total,
+            b.systick_overhead,
+            b.handler_delay,
+            b.assertion_delay,
+            b.latency
+        );
+        print_stats(scenario.name, "latency", &latency);
+        if let Some(d) = &b.detail {
+            print_stats(scenario.name, d.name, &detail);
+        }
     }
 
-    // wait a bit to make sure the timer interrupts have been executed
-    asm::delay(12_500_000u32);
-
-    //===============  calculate and print the irq latency ====================
-
-    // this delay is the overhead of readying twice the value CVR of SYSTICK (once at start of the measurement and once at the end)
-    // see: https://developer.arm.com/documentation/ka001406/latest/
-    let systick_measurment_delay = 2;
-
-    // time needed to execute the STR instruction to store 1 ( assert the ISR) to the TIMER INTF register
-    let intf_write_delay = start_time - intf_assersion_time - systick_measurment_delay;
-
-    // this delay is due to the assembly instructions that must be executed at the start of the interrupt handler
-    // before the systick timer value can be read to a register. The assembly instructions are:
-    // push	{r7, lr} (Takes 3 clock cycles)
-    // add	r7, sp, #0 (Takes 1 clock cycles)
-    // sub	sp, #8 (Takes 1 clock cycles)
-    // ldr	r1, .LCPI43_0 (Takes 2 clock cycles)
-    let irq_handler_dely = 7;
-
-    println!(
-        "Total measurement time           = {} clock cycles",
-        start_time.sub(unsafe { TIMER_IRQ_ACK_TIME }) // TIMER_IRQ_ACK_TIME holds the value of systick timer as soon as the TIMER_IRQ_0 isr starts executing
-    );
-    println!("systick measurment delay         = 02 clock cycles");
-    println!("irq handler delay                = 07 clock cycles");
-    println!(
-        "Alarm0 interrupt assertion delay = {:02} clock cycles",
-        intf_write_delay
-    );
-    println!(
-        "total measurment delay           = {} clock cycles",
-        intf_write_delay.add(9)
-    );
-    println!(
-        "irq latency on core0             = {} clock cycles",
-        start_time
-            .sub(unsafe { TIMER_IRQ_ACK_TIME }) // TIMER_IRQ_ACK_TIME holds the value of systick timer as soon as the TIMER_IRQ_0 isr starts executing
-            .sub(intf_write_delay) //subtract measument delay1
-            .sub(irq_handler_dely) //subtract measument delay2
-            .sub(systick_measurment_delay) //subtract measument delay3
-    );
-
     // config led pin
     let pins = hal::gpio::Pins::new(
         pac.IO_BANK0,
@@ -164,20 +443,448 @@ fn main1() -> ! {
     }
 }
 
-#[link_section = ".sram3_code"]
-#[no_mangle]
-pub extern "C" fn core0_timer_irq() {
+/// Prints the statistics of `quantity` over the trials of `scenario`, and its
+/// `interrupt_latency_stats` record
+fn print_stats(scenario: &str, quantity: &str, stats: &Stats) {
+    println!("{=str} over {}", quantity, stats);
+    println!(
+        "@interrupt_latency_stats scenario={=str} perturbation={} quantity={=str} {}",
+        scenario,
+        TRIALS.perturbation.name(),
+        quantity,
+        Summary(stats)
+    );
+}
+
+/// Reads the SysTick, forces the TIMER interrupts of `alarms` and reads the SysTick again, then
+/// waits until the handlers have stopped the forced interrupts of `wait`. The address of CVR stays
+/// in r1 for the handlers generated by `stamp_handler!`.
+#[inline(always)]
+unsafe fn force_irq(alarms: u32, wait: u32) -> (u32, u32) {
+    let start: u32;
+    let asserted: u32;
+    core::arch::asm!(
+        "ldr {start}, [r1]", // read systic right before asserting the interrupt line
+        "str {alarms}, [{intf}]", // force trigger interrupts
+        "ldr {asserted}, [r1]", // read systic right after asserting the interrupt line
+        "2:",
+        "ldr {tmp}, [{intf}]",
+        "tst {tmp}, {wait}",
+        "bne 2b",
+        in("r1") SYST_CVR,
+        alarms = in(reg) alarms,
+        intf = in(reg) TIMER_INTF,
+        wait = in(reg) wait,
+        start = out(reg) start,
+        asserted = out(reg) asserted,
+        tmp = out(reg) _,
+    );
+    (start, asserted)
+}
+
+/// Waits until the handlers have stopped the forced interrupts
+fn wait_for_handlers() {
+    while unsafe { TIMER_INTF.read_volatile() } & (ALARM0 | ALARM1) != 0 {}
+}
+
+/// Stops the forced interrupts of `alarms`, leaving the other one forced
+fn clear_alarm(alarms: u32) {
     unsafe {
-        const SYST_CVR: *const u32 = 0xE000_E018 as *const u32;
-        let ack_time: u32;
-        core::arch::asm!(
-            "ldr {0}, [{1}]", // read systick current value register CVR
-            out(reg) ack_time,
-            in(reg) SYST_CVR,
-        );
+        let timer = &*pac::TIMER::ptr();
+        timer.intf.modify(|r, w| w.bits(r.bits() & !alarms));
+    }
+}
+
+fn stamp(slot: usize) -> u32 {
+    unsafe { core::ptr::addr_of!(STAMPS[slot]).read_volatile() }
+}
+
+/// Unmasks `interrupt` on the calling core with `priority`
+fn unmask(interrupt: pac::Interrupt, priority: u8) {
+    unsafe {
+        let mut core = pac::CorePeripherals::steal();
+        core.NVIC.set_priority(interrupt, priority);
+        pac::NVIC::unpend(interrupt);
+        pac::NVIC::unmask(interrupt);
+    }
+}
+
+fn mask_all() {
+    pac::NVIC::mask(Interrupt::TIMER_IRQ_0);
+    pac::NVIC::mask(Interrupt::TIMER_IRQ_1);
+    clear_alarm(ALARM0 | ALARM1);
+}
+
+/// Registers `handlers` in the vector table of core0 in SRAM2 and activates it
+fn use_ram_table(handlers: &[(pac::Interrupt, extern "C" fn())]) {
+    unsafe {
+        let mut pac = pac::Peripherals::steal();
+        let table = &mut *core::ptr::addr_of_mut!(CORE0_VECTOR_TABLE);
+        for &(interrupt, handler) in handlers {
+            table.register_handler(interrupt as usize, handler);
+        }
+        table.activate(&mut pac.PPB);
+    }
+    asm::dsb();
+    asm::isb();
+}
+
+fn use_flash_table() {
+    unsafe {
+        let pac = pac::Peripherals::steal();
+        pac.PPB.vtor.write(|w| w.bits(FLASH_VECTOR_TABLE));
+    }
+    asm::dsb();
+    asm::isb();
+}
+
+/// Invalidates the whole XIP cache, reading FLUSH stalls until the flush is complete
+#[link_section = ".sram2_code"]
+fn flush_xip_cache() {
+    unsafe {
+        XIP_CTRL_FLUSH.write_volatile(1);
+        let _ = XIP_CTRL_FLUSH.read_volatile();
+    }
+}
+
+/// Forces TIMER_IRQ_0 from SRAM2 and returns the breakdown of the handler stamping `slot`
+#[link_section = ".sram2_code"]
+fn measure_irq0(slot: usize) -> Breakdown {
+    let (start, asserted) = unsafe { force_irq(ALARM0, ALARM0 | ALARM1) };
+    Breakdown::new(start, asserted, stamp(slot), CycleCounter::overhead(), 0)
+}
+
+fn measure_core0(_: &mut SioFifo) -> Breakdown {
+    use_ram_table(&[(Interrupt::TIMER_IRQ_0, core0_timer_irq)]);
+    unmask(Interrupt::TIMER_IRQ_0, PRIORITY_HIGH);
+    measure_irq0(SLOT_CORE0)
+}
+
+fn measure_core1(fifo: &mut SioFifo) -> Breakdown {
+    fifo.write_blocking(CMD_MEASURE);
+    let start = fifo.read_blocking();
+    let asserted = fifo.read_blocking();
+    let stamp = fifo.read_blocking();
+    let overhead = fifo.read_blocking();
+    Breakdown::new(start, asserted, stamp, overhead, 0)
+}
+
+fn measure_flash_table_cold(_: &mut SioFifo) -> Breakdown {
+    use_flash_table();
+    unmask(Interrupt::TIMER_IRQ_0, PRIORITY_HIGH);
+    flush_xip_cache();
+    measure_irq0(SLOT_FLASH_TABLE)
+}
+
+fn measure_flash_table_warm(_: &mut SioFifo) -> Breakdown {
+    use_flash_table();
+    unmask(Interrupt::TIMER_IRQ_0, PRIORITY_HIGH);
+    // a first interrupt brings the vector in the cache
+    measure_irq0(SLOT_FLASH_TABLE);
+    measure_irq0(SLOT_FLASH_TABLE)
+}
+
+fn measure_flash_handler_cold(_: &mut SioFifo) -> Breakdown {
+    use_ram_table(&[(Interrupt::TIMER_IRQ_0, flash_timer_irq)]);
+    unmask(Interrupt::TIMER_IRQ_0, PRIORITY_HIGH);
+    flush_xip_cache();
+    measure_irq0(SLOT_FLASH_HANDLER)
+}
+
+fn measure_flash_handler_warm(_: &mut SioFifo) -> Breakdown {
+    use_ram_table(&[(Interrupt::TIMER_IRQ_0, flash_timer_irq)]);
+    unmask(Interrupt::TIMER_IRQ_0, PRIORITY_HIGH);
+    // a first interrupt brings the handler in the cache
+    measure_irq0(SLOT_FLASH_HANDLER);
+    measure_irq0(SLOT_FLASH_HANDLER)
+}
 
-        core::ptr::write_volatile(&mut TIMER_IRQ_ACK_TIME, ack_time);
+/// TIMER_IRQ_0 handler of the tail_chain scenario, forces TIMER_IRQ_1 and stops TIMER_IRQ_0 with
+/// the same write, then stores the SysTick values read around the write in `TRIGGER` and returns.
+/// r1 is left with the address of CVR for the tail-chained handler, and r2 with a last SysTick
+/// read right before `bx lr`: the exception return doesn't restore r0-r3 when it tail-chains.
+#[link_section = ".sram3_code"]
+#[unsafe(naked)]
+extern "C" fn tail_chaining_irq() {
+    core::arch::naked_asm!(
+        "ldr r1, ={cvr}",
+        "ldr r2, ={intf}",
+        "movs r3, #{alarm1}",
+        "ldr r0, [r1]", // read systic right before asserting the interrupt line
+        "str r3, [r2]", // force TIMER_IRQ_1, stop TIMER_IRQ_0
+        "ldr r3, [r1]", // read systic right after asserting the interrupt line
+        "ldr r2, ={trigger}",
+        "str r0, [r2]",
+        "str r3, [r2, #4]",
+        "ldr r2, [r1]", // read systick right before returning
+        "bx lr",
+        ".ltorg",
+        cvr = const SYST_CVR_ADDR,
+        intf = const TIMER_INTF_ADDR,
+        alarm1 = const ALARM1,
+        trigger = sym TRIGGER,
+    )
+}
+
+/// TIMER_IRQ_1 handler of the tail_chain scenario, `stamp_handler!` which also stores r2, the
+/// SysTick read by `tail_chaining_irq` before returning, in `TAIL_CHAIN_EXIT`.
+#[link_section = ".sram3_code"]
+#[unsafe(naked)]
+extern "C" fn tail_chained_irq() {
+    core::arch::naked_asm!(
+        "ldr r0, [r1]", // read systick current value register CVR
+        "ldr r3, ={exit}",
+        "str r2, [r3]",
+        "ldr r2, ={stamps}+{offset}",
+        "str r0, [r2]",
         // stop this triggering interrupt
-        pac::Peripherals::steal().TIMER.intf.reset();
+        "ldr r2, ={intf}",
+        "ldr r3, [r2]",
+        "movs r0, #{alarm1}",
+        "bics r3, r0",
+        "str r3, [r2]",
+        "bx lr",
+        ".ltorg",
+        exit = sym TAIL_CHAIN_EXIT,
+        stamps = sym STAMPS,
+        offset = const SLOT_TAIL_CHAIN * 4,
+        intf = const TIMER_INTF_ADDR,
+        alarm1 = const ALARM1,
+    )
+}
+
+fn measure_tail_chain(_: &mut SioFifo) -> Breakdown {
+    use_ram_table(&[
+        (Interrupt::TIMER_IRQ_0, tail_chaining_irq),
+        (Interrupt::TIMER_IRQ_1, tail_chained_irq),
+    ]);
+    unmask(Interrupt::TIMER_IRQ_0, PRIORITY_HIGH);
+    unmask(Interrupt::TIMER_IRQ_1, PRIORITY_HIGH);
+    unsafe { TIMER_INTF.write_volatile(ALARM0) };
+    wait_for_handlers();
+    let (start, asserted) = unsafe { core::ptr::addr_of!(TRIGGER).read_volatile() };
+    let exit = unsafe { core::ptr::addr_of!(TAIL_CHAIN_EXIT).read_volatile() };
+    // the end of the first handler is measured up to its last SysTick read, the latency is counted
+    // from there, i.e. from the `bx lr` starting the tail chaining
+    Breakdown::new(
+        start,
+        asserted,
+        stamp(SLOT_TAIL_CHAIN),
+        CycleCounter::overhead(),
+        asserted.wrapping_sub(exit),
+    )
+}
+
+fn measure_late_arrival(fifo: &mut SioFifo) -> Breakdown {
+    use_ram_table(&[
+        (Interrupt::TIMER_IRQ_0, late_low_irq),
+        (Interrupt::TIMER_IRQ_1, late_high_irq),
+    ]);
+    unmask(Interrupt::TIMER_IRQ_0, PRIORITY_LOW);
+    unmask(Interrupt::TIMER_IRQ_1, PRIORITY_HIGH);
+    fifo.write_blocking(CMD_LATE_ARRIVAL);
+    while fifo.read_blocking() != CORE1_READY {}
+
+    let mut b = measure_irq0(SLOT_LATE_HIGH);
+    // the stamps are SysTick values, a down counter: the handler executed first has the larger one
+    b.detail = Some(Detail {
+        name: "irq1_first",
+        value: (stamp(SLOT_LATE_HIGH) > stamp(SLOT_LATE_LOW)) as u32,
+    });
+    b
+}
+
+/// TIMER_IRQ_0 handler of the preemption scenario, forces TIMER_IRQ_1 which preempts it. Its
+/// prologue doesn't matter, the latency is measured from the write forcing TIMER_IRQ_1.
+#[link_section = ".sram3_code"]
+extern "C" fn preempted_irq() {
+    unsafe {
+        let trigger = force_irq(ALARM0 | ALARM1, ALARM1);
+        core::ptr::addr_of_mut!(TRIGGER).write_volatile(trigger);
+    }
+    clear_alarm(ALARM0);
+}
+
+fn measure_preemption(_: &mut SioFifo) -> Breakdown {
+    use_ram_table(&[
+        (Interrupt::TIMER_IRQ_0, preempted_irq),
+        (Interrupt::TIMER_IRQ_1, preempting_irq),
+    ]);
+    unmask(Interrupt::TIMER_IRQ_0, PRIORITY_LOW);
+    unmask(Interrupt::TIMER_IRQ_1, PRIORITY_HIGH);
+    unsafe { TIMER_INTF.write_volatile(ALARM0) };
+    wait_for_handlers();
+    let (start, asserted) = unsafe { core::ptr::addr_of!(TRIGGER).read_volatile() };
+    Breakdown::new(
+        start,
+        asserted,
+        stamp(SLOT_PREEMPTION),
+        CycleCounter::overhead(),
+        0,
+    )
+}
+
+fn measure_wfi(fifo: &mut SioFifo) -> Breakdown {
+    use_ram_table(&[
+        (Interrupt::TIMER_IRQ_0, core0_timer_irq),
+        (Interrupt::TIMER_IRQ_1, wake_kick_irq),
+    ]);
+    unmask(Interrupt::TIMER_IRQ_0, PRIORITY_HIGH);
+    unmask(Interrupt::TIMER_IRQ_1, PRIORITY_LOW);
+
+    let spinning = wake_core0(fifo, false);
+    let mut sleeping = wake_core0(fifo, true);
+    // clock cycles added by waking up
+    sleeping.detail = Some(Detail {
+        name: "wfi_wake",
+        value: sleeping.latency.wrapping_sub(spinning.latency),
+    });
+    sleeping
+}
+
+/// Asks core1 to force TIMER_IRQ_0 as soon as the FIFO is written, then writes to the FIFO and
+/// sleeps in WFI, or spins until the handler is done. r1 holds the address of CVR for the handler.
+///
+/// Once the handler has stopped TIMER_IRQ_0, core1 forces TIMER_IRQ_1: if the handler ran before
+/// WFI, this second interrupt wakes core0 up instead of leaving it asleep forever. Core0 sleeps
+/// again until the stamp is written, and waits for the second handler before returning.
+#[link_section = ".sram2_code"]
+fn wake_core0(fifo: &mut SioFifo, sleep: bool) -> Breakdown {
+    unsafe {
+        core::ptr::addr_of_mut!(STAMPS[SLOT_CORE0]).write_volatile(0);
+        core::ptr::addr_of_mut!(STAMPS[SLOT_WAKE_KICK]).write_volatile(0);
     }
+    fifo.write_blocking(CMD_WAKE);
+    while fifo.read_blocking() != CORE1_READY {}
+
+    let start: u32;
+    let asserted: u32;
+    unsafe {
+        if sleep {
+            core::arch::asm!(
+                "ldr {start}, [r1]",
+                "str {start}, [{fifo}]", // core1 forces the interrupt
+                "ldr {asserted}, [r1]",
+                "2:",
+                "wfi",
+                "ldr {tmp}, [{stamp}]",
+                "cmp {tmp}, #0",
+                "beq 2b",
+                in("r1") SYST_CVR,
+                fifo = in(reg) SIO_FIFO_WR,
+                stamp = in(reg) core::ptr::addr_of!(STAMPS[SLOT_CORE0]),
+                start = out(reg) start,
+                asserted = out(reg) asserted,
+                tmp = out(reg) _,
+            );
+        } else {
+            core::arch::asm!(
+                "ldr {start}, [r1]",
+                "str {start}, [{fifo}]", // core1 forces the interrupt
+                "ldr {asserted}, [r1]",
+                "2:",
+                "ldr {tmp}, [{stamp}]",
+                "cmp {tmp}, #0",
+                "beq 2b",
+                in("r1") SYST_CVR,
+                fifo = in(reg) SIO_FIFO_WR,
+                stamp = in(reg) core::ptr::addr_of!(STAMPS[SLOT_CORE0]),
+                start = out(reg) start,
+                asserted = out(reg) asserted,
+                tmp = out(reg) _,
+            );
+        }
+    }
+    while stamp(SLOT_WAKE_KICK) == 0 {}
+    Breakdown::new(
+        start,
+        asserted,
+        stamp(SLOT_CORE0),
+        CycleCounter::overhead(),
+        0,
+    )
+}
+
+// in SRAM4, so that the polling loops of core1 don't contest with the handlers of core0 in SRAM3
+#[link_section = ".sram4_code"]
+fn main2() -> ! {
+    stack::install_guard();
+    let mut pac = unsafe { pac::Peripherals::steal() };
+    let core = unsafe { pac::CorePeripherals::steal() };
+    let mut sio = Sio::new(pac.SIO);
+
+    // configure systic to prepare for measurements
+    let _cycle_counter = CycleCounter::new(core.SYST);
+
+    // vector table in SRAM3, handler in SRAM2: the mirror of core0
+    unsafe {
+        let table = &mut *core::ptr::addr_of_mut!(CORE1_VECTOR_TABLE);
+        table.init(&mut pac.PPB);
+        table.register_handler(Interrupt::TIMER_IRQ_0 as usize, core1_timer_irq);
+        table.activate(&mut pac.PPB);
+    }
+    sio.fifo.write_blocking(CORE1_READY);
+
+    loop {
+        match sio.fifo.read_blocking() {
+            CMD_MEASURE => {
+                unmask(Interrupt::TIMER_IRQ_0, PRIORITY_HIGH);
+                let (start, asserted) = measure_on_core1();
+                pac::NVIC::mask(Interrupt::TIMER_IRQ_0);
+                sio.fifo.write_blocking(start);
+                sio.fifo.write_blocking(asserted);
+                sio.fifo.write_blocking(stamp(SLOT_CORE1));
+                sio.fifo.write_blocking(CycleCounter::overhead());
+            }
+            CMD_LATE_ARRIVAL => {
+                sio.fifo.write_blocking(CORE1_READY);
+                unsafe {
+                    core::arch::asm!(
+                        "2:",
+                        "ldr {tmp}, [{intf}]",
+                        "tst {tmp}, {alarm0}",
+                        "beq 2b",
+                        "str {both}, [{intf}]",
+                        intf = in(reg) TIMER_INTF,
+                        alarm0 = in(reg) ALARM0,
+                        both = in(reg) ALARM0 | ALARM1,
+                        tmp = out(reg) _,
+                    );
+                }
+            }
+            CMD_WAKE => {
+                sio.fifo.write_blocking(CORE1_READY);
+                unsafe {
+                    core::arch::asm!(
+                        "2:",
+                        "ldr {tmp}, [{st}]",
+                        "tst {tmp}, {vld}",
+                        "beq 2b",
+                        "str {alarm0}, [{intf}]",
+                        "ldr {tmp}, [{rd}]",
+                        // wait for the handler of core0, then wake it up in case it wasn't asleep
+                        "3:",
+                        "ldr {tmp}, [{intf}]",
+                        "tst {tmp}, {alarm0}",
+                        "bne 3b",
+                        "str {alarm1}, [{intf}]",
+                        st = in(reg) SIO_FIFO_ST,
+                        rd = in(reg) SIO_FIFO_RD,
+                        vld = in(reg) 1u32,
+                        alarm0 = in(reg) ALARM0,
+                        alarm1 = in(reg) ALARM1,
+                        intf = in(reg) TIMER_INTF,
+                        tmp = out(reg) _,
+                    );
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Forces TIMER_IRQ_0 from SRAM3 and waits for the handler
+#[link_section = ".sram3_code"]
+fn measure_on_core1() -> (u32, u32) {
+    unsafe { force_irq(ALARM0, ALARM0) }
 }
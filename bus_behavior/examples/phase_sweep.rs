@@ -0,0 +1,127 @@
+// Phase-offset sweep
+// exp7_1 shows that one NOP on core1 is enough to bring the two cores back in lockstep. This
+// example measures the contention as a function of the skew between the two cores: for every
+// access pattern (read/read, read/write, write/write) both cores execute ACCESSES accesses to the
+// same SRAM4 word, with 0..=MAX_SKEW NOPs inserted before the measurement on core0 (negative
+// offsets) or on core1 (positive offsets).
+//
+// Each point is printed as a `phase_sweep` record, and each pattern as a contention map: the
+// sram4_contested counts for the offsets -MAX_SKEW..=MAX_SKEW. The sweep is repeated at each system
+// clock of `CLOCK_CONFIGS`.
+//
+// # Assumption/Expected result
+// - an access takes 2 cycles, so with an even offset the two cores request SRAM4 on the same
+//   cycle, one access is contested and from then on the cores are one cycle apart (experiment 5):
+//   one contested access, 2 * ACCESSES + 1 cycles on the core that lost the arbitration
+// - with an odd offset the accesses of the two cores interleave, no contested access
+// - an offset larger than the 2 * ACCESSES cycles of the sequence means that the sequences don't
+//   overlap at all
+
+#![no_std]
+#![no_main]
+
+use bus_behavior::clock_config::CLOCK_CONFIGS;
+use bus_behavior::experiment::{self, Bank, BusPriority, CoreSetup, Experiment, Op, Runner};
+use bus_behavior::record::Counts;
+use bus_behavior::verdict::Expectation;
+use bus_behavior::PerfEvent;
+use defmt_rtt as _;
+use panic_probe as _;
+
+#[link_section = ".boot2"]
+#[used]
+pub static BOOT2: [u8; 256] = rp2040_boot2::BOOT_LOADER_GENERIC_03H;
+
+/// Accesses executed by each core
+const ACCESSES: usize = 10;
+/// Largest number of NOPs inserted on one core
+const MAX_SKEW: usize = 24;
+/// Number of offsets of a contention map, -MAX_SKEW..=MAX_SKEW
+const OFFSETS: usize = 2 * MAX_SKEW + 1;
+
+const READS: [Op; ACCESSES] = [Op::Ldr; ACCESSES];
+const WRITES: [Op; ACCESSES] = [Op::Str; ACCESSES];
+
+/// (name, core0 operations, core1 operations)
+const PATTERNS: [(&str, &[Op], &[Op]); 3] = [
+    ("read_read", &READS, &READS),
+    ("read_write", &READS, &WRITES),
+    ("write_write", &WRITES, &WRITES),
+];
+
+const PERF_EVENTS: [PerfEvent; 4] = [
+    PerfEvent::Sram4Contested,
+    PerfEvent::Sram4,
+    PerfEvent::Sram2Contested,
+    PerfEvent::Sram3Contested,
+];
+
+#[rp2040_hal::entry]
+fn main() -> ! {
+    experiment::with_runner(sweep)
+}
+
+fn sweep(runner: &mut Runner) {
+    for config in &CLOCK_CONFIGS {
+        runner.set_clock(config);
+        sweep_patterns(runner);
+    }
+}
+
+fn sweep_patterns(runner: &mut Runner) {
+    let sys_mhz = runner.clock().sys_mhz;
+    for (pattern, core0_ops, core1_ops) in PATTERNS {
+        let mut contention_map = [0u32; OFFSETS];
+        for (i, contested) in contention_map.iter_mut().enumerate() {
+            let offset = i as i32 - MAX_SKEW as i32;
+            let experiment = Experiment {
+                name: "phase_sweep",
+                description: pattern,
+                bus_priority: BusPriority::CORES_HIGH,
+                initial_value: 77,
+                perf_events: PERF_EVENTS,
+                cores: [
+                    CoreSetup {
+                        ops: core0_ops,
+                        value: 0,
+                        data: Bank::Sram4,
+                        code: Bank::Sram2,
+                        vector_table: Bank::Sram2,
+                        padding: (-offset).max(0) as usize,
+                    },
+                    CoreSetup {
+                        ops: core1_ops,
+                        value: 7,
+                        data: Bank::Sram4,
+                        code: Bank::Sram3,
+                        vector_table: Bank::Sram3,
+                        padding: offset.max(0) as usize,
+                    },
+                ],
+                expected: Expectation::NONE,
+            };
+            let measurement = runner.measure(&experiment);
+            *contested = measurement.counts.counts[0];
+
+            defmt::println!(
+                "@phase_sweep sys_mhz={=u32} pattern={=str} offset={=i32} core0_padding={=usize} core1_padding={=usize} core0_cycles={=u32} core1_cycles={=u32} {}",
+                sys_mhz,
+                pattern,
+                offset,
+                experiment.cores[0].padding,
+                experiment.cores[1].padding,
+                measurement.cycles[0],
+                measurement.cycles[1],
+                Counts(&measurement.counts)
+            );
+        }
+        defmt::println!(
+            "{=u32} MHz {=str}: sram4_contested for offsets -{=usize}..={=usize}: {=[?]}",
+            sys_mhz,
+            pattern,
+            MAX_SKEW,
+            MAX_SKEW,
+            &contention_map[..]
+        );
+    }
+}
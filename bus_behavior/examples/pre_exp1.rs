@@ -25,14 +25,14 @@
 #![no_std]
 #![no_main]
 
-use bus_behavior::{hundred_nops, hundred_reads, hundred_writes, systic_init};
+use bus_behavior::{hundred_nops, hundred_reads, hundred_writes, stack, CycleCounter};
 use cortex_m::asm;
 use defmt::*;
 use defmt_rtt as _;
 
 use embedded_hal::digital::v2::ToggleableOutputPin;
 use hal::{
-    multicore::{Multicore, Stack},
+    multicore::Multicore,
     pac::{self},
     Clock,
 };
@@ -40,7 +40,6 @@ use panic_probe as _;
 use rp2040_hal as hal;
 
 const XTAL_FREQ_HZ: u32 = 12_000_000u32;
-static mut CORE1_STACK: Stack<4096> = Stack::new();
 
 #[link_section = ".boot2"]
 #[used]
@@ -70,7 +69,8 @@ fn main1() -> ! {
 
     println!("Running at {} MHz", clocks.system_clock.freq().to_MHz());
     // configure systic to prepare for measurements
-    systic_init();
+    let core = pac::CorePeripherals::take().unwrap();
+    let _cycle_counter = CycleCounter::new(core.SYST);
 
     // measure clock cycles for 100 reads & 100 writes on core 0 before waking up core 1
     println!(
@@ -83,10 +83,11 @@ fn main1() -> ! {
     );
     println!("100 NOPs   on core 0 => {} clock cycles", hundred_nops());
 
+    stack::install_guard();
     let mut mc = Multicore::new(&mut pac.PSM, &mut pac.PPB, &mut sio.fifo);
     let cores = mc.cores();
     let core1 = &mut cores[1];
-    let _ = core1.spawn(unsafe { &mut CORE1_STACK.mem }, || main2());
+    let _ = core1.spawn(unsafe { stack::core1_stack() }, || main2());
 
     // configure bus priorities to max for core 0 and core 1
     pac.RESETS.reset.modify(|_, w| w.busctrl().clear_bit()); // Take BUSCTRL out of reset mode
@@ -113,8 +114,10 @@ fn main1() -> ! {
 }
 
 fn main2() -> ! {
+    stack::install_guard();
     // configure systic to prepare for measurements
-    systic_init();
+    let core = unsafe { pac::CorePeripherals::steal() };
+    let _cycle_counter = CycleCounter::new(core.SYST);
     // measure clock cycles for 100 reads & 100 writes on Core 1
     println!(
         "100 reads  on core 1 => {} clock cycles",
@@ -0,0 +1,253 @@
+// Memory-region access latency matrix
+// Measure the load and store latency of every memory region of the rp2040 from both cores, using
+// the same Systick technique as `pre_exp1.rs`, and print the results as one markdown table.
+//
+// Each access is repeated 10 times, the table shows the average cycles per access. Only one core
+// measures at a time, the other one sleeps in WFE so it neither fetches code nor accesses memory.
+// Stores write back the value that was read right before the measurement, so they don't corrupt
+// whatever lives at the measured address (.data, registers...). ROM and flash are only read.
+//
+// # Assumption/Expected result
+// - SRAM (striped, non-striped, SRAM4/5), USB DPRAM, ROM and XIP cache hits: 2 cycles, same as
+//   Table1, since those are zero wait-state slaves of the bus fabric
+// - SIO: 1 cycle, it is attached directly to each core's single-cycle IO port instead of the bus fabric
+// - APB peripherals: more than 2 cycles, as every access goes through the AHB to APB bridge
+// - XIP_NOALLOC: tens of cycles. This alias still hits when the line is cached and only skips the
+//   allocation on a miss, so the cache is flushed before its row and every access misses
+// - XIP_NOCACHE and XIP_NOCACHE_NOALLOC: tens of cycles, they don't check the cache and every
+//   access becomes a QSPI transfer
+
+#![no_std]
+#![no_main]
+
+use bus_behavior::{measure_cycles, stack, CycleCounter};
+use cortex_m::asm;
+use defmt::*;
+use defmt_rtt as _;
+
+use embedded_hal::digital::v2::ToggleableOutputPin;
+use hal::{
+    multicore::Multicore,
+    pac::{self},
+    sio::SioFifo,
+    Clock, Sio,
+};
+use panic_probe as _;
+use rp2040_hal as hal;
+
+const XTAL_FREQ_HZ: u32 = 12_000_000u32;
+
+#[link_section = ".boot2"]
+#[used]
+pub static BOOT2: [u8; 256] = rp2040_boot2::BOOT_LOADER_GENERIC_03H;
+
+const CORE1_DONE: u32 = 7;
+
+/// Number of times each access is repeated in a measurement, this must match the count given
+/// to `measure_cycles!` in `measure_region()`
+const REPEAT: u32 = 10;
+
+/// Word that lives in flash, its address is used to measure the XIP aliases
+static FLASH_WORD: u32 = 0x1234_5678;
+
+/// Writing it invalidates the whole XIP cache, reading it stalls until the flush is complete
+const XIP_CTRL_FLUSH: *mut u32 = 0x1400_0004 as *mut u32;
+
+struct Region {
+    name: &'static str,
+    addr: *mut u32,
+    writable: bool,
+    /// The XIP cache is flushed before the measurement
+    flush: bool,
+}
+
+const REGIONS: usize = 13;
+
+fn regions() -> [Region; REGIONS] {
+    // offset of FLASH_WORD inside the 16MB flash window, to build the address in each XIP alias
+    let flash_offset = core::ptr::addr_of!(FLASH_WORD) as u32 & 0x00FF_FFFF;
+    let region = |name, addr: u32, writable| Region {
+        name,
+        addr: addr as *mut u32,
+        writable,
+        flush: false,
+    };
+    [
+        region("ROM", 0x0000_0010, false),
+        region("XIP flash (cached)", 0x1000_0000 | flash_offset, false),
+        Region {
+            flush: true,
+            ..region("XIP_NOALLOC (flushed)", 0x1100_0000 | flash_offset, false)
+        },
+        region("XIP_NOCACHE", 0x1200_0000 | flash_offset, false),
+        region("XIP_NOCACHE_NOALLOC", 0x1300_0000 | flash_offset, false),
+        region("SRAM striped", 0x2000_0000, true),
+        region("SRAM0 non-striped alias", 0x2100_0000, true),
+        region("SRAM4", 0x2004_0000, true),
+        region("SRAM5", 0x2004_1000, true),
+        region("USB DPRAM", 0x5010_0000, true),
+        region("SIO (INTERP0_ACCUM0)", 0xd000_0080, true),
+        region("AHB-Lite (DMA CH0_READ_ADDR)", 0x5000_0000, true),
+        region("APB (WATCHDOG SCRATCH0)", 0x4005_800c, true),
+    ]
+}
+
+/// Total cycles for `REPEAT` loads and `REPEAT` stores of each region, per core
+static mut RESULTS: [[(u32, Option<u32>); REGIONS]; 2] = [[(0, None); REGIONS]; 2];
+
+#[rp2040_hal::entry]
+fn main1() -> ! {
+    // Grab our singleton objects
+    let mut pac = pac::Peripherals::take().unwrap();
+    let mut sio = hal::Sio::new(pac.SIO);
+
+    // Set up the watchdog driver - needed by the clock setup code
+    let mut watchdog = hal::Watchdog::new(pac.WATCHDOG);
+
+    // Configure the clocks
+    let clocks = hal::clocks::init_clocks_and_plls(
+        XTAL_FREQ_HZ,
+        pac.XOSC,
+        pac.CLOCKS,
+        pac.PLL_SYS,
+        pac.PLL_USB,
+        &mut pac.RESETS,
+        &mut watchdog,
+    )
+    .ok()
+    .unwrap();
+
+    println!("Running at {} MHz", clocks.system_clock.freq().to_MHz());
+
+    // USB DPRAM and DMA registers can only be accessed when those blocks are out of reset
+    pac.RESETS
+        .reset
+        .modify(|_, w| w.usbctrl().clear_bit().dma().clear_bit());
+    while pac.RESETS.reset_done.read().usbctrl().bit_is_clear()
+        || pac.RESETS.reset_done.read().dma().bit_is_clear()
+    {}
+
+    // configure systic to prepare for measurements
+    let core = pac::CorePeripherals::take().unwrap();
+    let _cycle_counter = CycleCounter::new(core.SYST);
+
+    // measure on core 0 before waking up core 1
+    measure_regions(unsafe { &mut RESULTS[0] });
+
+    stack::install_guard();
+    let mut mc = Multicore::new(&mut pac.PSM, &mut pac.PPB, &mut sio.fifo);
+    let cores = mc.cores();
+    let core1 = &mut cores[1];
+    let _ = core1.spawn(unsafe { stack::core1_stack() }, || main2());
+
+    // wait for Core 1 to finish its measurements
+    while wait_for_other_core(&mut sio.fifo) != CORE1_DONE {}
+
+    println!("| Region | Address | Core 0 LDR | Core 0 STR | Core 1 LDR | Core 1 STR |");
+    println!("| ------ | ------- | ---------- | ---------- | ---------- | ---------- |");
+    for (i, region) in regions().iter().enumerate() {
+        let ((load0, store0), (load1, store1)) = unsafe { (RESULTS[0][i], RESULTS[1][i]) };
+        let per_access = |cycles: u32| cycles as f32 / REPEAT as f32;
+        match (store0, store1) {
+            (Some(store0), Some(store1)) => println!(
+                "| {} | {:#x} | {} | {} | {} | {} |",
+                region.name,
+                region.addr as u32,
+                per_access(load0),
+                per_access(store0),
+                per_access(load1),
+                per_access(store1)
+            ),
+            _ => println!(
+                "| {} | {:#x} | {} | - | {} | - |",
+                region.name,
+                region.addr as u32,
+                per_access(load0),
+                per_access(load1)
+            ),
+        }
+    }
+
+    // Set the pins to their default state
+    let pins = hal::gpio::Pins::new(
+        pac.IO_BANK0,
+        pac.PADS_BANK0,
+        sio.gpio_bank0,
+        &mut pac.RESETS,
+    );
+    // Configure GPIO25 as an output
+    let mut led_pin = pins.gpio25.into_push_pull_output();
+
+    // sign of life
+    loop {
+        led_pin.toggle().unwrap();
+        asm::delay(12_500_000u32);
+    }
+}
+
+fn main2() -> ! {
+    stack::install_guard();
+    let pac = unsafe { pac::Peripherals::steal() };
+    let mut sio = Sio::new(pac.SIO);
+
+    // configure systic to prepare for measurements
+    let core = unsafe { pac::CorePeripherals::steal() };
+    let _cycle_counter = CycleCounter::new(core.SYST);
+
+    measure_regions(unsafe { &mut RESULTS[1] });
+    sio.fifo.write_blocking(CORE1_DONE);
+    // wake up core 0 from WFE
+    asm::sev();
+
+    loop {
+        asm::wfe();
+    }
+}
+
+/// Waits in WFE for a message from the other core, so that this core does not fetch code nor
+/// access memory while the other core is measuring. The sender must execute SEV after writing.
+fn wait_for_other_core(fifo: &mut SioFifo) -> u32 {
+    loop {
+        if let Some(msg) = fifo.read() {
+            return msg;
+        }
+        asm::wfe();
+    }
+}
+
+fn measure_regions(results: &mut [(u32, Option<u32>); REGIONS]) {
+    for (result, region) in results.iter_mut().zip(regions().iter()) {
+        if region.flush {
+            unsafe {
+                XIP_CTRL_FLUSH.write_volatile(1);
+                let _ = XIP_CTRL_FLUSH.read_volatile();
+            }
+        }
+        // a first access to make sure flash is in the XIP cache, it is not measured. It doesn't
+        // allocate through the NOALLOC aliases, which keep missing after a flush
+        let _ = unsafe { region.addr.read_volatile() };
+        *result = measure_region(region.addr, region.writable);
+    }
+}
+
+/// Measures the total clock cycles taken by `REPEAT` loads and, if the region is writable,
+/// `REPEAT` stores to `addr`
+#[link_section = ".sram4_code"]
+#[inline(never)]
+fn measure_region(addr: *mut u32, writable: bool) -> (u32, Option<u32>) {
+    unsafe {
+        let loads = measure_cycles!(10, "ldr {v}, [{p}]", p = in(reg) addr, v = out(reg) _);
+        let stores = if writable {
+            let value = addr.read_volatile();
+            Some(measure_cycles!(
+                10,
+                "str {v}, [{p}]",
+                p = in(reg) addr,
+                v = in(reg) value,
+            ))
+        } else {
+            None
+        };
+        (loads, stores)
+    }
+}
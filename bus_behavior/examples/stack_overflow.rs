@@ -0,0 +1,105 @@
+// Stack overflow detection
+// Recurse on core0 until the stack reaches its margin, the lowest `stack::MARGIN` bytes above the
+// MPU guard, then report the overflow with `stack::check()`.
+//
+// The recursion stops as soon as SP is in the margin. A frame of `recurse()` and the calls it
+// makes take less than the margin, so the deepest one never reaches the guard. Without this stop
+// the next pushes would hit the guard and lock the core up (see src/stack.rs), and the report
+// would never be printed.
+//
+// # Assumption/Expected result
+// - before the recursion, `high_water_mark` is the depth used by the setup, far from the margin
+// - after it, `check()` fails and prints
+//   `stack overflow on core0: <used> of 8192 bytes used, <n> bytes in the margin`, with `<n>`
+//   between 1 and `stack::MARGIN`
+
+#![no_std]
+#![no_main]
+
+use bus_behavior::stack;
+use cortex_m::{asm, register::msp};
+use defmt::*;
+use defmt_rtt as _;
+
+use embedded_hal::digital::v2::ToggleableOutputPin;
+use hal::{pac, Clock};
+use panic_probe as _;
+use rp2040_hal as hal;
+
+const XTAL_FREQ_HZ: u32 = 12_000_000u32;
+
+#[link_section = ".boot2"]
+#[used]
+pub static BOOT2: [u8; 256] = rp2040_boot2::BOOT_LOADER_GENERIC_03H;
+
+/// Calls itself until SP is below `limit`, each frame writes a few words of the stack so that the
+/// paint of the margin is overwritten. Returns the number of frames.
+#[inline(never)]
+fn recurse(limit: u32) -> u32 {
+    let mut words = [0u32; 4];
+    core::hint::black_box(&mut words);
+    if msp::read() < limit {
+        return 1;
+    }
+    recurse(limit) + 1
+}
+
+#[rp2040_hal::entry]
+fn main() -> ! {
+    // Grab our singleton objects
+    let mut pac = pac::Peripherals::take().unwrap();
+    let sio = hal::Sio::new(pac.SIO);
+
+    // Set up the watchdog driver - needed by the clock setup code
+    let mut watchdog = hal::Watchdog::new(pac.WATCHDOG);
+
+    // Configure the clocks
+    let clocks = hal::clocks::init_clocks_and_plls(
+        XTAL_FREQ_HZ,
+        pac.XOSC,
+        pac.CLOCKS,
+        pac.PLL_SYS,
+        pac.PLL_USB,
+        &mut pac.RESETS,
+        &mut watchdog,
+    )
+    .ok()
+    .unwrap();
+
+    println!("Running at {} MHz", clocks.system_clock.freq().to_MHz());
+
+    stack::install_guard();
+    let bounds = stack::bounds(0);
+    println!(
+        "core0 stack: {=u32} of {=u32} bytes used",
+        stack::high_water_mark(0),
+        bounds.size()
+    );
+
+    let frames = recurse(bounds.bottom + stack::MARGIN);
+    println!("{=u32} frames down to the margin", frames);
+    match stack::check(0) {
+        Ok(used) => error!(
+            "no overflow reported, {=u32} of {=u32} bytes used",
+            used,
+            bounds.size()
+        ),
+        Err(overflow) => println!("{}", overflow),
+    }
+
+    // Set the pins to their default state
+    let pins = hal::gpio::Pins::new(
+        pac.IO_BANK0,
+        pac.PADS_BANK0,
+        sio.gpio_bank0,
+        &mut pac.RESETS,
+    );
+    // Configure GPIO25 as an output
+    let mut led_pin = pins.gpio25.into_push_pull_output();
+
+    // sign of life
+    loop {
+        led_pin.toggle().unwrap();
+        asm::delay(12_500_000u32);
+    }
+}
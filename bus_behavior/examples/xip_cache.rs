@@ -0,0 +1,368 @@
+// XIP cache characterization
+// Most of our firmware executes from flash through the XIP cache, this example measures:
+// 1. the cost of a data load that misses the cache vs one that hits it
+// 2. the cost of executing code from flash with a cold cache vs a warm cache
+// 3. the cost of flushing the cache through XIP_CTRL FLUSH
+// 4. load/store latency of the cache memory used as SRAM (cache disabled, mapped at 0x15000000),
+//    and of a flash load while the cache is disabled
+// 5. two cores thrashing the shared 16 KB cache: each core reads its own 16 KB flash buffer, one
+//    load per 8 byte cache line, first alone and then both at the same time.
+//
+// The measuring code is in SRAM2 (core0) and SRAM3 (core1), so only the measured accesses go
+// through XIP. Bus performance counters count the XIP_MAIN accesses and the ones delayed by the
+// other master (xip_main_contested), as well as the contested accesses to SRAM2/SRAM3 to check that
+// the code fetches of the two cores don't interfere. XIP_CTRL counters give the cache hit rate.
+//
+// # Assumption/Expected result
+// - a cache hit costs 2 cycles like any other zero wait-state slave
+// - a miss costs a QSPI transfer, a few tens of cycles depending on the boot2 clock divisor
+// - when both cores run at the same time their working sets (32 KB) don't fit the cache anymore,
+//   so most accesses miss and the hit rate of the concurrent run is much lower than for one core
+
+#![no_std]
+#![no_main]
+
+use bus_behavior::{measure_cycles, stack, CycleCounter, PerfCounters, PerfEvent};
+use cortex_m::asm;
+use defmt::*;
+use defmt_rtt as _;
+
+use embedded_hal::digital::v2::ToggleableOutputPin;
+use hal::{
+    multicore::Multicore,
+    pac::{self},
+    sio::SioFifo,
+    Clock, Sio,
+};
+use panic_probe as _;
+use rp2040_hal as hal;
+
+const XTAL_FREQ_HZ: u32 = 12_000_000u32;
+
+#[link_section = ".boot2"]
+#[used]
+pub static BOOT2: [u8; 256] = rp2040_boot2::BOOT_LOADER_GENERIC_03H;
+
+const CORE1_READY: u32 = 7;
+const START_THRASHING: u32 = 8;
+const CORE1_DONE: u32 = 9;
+
+// XIP_CTRL registers
+const XIP_CTRL_CTRL: *mut u32 = 0x1400_0000 as *mut u32;
+const XIP_CTRL_CTRL_EN: u32 = 1 << 0;
+const XIP_CTRL_FLUSH: *mut u32 = 0x1400_0004 as *mut u32;
+const XIP_CTRL_CTR_HIT: *mut u32 = 0x1400_000c as *mut u32;
+const XIP_CTRL_CTR_ACC: *mut u32 = 0x1400_0010 as *mut u32;
+/// The cache memory, accessible as SRAM when the cache is disabled
+const XIP_SRAM_BASE: *mut u32 = 0x1500_0000 as *mut u32;
+
+/// Size of a XIP cache line
+const CACHE_LINE: usize = 8;
+/// Each core reads 16 KB, the size of the whole cache
+const BUFFER_WORDS: usize = 16 * 1024 / 4;
+/// Number of times the buffers are read in the thrashing experiment
+const PASSES: usize = 4;
+
+// buffers in flash, not zero so they are not mistaken for .bss
+static FLASH_BUFFER0: [u32; BUFFER_WORDS] = [0xc0c0_c0c0; BUFFER_WORDS];
+static FLASH_BUFFER1: [u32; BUFFER_WORDS] = [0xc1c1_c1c1; BUFFER_WORDS];
+
+/// Cycles taken by each pass of core1 over its buffer while thrashing
+static mut CORE1_PASSES: [u32; PASSES] = [0; PASSES];
+
+#[rp2040_hal::entry]
+#[link_section = ".sram2_code"]
+fn main1() -> ! {
+    // Grab our singleton objects
+    let mut pac = pac::Peripherals::take().unwrap();
+    let mut sio = hal::Sio::new(pac.SIO);
+
+    // Set up the watchdog driver - needed by the clock setup code
+    let mut watchdog = hal::Watchdog::new(pac.WATCHDOG);
+
+    // Configure the clocks
+    let clocks = hal::clocks::init_clocks_and_plls(
+        XTAL_FREQ_HZ,
+        pac.XOSC,
+        pac.CLOCKS,
+        pac.PLL_SYS,
+        pac.PLL_USB,
+        &mut pac.RESETS,
+        &mut watchdog,
+    )
+    .ok()
+    .unwrap();
+    println!("Running at {} MHz", clocks.system_clock.freq().to_MHz());
+
+    // configure systic to prepare for measurements
+    let core = pac::CorePeripherals::take().unwrap();
+    let _cycle_counter = CycleCounter::new(core.SYST);
+
+    // configure performance counters to count all XIP accesses and the contested ones
+    let mut perf_counters = PerfCounters::new(
+        pac.BUSCTRL,
+        &mut pac.RESETS,
+        [
+            PerfEvent::XipMainContested,
+            PerfEvent::XipMain,
+            PerfEvent::Sram2Contested,
+            PerfEvent::Sram3Contested,
+        ],
+    );
+
+    //=================== 1. data load miss vs hit ===================
+    let flash_word = FLASH_BUFFER0.as_ptr();
+    flush_xip_cache();
+    let miss = load_core0(flash_word);
+    let hit = load_core0(flash_word);
+    println!("flash load, cache miss          = {} clock cycles", miss);
+    println!("flash load, cache hit           = {} clock cycles", hit);
+
+    //=================== 2. code execution cold vs warm ===================
+    flush_xip_cache();
+    let cold = hundred_nops_from_flash();
+    let warm = hundred_nops_from_flash();
+    println!("100 NOPs from flash, cold cache = {} clock cycles", cold);
+    println!("100 NOPs from flash, warm cache = {} clock cycles", warm);
+
+    //=================== 3. flush ===================
+    println!(
+        "XIP cache flush                 = {} clock cycles",
+        flush_cycles()
+    );
+
+    //=================== 4. cache as SRAM ===================
+    let (sram_load, sram_store, uncached_load) = cache_as_sram();
+    println!(
+        "cache-as-SRAM load              = {} clock cycles",
+        sram_load
+    );
+    println!(
+        "cache-as-SRAM store             = {} clock cycles",
+        sram_store
+    );
+    println!(
+        "flash load, cache disabled      = {} clock cycles",
+        uncached_load
+    );
+
+    //=================== 5. thrashing ===================
+    // reference: core0 alone, first pass misses on every line, the next ones should all hit
+    let mut alone = [0; PASSES];
+    flush_xip_cache();
+    perf_counters.reset();
+    reset_cache_counters();
+    // index loops, the iterator methods would be fetched from flash at opt-level 0
+    let mut pass = 0;
+    while pass < PASSES {
+        alone[pass] = read_buffer_core0(FLASH_BUFFER0.as_ptr());
+        pass += 1;
+    }
+    let alone_counts = perf_counters.read();
+    let (alone_hits, alone_lookups) = read_cache_counters();
+
+    // start core1 and wait until it is ready
+    stack::install_guard();
+    let mut mc = Multicore::new(&mut pac.PSM, &mut pac.PPB, &mut sio.fifo);
+    let cores = mc.cores();
+    let core1 = &mut cores[1];
+    let _ = core1.spawn(unsafe { stack::core1_stack() }, || main2());
+    while wait_for_other_core(&mut sio.fifo) != CORE1_READY {}
+
+    let mut concurrent = [0; PASSES];
+    flush_xip_cache();
+    perf_counters.reset();
+    reset_cache_counters();
+    sio.fifo.write(START_THRASHING);
+    asm::sev();
+    let mut pass = 0;
+    while pass < PASSES {
+        concurrent[pass] = read_buffer_core0(FLASH_BUFFER0.as_ptr());
+        pass += 1;
+    }
+    while wait_for_other_core(&mut sio.fifo) != CORE1_DONE {}
+    let counts = perf_counters.read();
+    let (hits, lookups) = read_cache_counters();
+
+    println!(
+        "16 KB read by core0 alone, per pass           = {} clock cycles",
+        alone
+    );
+    println!(
+        "  {}, cache hits {} / {}",
+        alone_counts, alone_hits, alone_lookups
+    );
+    println!(
+        "16 KB read by core0 while thrashing, per pass = {} clock cycles",
+        concurrent
+    );
+    println!(
+        "16 KB read by core1 while thrashing, per pass = {} clock cycles",
+        unsafe { CORE1_PASSES }
+    );
+    println!("  {}, cache hits {} / {}", counts, hits, lookups);
+
+    // config led pin
+    let pins = hal::gpio::Pins::new(
+        pac.IO_BANK0,
+        pac.PADS_BANK0,
+        sio.gpio_bank0,
+        &mut pac.RESETS,
+    );
+    let mut led_pin = pins.gpio25.into_push_pull_output();
+    loop {
+        // sign of life
+        led_pin.toggle().unwrap();
+        asm::delay(12_500_000u32);
+    }
+}
+
+#[link_section = ".sram3_code"]
+fn main2() -> ! {
+    stack::install_guard();
+    let pac = unsafe { pac::Peripherals::steal() };
+    let mut sio = Sio::new(pac.SIO);
+
+    // configure systic to prepare for measurements
+    let core = unsafe { pac::CorePeripherals::steal() };
+    let _cycle_counter = CycleCounter::new(core.SYST);
+
+    sio.fifo.write_blocking(CORE1_READY);
+    asm::sev();
+    while wait_for_other_core(&mut sio.fifo) != START_THRASHING {}
+
+    let mut pass = 0;
+    while pass < PASSES {
+        let cycles = read_buffer_core1(FLASH_BUFFER1.as_ptr());
+        unsafe { CORE1_PASSES[pass] = cycles };
+        pass += 1;
+    }
+
+    sio.fifo.write_blocking(CORE1_DONE);
+    asm::sev();
+    loop {
+        asm::wfe();
+    }
+}
+
+/// Waits in WFE for a message from the other core, the sender must execute SEV after writing.
+#[link_section = ".sram2_code"]
+fn wait_for_other_core(fifo: &mut SioFifo) -> u32 {
+    loop {
+        if let Some(msg) = fifo.read() {
+            return msg;
+        }
+        asm::wfe();
+    }
+}
+
+/// Invalidates the whole XIP cache, reading FLUSH stalls until the flush is complete
+#[link_section = ".sram2_code"]
+fn flush_xip_cache() {
+    unsafe {
+        XIP_CTRL_FLUSH.write_volatile(1);
+        let _ = XIP_CTRL_FLUSH.read_volatile();
+    }
+}
+
+/// Resets the XIP cache hit/access counters
+#[link_section = ".sram2_code"]
+fn reset_cache_counters() {
+    unsafe {
+        // writing any value clears these counters
+        XIP_CTRL_CTR_HIT.write_volatile(0);
+        XIP_CTRL_CTR_ACC.write_volatile(0);
+    }
+}
+
+/// Returns (cache hits, cache accesses)
+#[link_section = ".sram2_code"]
+fn read_cache_counters() -> (u32, u32) {
+    unsafe {
+        (
+            XIP_CTRL_CTR_HIT.read_volatile(),
+            XIP_CTRL_CTR_ACC.read_volatile(),
+        )
+    }
+}
+
+#[link_section = ".sram2_code"]
+#[inline(never)]
+fn load_core0(ptr: *const u32) -> u32 {
+    unsafe { measure_cycles!(1, "ldr {v}, [{p}]", p = in(reg) ptr, v = out(reg) _) }
+}
+
+/// Left in flash on purpose, so the NOPs are fetched through XIP
+#[inline(never)]
+fn hundred_nops_from_flash() -> u32 {
+    unsafe { measure_cycles!(100, "nop") }
+}
+
+#[link_section = ".sram2_code"]
+#[inline(never)]
+fn flush_cycles() -> u32 {
+    unsafe {
+        measure_cycles!(
+            1,
+            "str {one}, [{flush}]\nldr {v}, [{flush}]",
+            flush = in(reg) XIP_CTRL_FLUSH,
+            one = in(reg) 1u32,
+            v = out(reg) _,
+        )
+    }
+}
+
+/// Disables the cache, measures a load and a store to the cache memory and a load from flash,
+/// then enables the cache again. Returns (cache-as-SRAM load, cache-as-SRAM store, flash load)
+#[link_section = ".sram2_code"]
+#[inline(never)]
+fn cache_as_sram() -> (u32, u32, u32) {
+    unsafe {
+        let ctrl = XIP_CTRL_CTRL.read_volatile();
+        XIP_CTRL_CTRL.write_volatile(ctrl & !XIP_CTRL_CTRL_EN);
+
+        let load = measure_cycles!(1, "ldr {v}, [{p}]", p = in(reg) XIP_SRAM_BASE, v = out(reg) _);
+        let store = measure_cycles!(
+            1,
+            "str {v}, [{p}]",
+            p = in(reg) XIP_SRAM_BASE,
+            v = in(reg) 77u32,
+        );
+        let uncached = load_core0(FLASH_BUFFER0.as_ptr());
+
+        // the cache memory has been used as SRAM, so its content must be invalidated
+        XIP_CTRL_CTRL.write_volatile(ctrl);
+        flush_xip_cache();
+        (load, store, uncached)
+    }
+}
+
+/// Reads one word per cache line of a 16 KB flash buffer, returns the cycles taken
+#[link_section = ".sram2_code"]
+#[inline(never)]
+fn read_buffer_core0(buffer: *const u32) -> u32 {
+    unsafe {
+        measure_cycles!(
+            1,
+            "2:\nldr {v}, [{p}]\nadds {p}, #8\nsubs {n}, #1\nbne 2b",
+            p = inout(reg) buffer => _,
+            n = inout(reg) (BUFFER_WORDS * 4 / CACHE_LINE) as u32 => _,
+            v = out(reg) _,
+        )
+    }
+}
+
+/// Same as `read_buffer_core0()`, but in SRAM3 so that the two cores don't fetch from the same bank
+#[link_section = ".sram3_code"]
+#[inline(never)]
+fn read_buffer_core1(buffer: *const u32) -> u32 {
+    unsafe {
+        measure_cycles!(
+            1,
+            "2:\nldr {v}, [{p}]\nadds {p}, #8\nsubs {n}, #1\nbne 2b",
+            p = inout(reg) buffer => _,
+            n = inout(reg) (BUFFER_WORDS * 4 / CACHE_LINE) as u32 => _,
+            v = out(reg) _,
+        )
+    }
+}
@@ -0,0 +1,292 @@
+//! System clock configurations the experiments can be repeated at.
+//!
+//! The cycle counts of the README are measured at 125 MHz, the default of `init_clocks_and_plls`.
+//! A `ClockConfig` gives, for one system clock frequency, the PLL_SYS settings, the core voltage
+//! it needs and the divisor of the flash clock (SSI BAUDR, set to 4 by boot2), so that the
+//! experiments can be repeated at every clock in `CLOCK_CONFIGS` from the same binary and their
+//! records keyed by frequency.
+//!
+//! ```ignore
+//! let mut clock = SystemClock::init(pac.XOSC, pac.CLOCKS, pac.PLL_SYS, pac.PLL_USB, &mut pac.RESETS, &mut watchdog);
+//! for config in &CLOCK_CONFIGS {
+//!     lockstep::with_core1_parked(|| clock.set(config, &mut pac.RESETS));
+//!     ...
+//! }
+//! ```
+
+use cortex_m::{asm, interrupt, register::primask};
+use fugit::HertzU32;
+use rp2040_hal::{
+    clocks::{Clock, ClockSource, ClocksManager},
+    pac,
+    pll::{self, common_configs::PLL_USB_48MHZ, PLLConfig},
+    xosc::{self, CrystalOscillator, Stable},
+    Watchdog,
+};
+
+/// External high-speed crystal on the Raspberry Pi Pico board is 12 MHz.
+const XTAL_FREQ_HZ: u32 = 12_000_000u32;
+
+/// Base address of the XIP SSI, the flash interface
+const XIP_SSI_BASE: u32 = 0x1800_0000;
+
+/// Highest flash clock, the 03h read command of boot2 is specified up to 50 MHz for the W25Q16JV
+/// of the Pico
+const MAX_FLASH_MHZ: u32 = 50;
+
+/// Time given to the regulator to settle after raising the core voltage
+const VREG_SETTLE_US: u32 = 10_000;
+
+/// One system clock configuration
+#[derive(Clone, Copy, Debug, PartialEq, Eq, defmt::Format)]
+pub struct ClockConfig {
+    /// System clock frequency, the key of the records
+    pub sys_mhz: u32,
+    /// PLL_SYS VCO frequency, 12 MHz * FBDIV
+    pub vco_mhz: u32,
+    pub post_div1: u8,
+    pub post_div2: u8,
+    /// Core voltage, 850 to 1300 mV by steps of 50 mV
+    pub vreg_mv: u32,
+    /// Divisor of the system clock giving the flash clock, even
+    pub flash_divisor: u32,
+}
+
+impl ClockConfig {
+    /// VSEL field of the VREG register
+    fn vsel(&self) -> u32 {
+        (self.vreg_mv - 850) / 50 + 6
+    }
+
+    fn pll(&self) -> PLLConfig {
+        PLLConfig {
+            vco_freq: HertzU32::MHz(self.vco_mhz),
+            refdiv: 1,
+            post_div1: self.post_div1,
+            post_div2: self.post_div2,
+        }
+    }
+
+    /// Flash clock in MHz
+    pub fn flash_mhz(&self) -> u32 {
+        self.sys_mhz / self.flash_divisor
+    }
+}
+
+/// The configuration set by `init_clocks_and_plls` and boot2, the one the README results are
+/// measured at
+pub const SYS_125MHZ: ClockConfig = ClockConfig {
+    sys_mhz: 125,
+    vco_mhz: 1500,
+    post_div1: 6,
+    post_div2: 2,
+    vreg_mv: 1100,
+    flash_divisor: 4,
+};
+
+/// From the lowest clock usable with USB to overclocked frequencies. Above 133 MHz the core
+/// voltage is raised, and the flash divisor keeps the flash clock at or below `MAX_FLASH_MHZ`.
+pub const CLOCK_CONFIGS: [ClockConfig; 6] = [
+    ClockConfig {
+        sys_mhz: 48,
+        vco_mhz: 1440,
+        post_div1: 6,
+        post_div2: 5,
+        vreg_mv: 1100,
+        flash_divisor: 2,
+    },
+    SYS_125MHZ,
+    ClockConfig {
+        sys_mhz: 133,
+        vco_mhz: 1596,
+        post_div1: 6,
+        post_div2: 2,
+        vreg_mv: 1100,
+        flash_divisor: 4,
+    },
+    ClockConfig {
+        sys_mhz: 200,
+        vco_mhz: 1200,
+        post_div1: 6,
+        post_div2: 1,
+        vreg_mv: 1150,
+        flash_divisor: 4,
+    },
+    ClockConfig {
+        sys_mhz: 250,
+        vco_mhz: 1500,
+        post_div1: 6,
+        post_div2: 1,
+        vreg_mv: 1200,
+        flash_divisor: 6,
+    },
+    ClockConfig {
+        sys_mhz: 300,
+        vco_mhz: 1500,
+        post_div1: 5,
+        post_div2: 1,
+        vreg_mv: 1300,
+        flash_divisor: 6,
+    },
+];
+
+/// The system clock and the configuration it runs at
+pub struct SystemClock {
+    xosc: CrystalOscillator<Stable>,
+    clocks: ClocksManager,
+    config: ClockConfig,
+}
+
+impl SystemClock {
+    /// Same setup as `init_clocks_and_plls`, at `SYS_125MHZ`, keeping the crystal oscillator to
+    /// switch configurations later
+    pub fn init(
+        xosc_dev: pac::XOSC,
+        clocks_dev: pac::CLOCKS,
+        pll_sys_dev: pac::PLL_SYS,
+        pll_usb_dev: pac::PLL_USB,
+        resets: &mut pac::RESETS,
+        watchdog: &mut Watchdog,
+    ) -> Self {
+        let xosc = xosc::setup_xosc_blocking(xosc_dev, HertzU32::from_raw(XTAL_FREQ_HZ))
+            .ok()
+            .unwrap();
+        // needed by the watchdog and the timer
+        watchdog.enable_tick_generation((XTAL_FREQ_HZ / 1_000_000) as u8);
+
+        let mut clocks = ClocksManager::new(clocks_dev);
+        let xosc_freq = xosc.operating_frequency();
+        let pll_sys = pll::setup_pll_blocking(
+            pll_sys_dev,
+            xosc_freq,
+            SYS_125MHZ.pll(),
+            &mut clocks,
+            resets,
+        )
+        .ok()
+        .unwrap();
+        let pll_usb =
+            pll::setup_pll_blocking(pll_usb_dev, xosc_freq, PLL_USB_48MHZ, &mut clocks, resets)
+                .ok()
+                .unwrap();
+        clocks.init_default(&xosc, &pll_sys, &pll_usb).ok().unwrap();
+
+        SystemClock {
+            xosc,
+            clocks,
+            config: SYS_125MHZ,
+        }
+    }
+
+    pub fn config(&self) -> &ClockConfig {
+        &self.config
+    }
+
+    pub fn clocks(&self) -> &ClocksManager {
+        &self.clocks
+    }
+
+    /// Switches to `config`: the core voltage is raised and the flash divisor increased before
+    /// the system clock, and lowered after it.
+    ///
+    /// The XIP is stopped while the flash divisor changes, the other core must not execute from
+    /// flash meanwhile, see `lockstep::with_core1_parked()`.
+    pub fn set(&mut self, config: &ClockConfig, resets: &mut pac::RESETS) {
+        let current = self.config;
+        if *config == current {
+            return;
+        }
+        let vreg = unsafe { &*pac::VREG_AND_CHIP_RESET::ptr() };
+        if config.vreg_mv > current.vreg_mv {
+            set_vsel(vreg, config.vsel());
+            asm::delay(current.sys_mhz * VREG_SETTLE_US);
+        }
+        if config.flash_divisor > current.flash_divisor {
+            set_flash_divisor(config.flash_divisor);
+        }
+
+        // clk_sys and clk_ref are moved to their glitchless sources while PLL_SYS is reset
+        let pll_sys_dev = unsafe { pac::Peripherals::steal().PLL_SYS };
+        let pll_sys = pll::setup_pll_blocking(
+            pll_sys_dev,
+            self.xosc.operating_frequency(),
+            config.pll(),
+            &mut self.clocks,
+            resets,
+        )
+        .ok()
+        .unwrap();
+        let clocks = &mut self.clocks;
+        clocks
+            .reference_clock
+            .configure_clock(&self.xosc, self.xosc.get_freq())
+            .ok()
+            .unwrap();
+        clocks
+            .system_clock
+            .configure_clock(&pll_sys, pll_sys.get_freq())
+            .ok()
+            .unwrap();
+        clocks
+            .peripheral_clock
+            .configure_clock(&clocks.system_clock, clocks.system_clock.freq())
+            .ok()
+            .unwrap();
+
+        if config.flash_divisor < current.flash_divisor {
+            set_flash_divisor(config.flash_divisor);
+        }
+        if config.vreg_mv < current.vreg_mv {
+            set_vsel(vreg, config.vsel());
+        }
+        self.config = *config;
+    }
+}
+
+fn set_vsel(vreg: &pac::vreg_and_chip_reset::RegisterBlock, vsel: u32) {
+    vreg.vreg
+        .modify(|r, w| unsafe { w.bits((r.bits() & !0xf0) | (vsel << 4)) });
+}
+
+/// Writes the SSI BAUDR register, which can only be written with the SSI disabled. Nothing may
+/// be fetched from flash until the SSI is enabled again, so this executes from SRAM2 with
+/// interrupts disabled and the accesses are done in a single `asm!` block.
+#[link_section = ".sram2_code"]
+#[inline(never)]
+fn set_flash_divisor(divisor: u32) {
+    let primask = primask::read();
+    interrupt::disable();
+    unsafe {
+        core::arch::asm!(
+            "movs {tmp}, #0",
+            // SSIENR
+            "str {tmp}, [{ssi}, #0x08]",
+            // BAUDR
+            "str {divisor}, [{ssi}, #0x14]",
+            "movs {tmp}, #1",
+            "str {tmp}, [{ssi}, #0x08]",
+            ssi = in(reg) XIP_SSI_BASE,
+            divisor = in(reg) divisor,
+            tmp = out(reg) _,
+        );
+    }
+    if primask.is_active() {
+        unsafe { interrupt::enable() };
+    }
+}
+
+/// Checks the configurations at compile time: PLL limits, VREG range, even flash divisor and
+/// flash clock
+const _: () = {
+    let mut i = 0;
+    while i < CLOCK_CONFIGS.len() {
+        let c = CLOCK_CONFIGS[i];
+        assert!(c.vco_mhz.is_multiple_of(12) && c.vco_mhz >= 750 && c.vco_mhz <= 1600);
+        assert!(c.post_div1 >= c.post_div2 && c.post_div1 <= 7 && c.post_div2 >= 1);
+        assert!(c.vco_mhz / (c.post_div1 as u32 * c.post_div2 as u32) == c.sys_mhz);
+        assert!(c.vreg_mv >= 850 && c.vreg_mv <= 1300 && c.vreg_mv.is_multiple_of(50));
+        assert!(c.flash_divisor >= 2 && c.flash_divisor.is_multiple_of(2));
+        assert!(c.sys_mhz <= MAX_FLASH_MHZ * c.flash_divisor);
+        i += 1;
+    }
+};
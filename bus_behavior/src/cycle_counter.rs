@@ -0,0 +1,99 @@
+use core::sync::atomic::{AtomicU32, Ordering};
+
+use cortex_m::peripheral::{syst::SystClkSource, SYST};
+use rp2040_hal::pac;
+
+/// Systick control and status register (CSR)
+pub const SYST_CSR: *const u32 = 0xE000_E010 as *const u32;
+/// Systick current value register (CVR)
+pub const SYST_CVR: *const u32 = 0xE000_E018 as *const u32;
+
+/// Systick is a 24 bit down counter, it is reloaded with this value after reaching zero
+const SYST_RELOAD: u32 = 0x00FF_FFFF;
+/// COUNTFLAG bit in CSR, set when the counter reaches zero and cleared when CSR is read
+const SYST_CSR_COUNTFLAG: u32 = 1 << 16;
+
+/// Calibrated overhead of the CVR reads, per core. Defaults to the 2 cycles documented by ARM
+/// until `CycleCounter::new()` is called on that core.
+static OVERHEAD: [AtomicU32; 2] = [AtomicU32::new(2), AtomicU32::new(2)];
+
+/// Cycle counter built on top of the Systick timer of the core that creates it.
+///
+/// Each core has its own Systick, so a `CycleCounter` must be created on both cores before any
+/// measurement is made with `measure_cycles!`:
+///
+/// ```ignore
+/// // core 0
+/// let core = pac::CorePeripherals::take().unwrap();
+/// let _cycle_counter = CycleCounter::new(core.SYST);
+/// // core 1
+/// let core = unsafe { pac::CorePeripherals::steal() };
+/// let _cycle_counter = CycleCounter::new(core.SYST);
+/// ```
+///
+/// A single measurement can span at most one wraparound of the 24 bit counter (~134 ms at 125 MHz).
+pub struct CycleCounter {
+    syst: SYST,
+}
+
+impl CycleCounter {
+    /// Takes ownership of the calling core's Systick, restarts it from the max reload value using
+    /// the processor clock, and calibrates the overhead of reading CVR.
+    pub fn new(mut syst: SYST) -> Self {
+        // stop the counter before touching the reload value, then write CVR to clear it together
+        // with COUNTFLAG, so that the counter starts at the reload value as soon as it is enabled
+        syst.disable_counter();
+        syst.disable_interrupt();
+        syst.set_clock_source(SystClkSource::Core);
+        syst.set_reload(SYST_RELOAD);
+        syst.clear_current();
+        syst.enable_counter();
+
+        let counter = CycleCounter { syst };
+        counter.calibrate();
+        counter
+    }
+
+    /// Measures an empty sequence, i.e. two back to back CVR reads, and records the result as the
+    /// overhead to subtract from every measurement made on this core.
+    fn calibrate(&self) {
+        let overhead = &OVERHEAD[core_id()];
+        overhead.store(0, Ordering::Relaxed);
+        // take the minimum of a few runs in case an interrupt hits one of them
+        let measured = (0..8)
+            .map(|_| unsafe { crate::measure_cycles!(0, "nop") })
+            .min()
+            .unwrap();
+        overhead.store(measured, Ordering::Relaxed);
+    }
+
+    /// Calibrated overhead of a measurement on the calling core, in clock cycles
+    pub fn overhead() -> u32 {
+        OVERHEAD[core_id()].load(Ordering::Relaxed)
+    }
+
+    /// Converts two CVR readings into the number of clock cycles elapsed between them, minus the
+    /// calibrated overhead. `csr` is the value of CSR read right after `end`, with COUNTFLAG
+    /// cleared by a CSR read right before `start`; this is what `measure_cycles!` does.
+    #[inline(always)]
+    pub fn elapsed(start: u32, end: u32, csr: u32) -> u32 {
+        // COUNTFLAG is also set if the counter reaches zero right after `end` was read, but then
+        // `end` can not be larger than `start`
+        let wrapped = csr & SYST_CSR_COUNTFLAG != 0 && end > start;
+        let ticks = if wrapped {
+            start + (SYST_RELOAD + 1) - end
+        } else {
+            start.wrapping_sub(end)
+        };
+        ticks.saturating_sub(Self::overhead())
+    }
+
+    /// Gives the Systick back, e.g. to use it as a delay provider
+    pub fn free(self) -> SYST {
+        self.syst
+    }
+}
+
+pub(crate) fn core_id() -> usize {
+    unsafe { (*pac::SIO::PTR).cpuid.read().bits() as usize }
+}
@@ -0,0 +1,227 @@
+//! Timestamps of GPIO edges on a timebase common to both cores, captured by PIO0 and the DMA.
+//!
+//! The Systick of a core only sees what happens on that core. To compare events of both cores,
+//! e.g. when each core enters and leaves its ISR, the cores drive a pin each through SIO and PIO0
+//! timestamps the edges of the pins at the resolution of the system clock:
+//!
+//! ```ignore
+//! let mut capture = EdgeCapture::new(pac.PIO0, &mut pac.RESETS, &[2, 3]);
+//! capture.arm();
+//! // core0 pulses GPIO2, core1 pulses GPIO3
+//! capture.disarm();
+//! for edge in capture.edges(0) {
+//!     println!("{}", edge);
+//! }
+//! ```
+//!
+//! Each pin is watched by two state machines executing `PROGRAM`, a loop that tests the pin and
+//! decrements X, so each state machine samples the pin every other cycle. The second state machine
+//! starts one cycle after the first one, so between them the pin is sampled at every cycle, and
+//! the timestamp of an edge is the earliest of their two samples. On each edge a state machine
+//! pushes X to its RX FIFO, which is drained to an SRAM buffer by a DMA channel paced by the FIFO.
+//!
+//! The timestamps are clock cycles since `arm()`, plus the constant delay of the input
+//! synchronizers, which cancels out when comparing edges. Each pin must start low when the
+//! capture is armed, the captures then alternate between rising and falling edges. The edges of
+//! a pin must be at least 8 cycles apart, so that both state machines see every edge, and the
+//! captures must be read within 2^32 cycles of `arm()` (34 s at 125 MHz).
+
+use rp2040_hal::pac;
+
+/// Number of pins that can be captured, two state machines of PIO0 each
+pub const MAX_PINS: usize = 2;
+/// Number of edges captured per pin
+pub const CAPACITY: usize = 64;
+
+/// Number of PIO0 state machines used
+const STATE_MACHINES: usize = 2 * MAX_PINS;
+
+/// First of the `STATE_MACHINES` DMA channels used, one per state machine. Channel 0 is left to
+/// the examples hammering the bus with the DMA.
+const DMA_FIRST_CHANNEL: usize = 8;
+const DMA_BASE: u32 = 0x5000_0000;
+const DMA_CHAN_ABORT: *mut u32 = 0x5000_0444 as *mut u32;
+/// DREQ of the RX FIFO of PIO0 state machine 0, the other state machines follow
+const DREQ_PIO0_RX0: u32 = 4;
+
+/// Address of the RX FIFO of state machine 0 of PIO0, the other state machines follow
+const PIO0_RXF0: u32 = 0x5020_0020;
+
+// state machine configuration
+/// CLKDIV INT = 1, the state machines execute one instruction per system clock cycle
+const CLKDIV: u32 = 1 << 16;
+/// SHIFTCTRL FJOIN_RX (8 entry RX FIFO) and AUTOPUSH with PUSH_THRESH = 0, i.e. 32 bits
+const SHIFTCTRL: u32 = (1 << 31) | (1 << 16);
+/// CTRL bits restarting the state machines and their clock dividers
+const CTRL_RESTART: u32 = (0xf << 4) | (0xf << 8);
+
+/// Entry points of the state machines sampling on even and odd cycles
+const EVEN_ENTRY: u16 = 1;
+const ODD_ENTRY: u16 = 0;
+/// `.wrap_target` and `.wrap` of `PROGRAM`
+const WRAP_BOTTOM: u32 = 1;
+const WRAP_TOP: u32 = 8;
+
+/// Timestamping program, X counts down from 0xffffffff. Every loop samples the pin with
+/// `jmp pin` and decrements X with `jmp x--`, one cycle each. A rising edge costs one cycle
+/// without decrement (`in`), a falling edge three cycles with one decrement (`jmp`, `in`,
+/// `jmp x--`), so the cycle at which capture `j` was sampled is `2 * (!x + j)`, plus 1 for the
+/// state machine entering at `ODD_ENTRY`.
+const PROGRAM: [u16; 9] = [
+    MOV_Y_Y,        // 0:            nop, delays the odd state machine by one cycle
+    jmp(PIN, 3),    // 1: wait_high: jmp pin, rise
+    jmp(X_DEC, 1),  // 2:            jmp x--, wait_high
+    IN_X_32,        // 3: rise:      in x, 32 (autopush)
+    jmp(PIN, 6),    // 4: wait_low:  jmp pin, high
+    jmp(ALWAYS, 7), // 5:            jmp fall
+    jmp(X_DEC, 4),  // 6: high:      jmp x--, wait_low
+    IN_X_32,        // 7: fall:      in x, 32 (autopush)
+    jmp(X_DEC, 1),  // 8:            jmp x--, wait_high
+];
+
+// PIO instruction encodings
+const ALWAYS: u16 = 0b000;
+const X_DEC: u16 = 0b010;
+const PIN: u16 = 0b110;
+/// `in x, 32`
+const IN_X_32: u16 = 0x4020;
+/// `mov x, ~null`
+const MOV_X_NOT_NULL: u16 = 0xa02b;
+/// `mov y, y`, the nop of the PIO assembler
+const MOV_Y_Y: u16 = 0xa042;
+
+/// `jmp condition, address`
+const fn jmp(condition: u16, address: u16) -> u16 {
+    (condition << 5) | address
+}
+
+/// Captures written by the DMA, one buffer per state machine
+static mut BUFFERS: [[u32; CAPACITY]; STATE_MACHINES] = [[0; CAPACITY]; STATE_MACHINES];
+
+/// An edge of a pin
+#[derive(Clone, Copy, Debug, PartialEq, Eq, defmt::Format)]
+pub struct Edge {
+    pub rising: bool,
+    /// Clock cycles since the capture was armed
+    pub cycle: u32,
+}
+
+/// Edge timestamping of up to `MAX_PINS` GPIOs with PIO0
+pub struct EdgeCapture {
+    pio: pac::PIO0,
+    pin_count: usize,
+}
+
+impl EdgeCapture {
+    /// Takes PIO0 and the DMA out of reset and loads the timestamping program for `pins`, the GPIO
+    /// numbers of the pins to capture. The pins can be configured for any function, PIO reads
+    /// them through their input buffers.
+    pub fn new(pio: pac::PIO0, resets: &mut pac::RESETS, pins: &[u8]) -> Self {
+        assert!(pins.len() <= MAX_PINS, "too many pins to capture");
+        resets
+            .reset
+            .modify(|_, w| w.pio0().clear_bit().dma().clear_bit());
+        while resets.reset_done.read().pio0().bit_is_clear() {}
+        while resets.reset_done.read().dma().bit_is_clear() {}
+
+        pio.ctrl.write(|w| unsafe { w.bits(0) });
+        for (address, &instruction) in PROGRAM.iter().enumerate() {
+            pio.instr_mem[address].write(|w| unsafe { w.bits(instruction as u32) });
+        }
+        for (sm, &pin) in pins.iter().flat_map(|pin| [pin, pin]).enumerate() {
+            let sm = &pio.sm[sm];
+            sm.sm_clkdiv.write(|w| unsafe { w.bits(CLKDIV) });
+            sm.sm_execctrl.write(|w| unsafe {
+                w.bits(((pin as u32) << 24) | (WRAP_TOP << 12) | (WRAP_BOTTOM << 7))
+            });
+            sm.sm_pinctrl.write(|w| unsafe { w.bits(0) });
+        }
+        EdgeCapture {
+            pio,
+            pin_count: pins.len(),
+        }
+    }
+
+    /// Clears the previous captures and starts the state machines of all the pins in the same
+    /// cycle, which is cycle 0 of the timestamps
+    pub fn arm(&mut self) {
+        self.disarm();
+        let state_machines = 2 * self.pin_count;
+        let pio = &self.pio;
+        pio.ctrl.write(|w| unsafe { w.bits(CTRL_RESTART) });
+        for (i, sm) in pio.sm.iter().take(state_machines).enumerate() {
+            // changing FJOIN_RX flushes the FIFOs
+            sm.sm_shiftctrl.write(|w| unsafe { w.bits(0) });
+            sm.sm_shiftctrl.write(|w| unsafe { w.bits(SHIFTCTRL) });
+            let entry = if i % 2 == 0 { EVEN_ENTRY } else { ODD_ENTRY };
+            for instruction in [MOV_X_NOT_NULL, jmp(ALWAYS, entry)] {
+                sm.sm_instr.write(|w| unsafe { w.bits(instruction as u32) });
+            }
+
+            let channel = dma_channel(i);
+            unsafe {
+                channel.add(0).write_volatile(PIO0_RXF0 + 4 * i as u32);
+                channel
+                    .add(1)
+                    .write_volatile(core::ptr::addr_of_mut!(BUFFERS[i]) as u32);
+                channel.add(2).write_volatile(CAPACITY as u32);
+                channel.add(3).write_volatile(dma_ctrl(i));
+            }
+        }
+        let enable = (1 << state_machines) - 1;
+        pio.ctrl
+            .write(|w| unsafe { w.bits(enable | (enable << 8)) });
+    }
+
+    /// Stops the state machines and the DMA channels, the captures are kept
+    pub fn disarm(&mut self) {
+        self.pio.ctrl.write(|w| unsafe { w.bits(0) });
+        let channels = ((1 << STATE_MACHINES) - 1) << DMA_FIRST_CHANNEL;
+        unsafe {
+            DMA_CHAN_ABORT.write_volatile(channels);
+            while DMA_CHAN_ABORT.read_volatile() != 0 {}
+        }
+    }
+
+    /// Edges of the `pin`-th pin given to `new()` captured since `arm()`, in order. The capture
+    /// can still be armed.
+    pub fn edges(&self, pin: usize) -> impl Iterator<Item = Edge> {
+        assert!(pin < self.pin_count);
+        let (even, odd) = (2 * pin, 2 * pin + 1);
+        let count = captured(even).min(captured(odd));
+        (0..count).map(move |j| {
+            let [even_x, odd_x] = [even, odd]
+                .map(|sm| unsafe { core::ptr::addr_of!(BUFFERS[sm][j]).read_volatile() });
+            let even_cycle = (!even_x).wrapping_add(j as u32).wrapping_mul(2);
+            let odd_cycle = (!odd_x).wrapping_add(j as u32).wrapping_mul(2) + 1;
+            Edge {
+                rising: j % 2 == 0,
+                cycle: even_cycle.min(odd_cycle),
+            }
+        })
+    }
+
+    /// Gives PIO0 back, the capture is disarmed
+    pub fn free(mut self) -> pac::PIO0 {
+        self.disarm();
+        self.pio
+    }
+}
+
+/// Registers READ_ADDR, WRITE_ADDR, TRANS_COUNT and CTRL_TRIG of the DMA channel draining the RX
+/// FIFO of state machine `sm`
+fn dma_channel(sm: usize) -> *mut u32 {
+    (DMA_BASE + 0x40 * (DMA_FIRST_CHANNEL + sm) as u32) as *mut u32
+}
+
+/// EN, DATA_SIZE = word, INCR_WRITE, CHAIN_TO itself (no chaining), TREQ_SEL = RX FIFO of `sm`
+fn dma_ctrl(sm: usize) -> u32 {
+    let channel = (DMA_FIRST_CHANNEL + sm) as u32;
+    1 | (2 << 2) | (1 << 5) | (channel << 11) | ((DREQ_PIO0_RX0 + sm as u32) << 15)
+}
+
+/// Number of captures written by the DMA channel of state machine `sm`
+fn captured(sm: usize) -> usize {
+    let remaining = unsafe { dma_channel(sm).add(2).read_volatile() };
+    CAPACITY - remaining as usize
+}
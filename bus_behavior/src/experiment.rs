@@ -0,0 +1,648 @@
+//! Declarative description of the lockstep experiments, and the runner that executes them.
+//!
+//! An experiment is a struct literal: which operations each core executes, on which data, from
+//! which code bank, with which vector table bank, padding NOPs and bus priorities, and what it is
+//! expected to measure. The runner
+//! generates the timer ISR of each core as Thumb machine code in a buffer of the selected bank, so
+//! any combination can be measured from the same binary without a `#[link_section]` per case.
+
+use cortex_m::asm;
+use embedded_hal::digital::v2::ToggleableOutputPin;
+use rp2040_hal::{self as hal, pac, vector_table::VectorTable, Sio};
+
+use crate::{
+    clock_config::{ClockConfig, SystemClock, SYS_125MHZ},
+    lockstep,
+    record::{Counts, Summary},
+    stack,
+    stats::Stats,
+    trials::Trials,
+    verdict::{Expectation, Measurement, Verdict},
+    CycleCounter, PerfCounters, PerfEvent, SYST_CSR,
+};
+
+/// TIMER INTF register, written to 0 by the ISRs to stop the forced interrupt
+const TIMER_INTF: u32 = 0x4005_403c;
+
+/// Size of the buffer receiving the generated ISR of one core
+const CODE_HALFWORDS: usize = 196;
+/// Maximum number of operations plus padding NOPs per core, the rest of the buffer is taken by
+/// the ISR prologue/epilogue and its literal pool
+pub const MAX_OPS: usize = 160;
+
+/// Instruction executed by a core between the two Systick reads
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Op {
+    /// `ldr` from the core's data word
+    Ldr,
+    /// `str` of the core's `value` to its data word
+    Str,
+    Nop,
+}
+
+/// Memory banks that can hold the data, the code or the vector table of a core.
+///
+/// SRAM0/1 are not available since they hold `.data`/`.bss` and core0's stack, SRAM5 also holds
+/// core1's stack (see `memory.toml`).
+#[derive(Clone, Copy, Debug, PartialEq, Eq, defmt::Format)]
+pub enum Bank {
+    Sram2,
+    Sram3,
+    Sram4,
+    Sram5,
+}
+
+/// Bits of BUS_PRIORITY, `true` is high priority
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BusPriority {
+    pub proc0: bool,
+    pub proc1: bool,
+    pub dma_r: bool,
+    pub dma_w: bool,
+}
+
+impl BusPriority {
+    /// Both cores at high priority, the DMA at low priority
+    pub const CORES_HIGH: BusPriority = BusPriority {
+        proc0: true,
+        proc1: true,
+        dma_r: false,
+        dma_w: false,
+    };
+
+    fn bits(&self) -> u32 {
+        (self.proc0 as u32)
+            | ((self.proc1 as u32) << 4)
+            | ((self.dma_r as u32) << 8)
+            | ((self.dma_w as u32) << 12)
+    }
+}
+
+/// What one core does in an experiment
+#[derive(Clone, Copy, Debug)]
+pub struct CoreSetup {
+    /// Executed in sequence between the two Systick reads
+    pub ops: &'static [Op],
+    /// Value written by `Op::Str`
+    pub value: u32,
+    /// Bank of the data word accessed by `Op::Ldr`/`Op::Str`, both cores access the same word
+    /// when they select the same bank
+    pub data: Bank,
+    /// Bank the ISR is executed from
+    pub code: Bank,
+    /// Bank holding the vector table of the core
+    pub vector_table: Bank,
+    /// NOPs executed right before the first Systick read, to compensate for differences in the
+    /// interrupt entry of the two cores
+    pub padding: usize,
+}
+
+/// An experiment: both cores execute their operations in lockstep from the same timer interrupt
+#[derive(Clone, Copy, Debug)]
+pub struct Experiment {
+    pub name: &'static str,
+    pub description: &'static str,
+    pub bus_priority: BusPriority,
+    /// Written to the data words before the interrupt is triggered
+    pub initial_value: u32,
+    /// Events counted while the ISRs execute
+    pub perf_events: [PerfEvent; 4],
+    pub cores: [CoreSetup; 2],
+    /// Results documented in the README, checked after the run
+    pub expected: Expectation,
+}
+
+/// Written by the generated ISR of each core, the layout is hardcoded in `generate_isr()`
+#[repr(C)]
+struct CoreResult {
+    start: u32,
+    end: u32,
+    csr: u32,
+    /// Last value loaded by `Op::Ldr`
+    value: u32,
+}
+
+impl CoreResult {
+    const fn new() -> Self {
+        CoreResult {
+            start: 0,
+            end: 0,
+            csr: 0,
+            value: 0,
+        }
+    }
+}
+
+static mut RESULTS: [CoreResult; 2] = [CoreResult::new(), CoreResult::new()];
+
+#[repr(C, align(4))]
+struct CodeBuffer([u16; CODE_HALFWORDS]);
+
+/// Everything the runner places in a bank, one vector table and one ISR per core
+#[repr(C)]
+struct BankStorage {
+    vector_tables: [VectorTable; 2],
+    code: [CodeBuffer; 2],
+    data: u32,
+}
+
+impl BankStorage {
+    const fn new() -> Self {
+        BankStorage {
+            vector_tables: [VectorTable::new(), VectorTable::new()],
+            code: [
+                CodeBuffer([0; CODE_HALFWORDS]),
+                CodeBuffer([0; CODE_HALFWORDS]),
+            ],
+            data: 0,
+        }
+    }
+}
+
+#[link_section = ".sram2_code"]
+static mut SRAM2: BankStorage = BankStorage::new();
+#[link_section = ".sram3_code"]
+static mut SRAM3: BankStorage = BankStorage::new();
+#[link_section = ".sram4_code"]
+static mut SRAM4: BankStorage = BankStorage::new();
+#[link_section = ".sram5_code"]
+static mut SRAM5: BankStorage = BankStorage::new();
+
+impl Bank {
+    /// Name of the bank as written in the datasheet
+    pub fn name(&self) -> &'static str {
+        match self {
+            Bank::Sram2 => "sram2",
+            Bank::Sram3 => "sram3",
+            Bank::Sram4 => "sram4",
+            Bank::Sram5 => "sram5",
+        }
+    }
+
+    fn storage(&self) -> *mut BankStorage {
+        match self {
+            Bank::Sram2 => core::ptr::addr_of_mut!(SRAM2),
+            Bank::Sram3 => core::ptr::addr_of_mut!(SRAM3),
+            Bank::Sram4 => core::ptr::addr_of_mut!(SRAM4),
+            Bank::Sram5 => core::ptr::addr_of_mut!(SRAM5),
+        }
+    }
+
+    /// Address of the data word of this bank
+    fn data(&self) -> *mut u32 {
+        unsafe { core::ptr::addr_of_mut!((*self.storage()).data) }
+    }
+}
+
+/// Runs `experiments` one after the other and prints the results with the verdict of each
+/// experiment, then blinks the LED.
+///
+/// Must be called from the entry point of core0, core1 is started by the runner.
+#[link_section = ".sram2_code"]
+pub fn run(experiments: &[Experiment]) -> ! {
+    run_at_clocks(experiments, &[SYS_125MHZ])
+}
+
+/// Same as `run()`, once at each of `clock_configs`
+#[link_section = ".sram2_code"]
+pub fn run_at_clocks(experiments: &[Experiment], clock_configs: &[ClockConfig]) -> ! {
+    run_trials(experiments, clock_configs, &Trials::ONCE)
+}
+
+/// Same as `run_at_clocks()`, each experiment is repeated `trials.count` times. The results and
+/// the verdict of the first trial are printed as with a single trial, followed by the verdicts of
+/// all the trials and the statistics of the cycles and of the event counts. An experiment is
+/// tallied as passed only if all its trials passed.
+#[link_section = ".sram2_code"]
+pub fn run_trials(experiments: &[Experiment], clock_configs: &[ClockConfig], trials: &Trials) -> ! {
+    with_runner(|runner| {
+        for config in clock_configs {
+            runner.set_clock(config);
+            defmt::println!(
+                "==== {} MHz, flash clock {} MHz, core voltage {} mV",
+                config.sys_mhz,
+                config.flash_mhz(),
+                config.vreg_mv
+            );
+            run_experiments(runner, experiments, trials);
+        }
+    })
+}
+
+/// Results of an experiment over all its trials
+struct TrialStats {
+    cycles: [Stats; 2],
+    counts: [Stats; 4],
+    /// Number of pass, fail and inconclusive verdicts
+    verdicts: [u32; 3],
+}
+
+impl TrialStats {
+    fn new() -> Self {
+        TrialStats {
+            cycles: [Stats::new(); 2],
+            counts: [Stats::new(); 4],
+            verdicts: [0; 3],
+        }
+    }
+
+    fn add(&mut self, measurement: &Measurement, verdict: &Verdict) {
+        for (stats, &cycles) in self.cycles.iter_mut().zip(&measurement.cycles) {
+            stats.add(cycles);
+        }
+        for (stats, &count) in self.counts.iter_mut().zip(&measurement.counts.counts) {
+            stats.add(count);
+        }
+        self.verdicts[verdict_index(verdict)] += 1;
+    }
+
+    /// Index in the tally of the worst verdict of the trials
+    fn tally_index(&self) -> usize {
+        match self.verdicts {
+            [_, 0, 0] => 0,
+            [_, 0, _] => 2,
+            _ => 1,
+        }
+    }
+}
+
+fn verdict_index(verdict: &Verdict) -> usize {
+    match verdict {
+        Verdict::Pass => 0,
+        Verdict::Fail(_) => 1,
+        Verdict::Inconclusive(_) => 2,
+    }
+}
+
+/// Runs `experiments` at the current clock and prints their results
+fn run_experiments(runner: &mut Runner, experiments: &[Experiment], trials: &Trials) {
+    // number of pass, fail and inconclusive verdicts
+    let mut tally = [0u32; 3];
+    for experiment in experiments {
+        let mut stats = TrialStats::new();
+        let mut first = None;
+        trials.repeat(|trial| {
+            let measurement = runner.measure(experiment);
+            let verdict = experiment.expected.check(&measurement);
+            stats.add(&measurement, &verdict);
+            if trial == 0 {
+                first = Some((measurement, verdict));
+            }
+        });
+        let Some((measurement, verdict)) = first else {
+            continue;
+        };
+
+        defmt::println!("== {}: {}", experiment.name, experiment.description);
+        for (core, setup) in experiment.cores.iter().enumerate() {
+            defmt::println!(
+                "core {}: {} operations took {} clock cycles. read val is {}, data in {} is {}",
+                core,
+                setup.ops.len(),
+                measurement.cycles[core],
+                measurement.values[core],
+                setup.data,
+                unsafe { setup.data.data().read_volatile() }
+            );
+        }
+        defmt::println!("contested accesses {}", measurement.counts);
+
+        defmt::println!("verdict: {}", verdict);
+        print_record(runner.clock().sys_mhz, experiment, &measurement, &verdict);
+        if trials.count > 1 {
+            print_stats(runner.clock().sys_mhz, experiment, trials, &stats);
+        }
+        tally[stats.tally_index()] += 1;
+    }
+    defmt::println!(
+        "{} experiments at {} MHz: {} passed, {} failed, {} inconclusive",
+        experiments.len(),
+        runner.clock().sys_mhz,
+        tally[0],
+        tally[1],
+        tally[2]
+    );
+}
+
+/// Executes experiments built at runtime, see `with_runner()`
+pub struct Runner {
+    perf_counters: PerfCounters,
+    clock: SystemClock,
+}
+
+impl Runner {
+    /// Configuration of the system clock the experiments run at, `SYS_125MHZ` until `set_clock()`
+    /// is called
+    pub fn clock(&self) -> &ClockConfig {
+        self.clock.config()
+    }
+
+    /// Switches the system clock, core1 is parked in SRAM3 meanwhile
+    pub fn set_clock(&mut self, config: &ClockConfig) {
+        let clock = &mut self.clock;
+        let mut resets = unsafe { pac::Peripherals::steal().RESETS };
+        lockstep::with_core1_parked(|| clock.set(config, &mut resets));
+    }
+
+    /// Executes `experiment` and returns what it measured, nothing is printed
+    #[link_section = ".sram2_code"]
+    pub fn measure(&mut self, experiment: &Experiment) -> Measurement {
+        let busctrl = self.perf_counters.busctrl();
+        busctrl
+            .bus_priority
+            .write(|w| unsafe { w.bits(experiment.bus_priority.bits()) });
+        while busctrl.bus_priority_ack.read().bits() == 0 {}
+        self.perf_counters.select(experiment.perf_events);
+
+        // write something to the data of each core
+        for setup in &experiment.cores {
+            unsafe { setup.data.data().write_volatile(experiment.initial_value) };
+        }
+
+        // generate the ISRs of both cores and install them in their vector tables
+        let mut vector_tables = [core::ptr::null_mut::<VectorTable>(); 2];
+        let mut isrs = [idle_isr as extern "C" fn(); 2];
+        for (core, setup) in experiment.cores.iter().enumerate() {
+            isrs[core] = generate_isr(core, setup);
+            vector_tables[core] = unsafe {
+                core::ptr::addr_of_mut!((*setup.vector_table.storage()).vector_tables[core])
+            };
+        }
+        unsafe { lockstep::install(vector_tables, isrs) };
+
+        // count the contested accesses while the timer interrupts execute
+        let counts = self.perf_counters.measure(lockstep::trigger);
+
+        let mut measurement = Measurement {
+            cycles: [0; 2],
+            values: [0; 2],
+            counts,
+        };
+        let results = unsafe { core::ptr::addr_of!(RESULTS).read_volatile() };
+        for (core, result) in results.iter().enumerate() {
+            measurement.cycles[core] = CycleCounter::elapsed(result.start, result.end, result.csr);
+            measurement.values[core] = result.value;
+        }
+        measurement
+    }
+}
+
+/// Sets up the clocks, the cycle counter and the performance counters, starts core1, calls `f`
+/// with a `Runner` and then blinks the LED. This is what `run()` does with a list of experiments,
+/// `f` can instead build experiments at runtime, e.g. to sweep a parameter.
+///
+/// Must be called from the entry point of core0.
+#[link_section = ".sram2_code"]
+pub fn with_runner(f: impl FnOnce(&mut Runner)) -> ! {
+    // Grab our singleton objects
+    let mut pac = pac::Peripherals::take().unwrap();
+    let mut sio = Sio::new(pac.SIO);
+
+    // configure systic to prepare for measurements
+    let core = pac::CorePeripherals::take().unwrap();
+    let _cycle_counter = CycleCounter::new(core.SYST);
+
+    // configure performance counters, this also takes BUSCTRL out of reset mode, the events are
+    // selected by each experiment
+    let perf_counters =
+        PerfCounters::new(pac.BUSCTRL, &mut pac.RESETS, [PerfEvent::Sram4Contested; 4]);
+
+    // Set up the watchdog driver - needed by the clock setup code
+    let mut watchdog = hal::Watchdog::new(pac.WATCHDOG);
+
+    // Configure the clocks, at 125 MHz until the runner switches them
+    let clock = SystemClock::init(
+        pac.XOSC,
+        pac.CLOCKS,
+        pac.PLL_SYS,
+        pac.PLL_USB,
+        &mut pac.RESETS,
+        &mut watchdog,
+    );
+
+    // start the second core
+    lockstep::init(&mut pac.PSM, &mut pac.PPB, &mut sio.fifo);
+
+    f(&mut Runner {
+        perf_counters,
+        clock,
+    });
+    for core in 0..2 {
+        match stack::check(core) {
+            Ok(used) => defmt::println!(
+                "core{=usize} stack: {=u32} of {=u32} bytes used",
+                core,
+                used,
+                stack::bounds(core).size()
+            ),
+            Err(overflow) => defmt::error!("{}", overflow),
+        }
+    }
+
+    // config led pin
+    let pins = hal::gpio::Pins::new(
+        pac.IO_BANK0,
+        pac.PADS_BANK0,
+        sio.gpio_bank0,
+        &mut pac.RESETS,
+    );
+    let mut led_pin = pins.gpio25.into_push_pull_output();
+    loop {
+        // sign of life
+        led_pin.toggle().unwrap();
+        asm::delay(12_500_000u32);
+    }
+}
+
+/// Prints the `experiments` record of an experiment measured at `sys_mhz`, see `record`
+fn print_record(
+    sys_mhz: u32,
+    experiment: &Experiment,
+    measurement: &Measurement,
+    verdict: &Verdict,
+) {
+    let priority = experiment.bus_priority;
+    let [core0, core1] = &experiment.cores;
+    defmt::println!(
+        "@experiments experiment={=str} sys_mhz={=u32} proc0={=u8} proc1={=u8} dma_r={=u8} dma_w={=u8} \
+         core0_ops={=usize} core0_data={=str} core0_code={=str} core0_vector_table={=str} core0_padding={=usize} \
+         core1_ops={=usize} core1_data={=str} core1_code={=str} core1_vector_table={=str} core1_padding={=usize} \
+         core0_cycles={=u32} core1_cycles={=u32} core0_value={=u32} core1_value={=u32} {} verdict={=str}",
+        experiment.name,
+        sys_mhz,
+        priority.proc0 as u8,
+        priority.proc1 as u8,
+        priority.dma_r as u8,
+        priority.dma_w as u8,
+        core0.ops.len(),
+        core0.data.name(),
+        core0.code.name(),
+        core0.vector_table.name(),
+        core0.padding,
+        core1.ops.len(),
+        core1.data.name(),
+        core1.code.name(),
+        core1.vector_table.name(),
+        core1.padding,
+        measurement.cycles[0],
+        measurement.cycles[1],
+        measurement.values[0],
+        measurement.values[1],
+        Counts(&measurement.counts),
+        verdict.name()
+    );
+}
+
+/// Prints the verdicts and the statistics of an experiment repeated over `trials`, and one
+/// `experiment_stats` record per quantity, see `record`
+fn print_stats(sys_mhz: u32, experiment: &Experiment, trials: &Trials, stats: &TrialStats) {
+    defmt::println!(
+        "{} trials ({} perturbation): {} passed, {} failed, {} inconclusive",
+        trials.count,
+        trials.perturbation.name(),
+        stats.verdicts[0],
+        stats.verdicts[1],
+        stats.verdicts[2]
+    );
+    let cycles = [
+        ("core0_cycles", &stats.cycles[0]),
+        ("core1_cycles", &stats.cycles[1]),
+    ];
+    let counts = (experiment.perf_events.iter())
+        .zip(&stats.counts)
+        .map(|(event, stats)| (event.name(), stats));
+    for (quantity, stats) in cycles.into_iter().chain(counts) {
+        defmt::println!("{=str} over {}", quantity, stats);
+        defmt::println!(
+            "@experiment_stats experiment={=str} sys_mhz={=u32} perturbation={} quantity={=str} {}",
+            experiment.name,
+            sys_mhz,
+            trials.perturbation.name(),
+            quantity,
+            Summary(stats)
+        );
+    }
+}
+
+/// Placeholder until the ISRs are generated
+extern "C" fn idle_isr() {}
+
+/// Generates the timer ISR of `core` in its code bank and returns it.
+///
+/// The measured part is the same sequence as `measure_cycles!`: read CSR to clear COUNTFLAG, read
+/// CVR, operations, read CVR, read CSR. Before it the ISR saves r4-r7 and loads its operands from
+/// the literal pool, after it the ISR stores the readings to `RESULTS[core]`, stops the forced
+/// interrupt and sets the done flag of `lockstep`.
+fn generate_isr(core: usize, setup: &CoreSetup) -> extern "C" fn() {
+    assert!(
+        setup.ops.len() + setup.padding <= MAX_OPS,
+        "too many operations for the ISR buffer"
+    );
+    let buffer = unsafe { &mut (*setup.code.storage()).code[core].0 };
+    let mut asm = Assembler::new(buffer);
+
+    // r0 = data, r1 = value, r2 = Systick CSR, r3 = RESULTS[core]
+    asm.emit(0xB5F0); // push {r4, r5, r6, r7, lr}
+    asm.ldr_literal(0, setup.data.data() as u32);
+    asm.ldr_literal(1, setup.value);
+    asm.ldr_literal(2, SYST_CSR as u32);
+    asm.ldr_literal(3, unsafe { core::ptr::addr_of_mut!(RESULTS[core]) } as u32);
+    for _ in 0..setup.padding {
+        asm.emit(NOP);
+    }
+    asm.emit(ldr_imm(4, 2, 0)); // read systick CSR to clear COUNTFLAG
+    asm.emit(ldr_imm(5, 2, 8)); // read systick current value register CVR (start)
+    for op in setup.ops {
+        asm.emit(match op {
+            Op::Ldr => ldr_imm(7, 0, 0),
+            Op::Str => str_imm(1, 0, 0),
+            Op::Nop => NOP,
+        });
+    }
+    asm.emit(ldr_imm(4, 2, 8)); // read systick current value register CVR (end)
+    asm.emit(ldr_imm(6, 2, 0)); // read systick CSR to know whether the counter wrapped
+    asm.emit(str_imm(5, 3, 0)); // start
+    asm.emit(str_imm(4, 3, 4)); // end
+    asm.emit(str_imm(6, 3, 8)); // csr
+    asm.emit(str_imm(7, 3, 12)); // value
+
+    // stop this triggering interrupt
+    asm.ldr_literal(4, TIMER_INTF);
+    asm.emit(movs(5, 0));
+    asm.emit(str_imm(5, 4, 0));
+    asm.ldr_literal(4, lockstep::done_flag(core) as u32);
+    asm.emit(movs(5, 1));
+    asm.emit(str_imm(5, 4, 0));
+    asm.emit(0xBDF0); // pop {r4, r5, r6, r7, pc}
+    let isr = asm.finish();
+
+    // bit 0 set for Thumb state
+    unsafe { core::mem::transmute::<usize, extern "C" fn()>(isr as usize | 1) }
+}
+
+const NOP: u16 = 0xBF00;
+
+/// `ldr rt, [rn, #offset]`
+fn ldr_imm(rt: u16, rn: u16, offset: u16) -> u16 {
+    0x6800 | ((offset / 4) << 6) | (rn << 3) | rt
+}
+
+/// `str rt, [rn, #offset]`
+fn str_imm(rt: u16, rn: u16, offset: u16) -> u16 {
+    0x6000 | ((offset / 4) << 6) | (rn << 3) | rt
+}
+
+/// `movs rd, #imm`
+fn movs(rd: u16, imm: u16) -> u16 {
+    0x2000 | (rd << 8) | imm
+}
+
+/// Minimal Thumb-1 assembler writing to a code buffer, with a literal pool after the code
+struct Assembler<'a> {
+    buffer: &'a mut [u16; CODE_HALFWORDS],
+    len: usize,
+    /// (index of the `ldr rt, [pc, #imm]` instruction, literal value)
+    literals: [(usize, u32); 8],
+    literal_count: usize,
+}
+
+impl<'a> Assembler<'a> {
+    fn new(buffer: &'a mut [u16; CODE_HALFWORDS]) -> Self {
+        Assembler {
+            buffer,
+            len: 0,
+            literals: [(0, 0); 8],
+            literal_count: 0,
+        }
+    }
+
+    fn emit(&mut self, instruction: u16) {
+        self.buffer[self.len] = instruction;
+        self.len += 1;
+    }
+
+    /// `ldr rt, =value`, the offset is patched by `finish()`
+    fn ldr_literal(&mut self, rt: u16, value: u32) {
+        self.literals[self.literal_count] = (self.len, value);
+        self.literal_count += 1;
+        self.emit(0x4800 | (rt << 8));
+    }
+
+    /// Appends the literal pool and returns the address of the code
+    fn finish(self) -> *const u16 {
+        // literals must be word aligned, the buffer itself is
+        if !self.len.is_multiple_of(2) {
+            self.buffer[self.len] = NOP;
+        }
+        let pool = self.len.next_multiple_of(2);
+        for (i, &(instruction, value)) in self.literals[..self.literal_count].iter().enumerate() {
+            let literal = pool + 2 * i;
+            self.buffer[literal] = value as u16;
+            self.buffer[literal + 1] = (value >> 16) as u16;
+            // the offset is relative to the instruction address + 4, rounded down to a word
+            let pc = (instruction + 2) & !1;
+            self.buffer[instruction] |= ((literal - pc) / 2) as u16;
+        }
+        self.buffer.as_ptr()
+    }
+}
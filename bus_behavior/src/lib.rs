@@ -1,429 +1,107 @@
 #![no_std]
 
-use cortex_m::peripheral::syst::SystClkSource;
-use rp2040_hal::pac;
-
-pub fn systic_init() {
-    let mut core = unsafe { pac::CorePeripherals::steal() };
-    core.SYST.disable_interrupt();
-    core.SYST.set_clock_source(SystClkSource::Core);
-    core.SYST.enable_counter();
-    core.SYST.set_reload(0x00FFFFFF);
+pub mod clock_config;
+mod cycle_counter;
+pub mod edge_capture;
+pub mod experiment;
+pub mod lockstep;
+mod perf_counters;
+pub mod record;
+mod sram_load;
+pub mod stack;
+pub mod stats;
+pub mod trials;
+pub mod verdict;
+
+pub use cycle_counter::{CycleCounter, SYST_CSR, SYST_CVR};
+pub use perf_counters::{PerfCounters, PerfCounts, PerfEvent};
+
+/// Measures the clock cycles taken by `count` back to back copies of an instruction template.
+///
+/// Expands to an `asm!` block that reads the Systick CVR, executes `count` copies of `template`
+/// (unrolled by the assembler with `.rept`), then reads CVR again. The operand bindings are passed
+/// as is to `asm!` and must be named, e.g. `ptr = in(reg) ptr_sram4`. An optional `padding = N`
+/// inserts N NOPs right before the first CVR read, which is used to compensate for differences in
+/// the code executed by the two cores before the measurement starts.
+///
+/// ```ignore
+/// let cycles = unsafe {
+///     measure_cycles!(100, "ldr {value}, [{ptr}]", ptr = in(reg) ptr_sram4, value = out(reg) _)
+/// };
+/// ```
+///
+/// The cycle count for an operation can then be obtained by reading the STCVR immediately before and immediately after the operation in question.
+/// Because STCVR is a down counter, the number of core clock cycles taken by the operation is given by:
+/// (STCVR1 - STCVR2 - overhead)
+/// The overhead (2 cycles according to ARM) is because the read of the STCVR is Strongly-Ordered with regard to other memory accesses or data processing instructions.
+/// It is calibrated at startup by `CycleCounter::new()`, and the 24-bit wraparound of the counter is
+/// corrected using the COUNTFLAG bit of CSR, which is cleared right before and read right after the sequence.
+///
+/// A `CycleCounter` must be created on each core before making measurements
+#[macro_export]
+macro_rules! measure_cycles {
+    (padding = $padding:literal, $count:literal, $template:literal $(, $($operands:tt)*)?) => {{
+        let start: u32;
+        let end: u32;
+        let csr: u32;
+        core::arch::asm!(
+            concat!(".rept ", stringify!($padding)),
+            "nop",
+            ".endr",
+            "ldr {__csr}, [{__syst}]", // read systick CSR to clear COUNTFLAG
+            "ldr {__start}, [{__syst}, #8]", // read systick current value register CVR
+            concat!(".rept ", stringify!($count)),
+            $template,
+            ".endr",
+            "ldr {__end}, [{__syst}, #8]", // read systick current value register CVR
+            "ldr {__csr}, [{__syst}]", // read systick CSR to know whether the counter wrapped
+            __syst = in(reg) $crate::SYST_CSR,
+            __csr = out(reg) csr,
+            __start = out(reg) start,
+            __end = out(reg) end,
+            $($($operands)*)?
+        );
+        $crate::CycleCounter::elapsed(start, end, csr)
+    }};
+    ($count:literal, $template:literal $(, $($operands:tt)*)?) => {
+        $crate::measure_cycles!(padding = 0, $count, $template $(, $($operands)*)?)
+    };
 }
 
 /// performs 100 reads to the memory location pointerd by `ptr_from_ram` and returns the clock cycles taken for the process
-/// `systic_init()` must be used on each core to initialize systick timer correctly for making the measurements
+/// A `CycleCounter` must be created on each core to initialize systick timer correctly for making the measurements
 #[no_mangle]
 #[link_section = ".sram4_code"]
 pub fn hundred_reads(ptr_from_ram: *const u32) -> u32 {
     unsafe {
-        const SYST_CVR: *const u32 = 0xE000_E018 as *const u32;
-        let start: u32;
-        let end: u32;
-        core::arch::asm!(
-            "ldr {0}, [{3}]", // read systick current value register CVR
-            // 100 unrolled loop
-            "ldr {1}, [{4}]",
-            "ldr {1}, [{4}]",
-            "ldr {1}, [{4}]",
-            "ldr {1}, [{4}]",
-            "ldr {1}, [{4}]",
-            "ldr {1}, [{4}]",
-            "ldr {1}, [{4}]",
-            "ldr {1}, [{4}]",
-            "ldr {1}, [{4}]",
-            "ldr {1}, [{4}]", // 10
-
-            "ldr {1}, [{4}]",
-            "ldr {1}, [{4}]",
-            "ldr {1}, [{4}]",
-            "ldr {1}, [{4}]",
-            "ldr {1}, [{4}]",
-            "ldr {1}, [{4}]",
-            "ldr {1}, [{4}]",
-            "ldr {1}, [{4}]",
-            "ldr {1}, [{4}]",
-            "ldr {1}, [{4}]", // 20
-
-            "ldr {1}, [{4}]",
-            "ldr {1}, [{4}]",
-            "ldr {1}, [{4}]",
-            "ldr {1}, [{4}]",
-            "ldr {1}, [{4}]",
-            "ldr {1}, [{4}]",
-            "ldr {1}, [{4}]",
-            "ldr {1}, [{4}]",
-            "ldr {1}, [{4}]",
-            "ldr {1}, [{4}]", // 30
-
-            "ldr {1}, [{4}]",
-            "ldr {1}, [{4}]",
-            "ldr {1}, [{4}]",
-            "ldr {1}, [{4}]",
-            "ldr {1}, [{4}]",
-            "ldr {1}, [{4}]",
-            "ldr {1}, [{4}]",
-            "ldr {1}, [{4}]",
-            "ldr {1}, [{4}]",
-            "ldr {1}, [{4}]", // 40
-
-            "ldr {1}, [{4}]",
-            "ldr {1}, [{4}]",
-            "ldr {1}, [{4}]",
-            "ldr {1}, [{4}]",
-            "ldr {1}, [{4}]",
-            "ldr {1}, [{4}]",
-            "ldr {1}, [{4}]",
-            "ldr {1}, [{4}]",
-            "ldr {1}, [{4}]",
-            "ldr {1}, [{4}]", // 50
-
-            "ldr {1}, [{4}]",
-            "ldr {1}, [{4}]",
-            "ldr {1}, [{4}]",
-            "ldr {1}, [{4}]",
-            "ldr {1}, [{4}]",
-            "ldr {1}, [{4}]",
-            "ldr {1}, [{4}]",
-            "ldr {1}, [{4}]",
-            "ldr {1}, [{4}]",
-            "ldr {1}, [{4}]", // 60
-
-            "ldr {1}, [{4}]",
-            "ldr {1}, [{4}]",
-            "ldr {1}, [{4}]",
-            "ldr {1}, [{4}]",
-            "ldr {1}, [{4}]",
-            "ldr {1}, [{4}]",
-            "ldr {1}, [{4}]",
-            "ldr {1}, [{4}]",
-            "ldr {1}, [{4}]",
-            "ldr {1}, [{4}]", // 70
-
-            "ldr {1}, [{4}]",
-            "ldr {1}, [{4}]",
-            "ldr {1}, [{4}]",
-            "ldr {1}, [{4}]",
-            "ldr {1}, [{4}]",
-            "ldr {1}, [{4}]",
-            "ldr {1}, [{4}]",
-            "ldr {1}, [{4}]",
-            "ldr {1}, [{4}]",
-            "ldr {1}, [{4}]", // 80
-
-            "ldr {1}, [{4}]",
-            "ldr {1}, [{4}]",
-            "ldr {1}, [{4}]",
-            "ldr {1}, [{4}]",
-            "ldr {1}, [{4}]",
-            "ldr {1}, [{4}]",
-            "ldr {1}, [{4}]",
-            "ldr {1}, [{4}]",
-            "ldr {1}, [{4}]",
-            "ldr {1}, [{4}]", // 90
-
-            "ldr {1}, [{4}]",
-            "ldr {1}, [{4}]",
-            "ldr {1}, [{4}]",
-            "ldr {1}, [{4}]",
-            "ldr {1}, [{4}]",
-            "ldr {1}, [{4}]",
-            "ldr {1}, [{4}]",
-            "ldr {1}, [{4}]",
-            "ldr {1}, [{4}]",
-            "ldr {1}, [{4}]", // 100
-
-            "ldr {2}, [{3}]", // read systick current value register CVR
-            out(reg) start,
-            out(reg) _,
-            out(reg) end,
-            in(reg) SYST_CVR,
-            in(reg) ptr_from_ram,
-        );
-
-        // The cycle count for an operation can then be obtained by reading the STCVR immediately before and immediately after the operation in question.
-        // Because STCVR is a down counter, the number of core clock cycles taken by the operation is given by:
-        // (STCVR1 - STCVR2 - 2)
-        // The overhead of two cycles is because the read of the STCVR is Strongly-Ordered with regard to other memory accesses or data processing instructions.
-
-        start - end - 2 // todo: this could panic if start is read then STCVR wraps to zero
+        measure_cycles!(
+            100,
+            "ldr {value}, [{ptr}]",
+            ptr = in(reg) ptr_from_ram,
+            value = out(reg) _,
+        )
     }
 }
 
 /// performs 100 writes to the memory location pointerd by `ptr_from_ram` and returns the clock cycles taken for the process
-/// `systic_init()` must be used on each core to initialize systick timer correctly for making the measurements
+/// A `CycleCounter` must be created on each core to initialize systick timer correctly for making the measurements
 #[no_mangle]
 #[link_section = ".sram4_code"]
 pub fn hundred_writes(ptr_to_ram: *mut u32) -> u32 {
     unsafe {
-        const SYST_CVR: *const u32 = 0xE000_E018 as *const u32;
-        let start: u32;
-        let end: u32;
-
-        core::arch::asm!(
-            "ldr {0}, [{2}]",
-            // 100 unrolled loop
-            "str {3}, [{4}]",
-            "str {3}, [{4}]",
-            "str {3}, [{4}]",
-            "str {3}, [{4}]",
-            "str {3}, [{4}]",
-            "str {3}, [{4}]",
-            "str {3}, [{4}]",
-            "str {3}, [{4}]",
-            "str {3}, [{4}]",
-            "str {3}, [{4}]",
-
-            "str {3}, [{4}]",
-            "str {3}, [{4}]",
-            "str {3}, [{4}]",
-            "str {3}, [{4}]",
-            "str {3}, [{4}]",
-            "str {3}, [{4}]",
-            "str {3}, [{4}]",
-            "str {3}, [{4}]",
-            "str {3}, [{4}]",
-            "str {3}, [{4}]",
-
-            "str {3}, [{4}]",
-            "str {3}, [{4}]",
-            "str {3}, [{4}]",
-            "str {3}, [{4}]",
-            "str {3}, [{4}]",
-            "str {3}, [{4}]",
-            "str {3}, [{4}]",
-            "str {3}, [{4}]",
-            "str {3}, [{4}]",
-            "str {3}, [{4}]",
-
-            "str {3}, [{4}]",
-            "str {3}, [{4}]",
-            "str {3}, [{4}]",
-            "str {3}, [{4}]",
-            "str {3}, [{4}]",
-            "str {3}, [{4}]",
-            "str {3}, [{4}]",
-            "str {3}, [{4}]",
-            "str {3}, [{4}]",
-            "str {3}, [{4}]",
-
-            "str {3}, [{4}]",
-            "str {3}, [{4}]",
-            "str {3}, [{4}]",
-            "str {3}, [{4}]",
-            "str {3}, [{4}]",
-            "str {3}, [{4}]",
-            "str {3}, [{4}]",
-            "str {3}, [{4}]",
-            "str {3}, [{4}]",
-            "str {3}, [{4}]",
-
-            "str {3}, [{4}]",
-            "str {3}, [{4}]",
-            "str {3}, [{4}]",
-            "str {3}, [{4}]",
-            "str {3}, [{4}]",
-            "str {3}, [{4}]",
-            "str {3}, [{4}]",
-            "str {3}, [{4}]",
-            "str {3}, [{4}]",
-            "str {3}, [{4}]",
-
-            "str {3}, [{4}]",
-            "str {3}, [{4}]",
-            "str {3}, [{4}]",
-            "str {3}, [{4}]",
-            "str {3}, [{4}]",
-            "str {3}, [{4}]",
-            "str {3}, [{4}]",
-            "str {3}, [{4}]",
-            "str {3}, [{4}]",
-            "str {3}, [{4}]",
-
-            "str {3}, [{4}]",
-            "str {3}, [{4}]",
-            "str {3}, [{4}]",
-            "str {3}, [{4}]",
-            "str {3}, [{4}]",
-            "str {3}, [{4}]",
-            "str {3}, [{4}]",
-            "str {3}, [{4}]",
-            "str {3}, [{4}]",
-            "str {3}, [{4}]",
-
-            "str {3}, [{4}]",
-            "str {3}, [{4}]",
-            "str {3}, [{4}]",
-            "str {3}, [{4}]",
-            "str {3}, [{4}]",
-            "str {3}, [{4}]",
-            "str {3}, [{4}]",
-            "str {3}, [{4}]",
-            "str {3}, [{4}]",
-            "str {3}, [{4}]",
-
-            "str {3}, [{4}]",
-            "str {3}, [{4}]",
-            "str {3}, [{4}]",
-            "str {3}, [{4}]",
-            "str {3}, [{4}]",
-            "str {3}, [{4}]",
-            "str {3}, [{4}]",
-            "str {3}, [{4}]",
-            "str {3}, [{4}]",
-            "str {3}, [{4}]",
-
-            "ldr {1}, [{2}]",
-            out(reg) start, // 0
-            out(reg) end, // 1
-            in(reg) SYST_CVR, // 2
-
-            in(reg) 2507, // 3
-
-            in(reg) ptr_to_ram, // 4
-        );
-
-        // The cycle count for an operation can then be obtained by reading the STCVR immediately before and immediately after the operation in question.
-        // Because STCVR is a down counter, the number of core clock cycles taken by the operation is given by:
-        // (STCVR1 - STCVR2 - 2)
-        // The overhead of two cycles is because the read of the STCVR is Strongly-Ordered with regard to other memory accesses or data processing instructions.
-        start - end - 2
+        measure_cycles!(
+            100,
+            "str {value}, [{ptr}]",
+            ptr = in(reg) ptr_to_ram,
+            value = in(reg) 2507,
+        )
     }
 }
 
 /// performs 100 NOPs and returns the clock cycles taken for the process
-/// `systic_init()` must be used on each core to initialize systick timer correctly for making the measurements
+/// A `CycleCounter` must be created on each core to initialize systick timer correctly for making the measurements
 #[no_mangle]
 #[link_section = ".sram4_code"]
 pub fn hundred_nops() -> u32 {
-    unsafe {
-        const SYST_CVR: *const u32 = 0xE000_E018 as *const u32;
-        let start: u32;
-        let end: u32;
-
-        core::arch::asm!(
-            "ldr {0}, [{2}]",
-            // 100 unrolled loop
-            "nop",
-            "nop",
-            "nop",
-            "nop",
-            "nop",
-            "nop",
-            "nop",
-            "nop",
-            "nop",
-            "nop",
-
-            "nop",
-            "nop",
-            "nop",
-            "nop",
-            "nop",
-            "nop",
-            "nop",
-            "nop",
-            "nop",
-            "nop",
-
-            "nop",
-            "nop",
-            "nop",
-            "nop",
-            "nop",
-            "nop",
-            "nop",
-            "nop",
-            "nop",
-            "nop",
-
-            "nop",
-            "nop",
-            "nop",
-            "nop",
-            "nop",
-            "nop",
-            "nop",
-            "nop",
-            "nop",
-            "nop",
-
-            "nop",
-            "nop",
-            "nop",
-            "nop",
-            "nop",
-            "nop",
-            "nop",
-            "nop",
-            "nop",
-            "nop",
-
-            "nop",
-            "nop",
-            "nop",
-            "nop",
-            "nop",
-            "nop",
-            "nop",
-            "nop",
-            "nop",
-            "nop",
-
-            "nop",
-            "nop",
-            "nop",
-            "nop",
-            "nop",
-            "nop",
-            "nop",
-            "nop",
-            "nop",
-            "nop",
-
-            "nop",
-            "nop",
-            "nop",
-            "nop",
-            "nop",
-            "nop",
-            "nop",
-            "nop",
-            "nop",
-            "nop",
-
-            "nop",
-            "nop",
-            "nop",
-            "nop",
-            "nop",
-            "nop",
-            "nop",
-            "nop",
-            "nop",
-            "nop",
-
-            "nop",
-            "nop",
-            "nop",
-            "nop",
-            "nop",
-            "nop",
-            "nop",
-            "nop",
-            "nop",
-            "nop",
-
-            "ldr {1}, [{2}]",
-            out(reg) start, // 0
-            out(reg) end, // 1
-            in(reg) SYST_CVR, // 2
-        );
-
-        // The cycle count for an operation can then be obtained by reading the STCVR immediately before and immediately after the operation in question.
-        // Because STCVR is a down counter, the number of core clock cycles taken by the operation is given by:
-        // (STCVR1 - STCVR2 - 2)
-        // The overhead of two cycles is because the read of the STCVR is Strongly-Ordered with regard to other memory accesses or data processing instructions.
-        start - end - 2
-    }
+    unsafe { measure_cycles!(100, "nop") }
 }
@@ -0,0 +1,253 @@
+//! Executes a function on both cores at the same clock cycle.
+//!
+//! Both cores take TIMER_IRQ_0, which is forced through the INTF register of the TIMER. Each core
+//! fetches its vector and its handler from its own bank, SRAM2 for core0 and SRAM3 for core1, so
+//! that the two cores don't contest on their way to the measured code (see the memory
+//! partitioning section of the README).
+//!
+//! ```ignore
+//! lockstep::init(&mut pac.PSM, &mut pac.PPB, &mut sio.fifo);
+//! let [cycles0, cycles1] = lockstep::run(core0_reads, core1_reads);
+//! ```
+
+use core::sync::atomic::{compiler_fence, AtomicU32, Ordering};
+
+use cortex_m::asm;
+use rp2040_hal::{multicore::Multicore, pac, sio::SioFifo, vector_table::VectorTable, Sio};
+
+use crate::{stack, CycleCounter, PerfCounters, PerfCounts};
+
+const CORE1_READY: u32 = 7;
+/// Sent instead of a vector table address, which is aligned, to park core1
+const CORE1_PARK: u32 = 1;
+
+// vector tables for the two cores stored in different memeory regions to avoid any concurrent
+// access to the same memory bank when the two cores both receive an interrupt at the same time
+#[link_section = ".sram2_code"]
+static mut CORE0_VECTOR_TABLE: VectorTable = VectorTable::new();
+#[link_section = ".sram3_code"]
+static mut CORE1_VECTOR_TABLE: VectorTable = VectorTable::new();
+
+/// Function executed by the handler of a core and where to write its result. The types are erased
+/// since a static can't be generic, `run()` and the handlers agree on them.
+struct Task {
+    function: *const (),
+    result: *mut (),
+}
+
+impl Task {
+    const fn new() -> Self {
+        Task {
+            function: core::ptr::null(),
+            result: core::ptr::null_mut(),
+        }
+    }
+}
+
+// read by the handlers right before calling the function, so they are in the bank of the handler
+#[link_section = ".sram2_code"]
+static mut CORE0_TASK: Task = Task::new();
+#[link_section = ".sram3_code"]
+static mut CORE1_TASK: Task = Task::new();
+
+/// Set to 1 by the handler of each core once its result is written
+static DONE: [AtomicU32; 2] = [AtomicU32::new(0), AtomicU32::new(0)];
+
+/// Set to 1 by core1 once it is parked, core0 clears it to release core1
+#[link_section = ".sram3_code"]
+static CORE1_PARKED: AtomicU32 = AtomicU32::new(0);
+
+/// Starts core1 and unmasks TIMER_IRQ_0 on core0.
+///
+/// Must be called once from core0, with a `CycleCounter` created on core0. Core1 creates its own
+/// `CycleCounter` and then waits for the handlers of `run()`. Both cores install their stack
+/// guard (see `stack`).
+pub fn init(psm: &mut pac::PSM, ppb: &mut pac::PPB, fifo: &mut SioFifo) {
+    stack::install_guard();
+
+    // drain the fifo from core 1 side & start the second core, on its stack in its own bank (see
+    // memory.toml) so that the cores don't contest a bank when they push to their stacks
+    fifo.drain();
+    let mut mc = Multicore::new(psm, ppb, fifo);
+    let cores = mc.cores();
+    let core1 = &mut cores[1];
+    let _ = core1.spawn(unsafe { stack::core1_stack() }, || core1_main());
+
+    // unpend and unmask timer interrupts
+    pac::NVIC::unpend(pac::Interrupt::TIMER_IRQ_0);
+    unsafe { pac::NVIC::unmask(pac::Interrupt::TIMER_IRQ_0) };
+}
+
+/// Executes `core0` on core0 and `core1` on core1 at the same time and returns their results,
+/// typically the clock cycles measured by `measure_cycles!`.
+///
+/// The functions are called from TIMER_IRQ_0 handlers in SRAM2 (core0) and SRAM3 (core1), they
+/// should be placed in the same bank as the handler of their core with
+/// `#[link_section = ".sram2_code"]` and `#[link_section = ".sram3_code"]`.
+/// `init()` must have been called before.
+#[link_section = ".sram2_code"]
+pub fn run<T: Default>(core0: fn() -> T, core1: fn() -> T) -> [T; 2] {
+    execute(core0, core1, trigger).0
+}
+
+/// Same as `run()`, and also returns the bus events counted while the handlers execute
+#[link_section = ".sram2_code"]
+pub fn run_counting<T: Default>(
+    core0: fn() -> T,
+    core1: fn() -> T,
+    perf_counters: &mut PerfCounters,
+) -> ([T; 2], PerfCounts) {
+    execute(core0, core1, || perf_counters.measure(trigger))
+}
+
+fn execute<T: Default, R>(
+    core0: fn() -> T,
+    core1: fn() -> T,
+    f: impl FnOnce() -> R,
+) -> ([T; 2], R) {
+    let mut results = [T::default(), T::default()];
+    let results_ptr = results.as_mut_ptr();
+    unsafe {
+        CORE0_TASK = Task {
+            function: core0 as *const (),
+            result: results_ptr as *mut (),
+        };
+        CORE1_TASK = Task {
+            function: core1 as *const (),
+            result: results_ptr.add(1) as *mut (),
+        };
+        install(
+            [
+                core::ptr::addr_of_mut!(CORE0_VECTOR_TABLE),
+                core::ptr::addr_of_mut!(CORE1_VECTOR_TABLE),
+            ],
+            [core0_timer_irq::<T>, core1_timer_irq::<T>],
+        );
+    }
+    let r = f();
+    (results, r)
+}
+
+/// Registers `handlers` as TIMER_IRQ_0 handlers in `vector_tables`, activates the vector table of
+/// each core and waits until core1 is ready.
+///
+/// # Safety
+/// The vector tables must be valid and not used by anything else while they are initialized.
+pub(crate) unsafe fn install(vector_tables: [*mut VectorTable; 2], handlers: [extern "C" fn(); 2]) {
+    let mut pac = pac::Peripherals::steal();
+    let mut sio = Sio::new(pac.SIO);
+
+    for (vector_table, handler) in vector_tables.into_iter().zip(handlers) {
+        let vector_table = &mut *vector_table;
+        vector_table.init(&mut pac.PPB);
+        vector_table.register_handler(pac::Interrupt::TIMER_IRQ_0 as usize, handler);
+    }
+    (*vector_tables[0]).activate(&mut pac.PPB);
+    asm::dsb();
+    asm::isb();
+
+    // core1 activates its own vector table and answers when it is done
+    sio.fifo.write_blocking(vector_tables[1] as u32);
+    while sio.fifo.read_blocking() != CORE1_READY {}
+}
+
+/// Executes `f` on core0 while core1 spins in a loop in SRAM3, so that `f` can stop the XIP, e.g.
+/// to change the flash clock divisor (see `clock_config`). `init()` must have been called before.
+pub fn with_core1_parked<R>(f: impl FnOnce() -> R) -> R {
+    let pac = unsafe { pac::Peripherals::steal() };
+    let mut sio = Sio::new(pac.SIO);
+    sio.fifo.write_blocking(CORE1_PARK);
+    while CORE1_PARKED.load(Ordering::Acquire) == 0 {}
+
+    let r = f();
+    CORE1_PARKED.store(0, Ordering::Release);
+    r
+}
+
+/// Forces TIMER_IRQ_0 on both cores and waits until both handlers are done
+#[link_section = ".sram2_code"]
+pub(crate) fn trigger() {
+    DONE[0].store(0, Ordering::Relaxed);
+    DONE[1].store(0, Ordering::Relaxed);
+    compiler_fence(Ordering::SeqCst);
+
+    let timer = unsafe { &*pac::TIMER::ptr() };
+    // force trigger timer (ALARM0) interupt
+    timer.inte.write(|wr| wr.alarm_0().set_bit());
+    timer.intf.write(|wr| wr.alarm_0().set_bit());
+    while !(DONE[0].load(Ordering::Acquire) != 0 && DONE[1].load(Ordering::Acquire) != 0) {}
+}
+
+/// Address of the flag a handler installed with `install()` must set to 1 when it is done, after
+/// clearing the forced interrupt
+pub(crate) fn done_flag(core: usize) -> *mut u32 {
+    DONE[core].as_ptr()
+}
+
+#[link_section = ".sram2_code"]
+extern "C" fn core0_timer_irq<T>() {
+    unsafe { execute_task::<T>(0, core::ptr::addr_of!(CORE0_TASK)) }
+}
+
+#[link_section = ".sram3_code"]
+extern "C" fn core1_timer_irq<T>() {
+    unsafe { execute_task::<T>(1, core::ptr::addr_of!(CORE1_TASK)) }
+}
+
+/// Body of both handlers, inlined so that they execute the same instructions from their own bank
+#[inline(always)]
+unsafe fn execute_task<T>(core: usize, task: *const Task) {
+    let function = core::mem::transmute::<*const (), fn() -> T>((*task).function);
+    let result = function();
+    ((*task).result as *mut T).write(result);
+    // stop this triggering interrupt
+    (*pac::TIMER::ptr()).intf.reset();
+    DONE[core].store(1, Ordering::Release);
+}
+
+#[link_section = ".sram3_code"]
+fn core1_main() -> ! {
+    stack::install_guard();
+    let pac = unsafe { pac::Peripherals::steal() };
+    let mut sio = Sio::new(pac.SIO);
+
+    // configure systic to prepare for measurements
+    let core = unsafe { pac::CorePeripherals::steal() };
+    let _cycle_counter = CycleCounter::new(core.SYST);
+
+    // unpend and unmask timer interrupts
+    pac::NVIC::unpend(pac::Interrupt::TIMER_IRQ_0);
+    unsafe { pac::NVIC::unmask(pac::Interrupt::TIMER_IRQ_0) };
+
+    loop {
+        // core0 sends the address of the vector table to use for the next run
+        let vector_table = sio.fifo.read_blocking();
+        if vector_table == CORE1_PARK {
+            park();
+            continue;
+        }
+        pac.PPB.vtor.write(|w| unsafe { w.bits(vector_table) });
+        asm::dsb();
+        asm::isb();
+        // inform Core 0 that the timer interrupt can be triggered
+        sio.fifo.write_blocking(CORE1_READY);
+    }
+}
+
+/// Sets `CORE1_PARKED` and waits until core0 clears it. The loop is a single `asm!` block so that
+/// nothing is fetched from flash once the flag is set, even at opt-level 0.
+#[link_section = ".sram3_code"]
+fn park() {
+    unsafe {
+        core::arch::asm!(
+            "movs {tmp}, #1",
+            "str {tmp}, [{flag}]",
+            "2:",
+            "ldr {tmp}, [{flag}]",
+            "cmp {tmp}, #0",
+            "bne 2b",
+            flag = in(reg) CORE1_PARKED.as_ptr(),
+            tmp = out(reg) _,
+        );
+    }
+}
@@ -0,0 +1,187 @@
+use rp2040_hal::pac;
+
+/// Bus performance counters are 24 bit, they stop at this value instead of wrapping around
+const PERFCTR_MAX: u32 = 0x00FF_FFFF;
+
+/// Events that can be counted by the bus performance counters, the discriminant is the PERFSEL value.
+///
+/// `*_contested` events count the accesses to a slave that completed after being delayed by an
+/// access of another master, the other events count all completed accesses to that slave.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, defmt::Format)]
+#[repr(u8)]
+pub enum PerfEvent {
+    ApbContested = 0,
+    Apb = 1,
+    FastperiContested = 2,
+    Fastperi = 3,
+    Sram5Contested = 4,
+    Sram5 = 5,
+    Sram4Contested = 6,
+    Sram4 = 7,
+    Sram3Contested = 8,
+    Sram3 = 9,
+    Sram2Contested = 10,
+    Sram2 = 11,
+    Sram1Contested = 12,
+    Sram1 = 13,
+    Sram0Contested = 14,
+    Sram0 = 15,
+    XipMainContested = 16,
+    XipMain = 17,
+    RomContested = 18,
+    Rom = 19,
+}
+
+impl PerfEvent {
+    /// Name of the event as written in the datasheet
+    pub fn name(&self) -> &'static str {
+        match self {
+            PerfEvent::ApbContested => "apb_contested",
+            PerfEvent::Apb => "apb",
+            PerfEvent::FastperiContested => "fastperi_contested",
+            PerfEvent::Fastperi => "fastperi",
+            PerfEvent::Sram5Contested => "sram5_contested",
+            PerfEvent::Sram5 => "sram5",
+            PerfEvent::Sram4Contested => "sram4_contested",
+            PerfEvent::Sram4 => "sram4",
+            PerfEvent::Sram3Contested => "sram3_contested",
+            PerfEvent::Sram3 => "sram3",
+            PerfEvent::Sram2Contested => "sram2_contested",
+            PerfEvent::Sram2 => "sram2",
+            PerfEvent::Sram1Contested => "sram1_contested",
+            PerfEvent::Sram1 => "sram1",
+            PerfEvent::Sram0Contested => "sram0_contested",
+            PerfEvent::Sram0 => "sram0",
+            PerfEvent::XipMainContested => "xip_main_contested",
+            PerfEvent::XipMain => "xip_main",
+            PerfEvent::RomContested => "rom_contested",
+            PerfEvent::Rom => "rom",
+        }
+    }
+}
+
+/// Values of the four counters, together with the event each of them was counting
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PerfCounts {
+    pub events: [PerfEvent; 4],
+    pub counts: [u32; 4],
+}
+
+impl PerfCounts {
+    /// Count of the first counter selecting `event`, if any
+    pub fn get(&self, event: PerfEvent) -> Option<u32> {
+        self.events
+            .iter()
+            .position(|e| *e == event)
+            .map(|i| self.counts[i])
+    }
+
+    /// Whether counter `counter` reached its maximum value, in which case the real count is unknown
+    pub fn is_saturated(&self, counter: usize) -> bool {
+        self.counts[counter] >= PERFCTR_MAX
+    }
+
+    /// Whether any of the counters saturated
+    pub fn saturated(&self) -> bool {
+        (0..4).any(|i| self.is_saturated(i))
+    }
+}
+
+impl defmt::Format for PerfCounts {
+    fn format(&self, f: defmt::Formatter) {
+        defmt::write!(f, "[");
+        for i in 0..4 {
+            if i > 0 {
+                defmt::write!(f, ", ");
+            }
+            defmt::write!(f, "{=str} = {}", self.events[i].name(), self.counts[i]);
+            if self.is_saturated(i) {
+                defmt::write!(f, " (saturated)");
+            }
+        }
+        defmt::write!(f, "]");
+    }
+}
+
+/// The four bus performance counters of BUSCTRL.
+///
+/// ```ignore
+/// let mut perf_counters = PerfCounters::new(
+///     pac.BUSCTRL,
+///     &mut pac.RESETS,
+///     [PerfEvent::Sram4Contested, PerfEvent::XipMainContested, PerfEvent::ApbContested, PerfEvent::RomContested],
+/// );
+/// let counts = perf_counters.measure(|| {
+///     // concurrent accesses
+/// });
+/// println!("contested accesses {}", counts);
+/// ```
+///
+/// BUSCTRL also holds the bus priority registers, they are still available through `busctrl()`.
+pub struct PerfCounters {
+    busctrl: pac::BUSCTRL,
+    events: [PerfEvent; 4],
+}
+
+impl PerfCounters {
+    /// Takes BUSCTRL out of reset, selects `events` on counters 0 to 3 and clears the counters
+    pub fn new(busctrl: pac::BUSCTRL, resets: &mut pac::RESETS, events: [PerfEvent; 4]) -> Self {
+        resets.reset.modify(|_, w| w.busctrl().clear_bit());
+        while resets.reset_done.read().busctrl().bit_is_clear() {}
+
+        let mut counters = PerfCounters { busctrl, events };
+        counters.select(events);
+        counters
+    }
+
+    /// Selects the events counted by counters 0 to 3 and clears the counters
+    pub fn select(&mut self, events: [PerfEvent; 4]) {
+        self.events = events;
+        let sel = |i: usize| events[i] as u32;
+        unsafe {
+            self.busctrl.perfsel0.write(|w| w.bits(sel(0)));
+            self.busctrl.perfsel1.write(|w| w.bits(sel(1)));
+            self.busctrl.perfsel2.write(|w| w.bits(sel(2)));
+            self.busctrl.perfsel3.write(|w| w.bits(sel(3)));
+        }
+        self.reset();
+    }
+
+    /// Clears the four counters, writing any value to a counter clears it
+    pub fn reset(&mut self) {
+        self.busctrl.perfctr0.reset();
+        self.busctrl.perfctr1.reset();
+        self.busctrl.perfctr2.reset();
+        self.busctrl.perfctr3.reset();
+    }
+
+    /// Reads the four counters, they keep counting
+    pub fn read(&self) -> PerfCounts {
+        PerfCounts {
+            events: self.events,
+            counts: [
+                self.busctrl.perfctr0.read().bits(),
+                self.busctrl.perfctr1.read().bits(),
+                self.busctrl.perfctr2.read().bits(),
+                self.busctrl.perfctr3.read().bits(),
+            ],
+        }
+    }
+
+    /// Clears the counters, runs `f` and returns the events counted while it was running
+    pub fn measure<F: FnOnce()>(&mut self, f: F) -> PerfCounts {
+        self.reset();
+        f();
+        self.read()
+    }
+
+    /// The BUSCTRL registers, e.g. to configure the bus priorities
+    pub fn busctrl(&self) -> &pac::BUSCTRL {
+        &self.busctrl
+    }
+
+    /// Gives BUSCTRL back
+    pub fn free(self) -> pac::BUSCTRL {
+        self.busctrl
+    }
+}
@@ -0,0 +1,59 @@
+//! Machine-readable results, parsed on the host by `experiment_report` to regenerate the result
+//! tables of the README and CSV files.
+//!
+//! A record is a single defmt line made of `@<table>` followed by `key=value` fields, values
+//! don't contain spaces:
+//!
+//! ```text
+//! @experiments experiment=exp1 sys_mhz=125 proc0=1 proc1=1 ... core0_cycles=3 core1_cycles=2 ... sram4_contested=1 verdict=pass
+//! ```
+//!
+//! All the records of a table become the rows of that table, each key a column. The experiments
+//! still print their human readable lines next to the records.
+//!
+//! The `verdict` of an experiment is `pass`, `fail` when the results differ from the expectations,
+//! or `inconclusive` when they can't be checked (see `verdict`).
+//!
+//! A measurement repeated over many trials (see `trials`) is printed as one record per quantity,
+//! with the fields of `Summary`.
+
+use crate::{
+    stats::{Histogram, Stats},
+    PerfCounts,
+};
+
+/// Fields `<event>=<count>` of the four performance counters, for use in a record
+pub struct Counts<'a>(pub &'a PerfCounts);
+
+impl defmt::Format for Counts<'_> {
+    fn format(&self, f: defmt::Formatter) {
+        for i in 0..4 {
+            if i > 0 {
+                defmt::write!(f, " ");
+            }
+            defmt::write!(f, "{=str}={}", self.0.events[i].name(), self.0.counts[i]);
+        }
+    }
+}
+
+/// Fields `trials=... min=... max=... mean=... p50=... p90=... p99=... histogram=...` of the
+/// statistics of a quantity measured over many trials, see `stats`
+pub struct Summary<'a>(pub &'a Stats);
+
+impl defmt::Format for Summary<'_> {
+    fn format(&self, f: defmt::Formatter) {
+        let stats = self.0;
+        defmt::write!(
+            f,
+            "trials={} min={} max={} mean={=f32} p50={} p90={} p99={} histogram={}",
+            stats.count(),
+            stats.min(),
+            stats.max(),
+            stats.mean(),
+            stats.percentile(50),
+            stats.percentile(90),
+            stats.percentile(99),
+            Histogram(stats)
+        );
+    }
+}
@@ -0,0 +1,40 @@
+//! Copy of the SRAM sections from flash at boot.
+//!
+//! `.sram2_code`, `.sram3_code`, ... are linked at their address in SRAM with a load address in
+//! flash (see `build.rs`), like `.data`. Without a copy, the experiments only work when a debugger
+//! loads the ELF, not after flashing a UF2 or a power cycle. `memory.x` also emits a table in flash
+//! with a `(start, end, load address)` entry for each of these sections, between
+//! `__sram_load_table_start` and `__sram_load_table_end`.
+//!
+//! The copy is made by `__pre_init`, which the reset handler of cortex-m-rt calls before the
+//! initialization of `.data` and `.bss`. Nothing is initialized at that point, so it is written in
+//! assembly, and it stays in `.text` as the code it copies is not in SRAM yet. The sections are
+//! word aligned and their size is a multiple of 4. `__pre_init` then paints the stacks of both
+//! cores (see `stack`).
+
+core::arch::global_asm!(
+    ".section .text.__pre_init, \"ax\"",
+    ".global __pre_init",
+    ".type __pre_init, %function",
+    ".thumb_func",
+    "__pre_init:",
+    "    push {{r4, r5, lr}}",
+    "    ldr r0, =__sram_load_table_start",
+    "    ldr r1, =__sram_load_table_end",
+    // r2 = start, r3 = end, r4 = load address of the next section
+    "1:  cmp r0, r1",
+    "    bhs 3f",
+    "    ldm r0!, {{r2, r3, r4}}",
+    "2:  cmp r2, r3",
+    "    bhs 1b",
+    "    ldr r5, [r4]",
+    "    str r5, [r2]",
+    "    adds r4, #4",
+    "    adds r2, #4",
+    "    b 2b",
+    "3:  bl __paint_stacks",
+    "    pop {{r4, r5, pc}}",
+    // literal pool of the `ldr =`
+    ".ltorg",
+    ".size __pre_init, . - __pre_init",
+);
@@ -0,0 +1,206 @@
+//! Stacks of the two cores, painted at boot and protected by a guard.
+//!
+//! `memory.x` reserves the stack of each core at the end of the region given by `memory.toml`,
+//! above a guard of 256 bytes, and `_stack_start` of cortex-m-rt is the top of the stack of core0.
+//! Both stacks are filled with `PAINT` by `__pre_init` (see `sram_load`), the lowest word that
+//! doesn't hold it anymore gives the maximum depth reached by a stack:
+//!
+//! ```ignore
+//! let _ = core1.spawn(unsafe { stack::core1_stack() }, || core1_main());
+//! // ...
+//! defmt::println!("core1 used {} bytes of stack", stack::high_water_mark(1));
+//! ```
+//!
+//! The lowest `MARGIN` bytes of each stack are headroom that is not meant to be used: `check()`
+//! reports a stack overflow as soon as a push reached them, which `experiment::with_runner()`
+//! does after the experiments.
+//!
+//! `install_guard()` makes the guard of the calling core inaccessible with its MPU, so that an
+//! overflow past the margin stops the core instead of overwriting the memory below the stack.
+//! This is a hard stop, not a report: on ARMv6-M the exception entry stacks the HardFault frame
+//! below the push that faulted, in the guard, and the fault during this stacking locks the core
+//! up before any handler runs. The debugger then reports the lockup, and `check()` can't be
+//! called anymore, hence the margin above the guard.
+
+use core::ptr::addr_of;
+
+use cortex_m::{asm, peripheral::MPU};
+use cortex_m_rt::{exception, ExceptionFrame};
+
+use crate::cycle_counter::core_id;
+
+/// Value of the stack words that were never written since boot
+pub const PAINT: u32 = 0xC5C5_C5C5;
+
+/// Bytes at the bottom of each stack, above its guard, whose use is reported as an overflow. The
+/// deepest push that `check()` can still report leaves room for an exception frame (32 bytes) and
+/// a handler below it.
+pub const MARGIN: u32 = 128;
+
+/// MPU region of the guard
+const GUARD_REGION: u32 = 0;
+
+// defined by `memory.x`
+extern "C" {
+    static __core0_stack_guard: u32;
+    static __core0_stack_bottom: u32;
+    static __core0_stack_top: u32;
+    static __core1_stack_guard: u32;
+    static __core1_stack_bottom: u32;
+    static __core1_stack_top: u32;
+}
+
+// Paints the stacks, called by `__pre_init`. The stack of core0 is in use, it is painted up to SP.
+core::arch::global_asm!(
+    ".section .text.__paint_stacks, \"ax\"",
+    ".global __paint_stacks",
+    ".type __paint_stacks, %function",
+    ".thumb_func",
+    "__paint_stacks:",
+    "    ldr r0, ={paint}",
+    "    ldr r1, =__core0_stack_bottom",
+    "    mov r2, sp",
+    "1:  cmp r1, r2",
+    "    bhs 2f",
+    "    str r0, [r1]",
+    "    adds r1, #4",
+    "    b 1b",
+    "2:  ldr r1, =__core1_stack_bottom",
+    "    ldr r2, =__core1_stack_top",
+    "3:  cmp r1, r2",
+    "    bhs 4f",
+    "    str r0, [r1]",
+    "    adds r1, #4",
+    "    b 3b",
+    "4:  bx lr",
+    ".ltorg",
+    ".size __paint_stacks, . - __paint_stacks",
+    paint = const PAINT,
+);
+
+/// Addresses of a stack, which grows down from `top` to `bottom`, and of its guard below it
+#[derive(Clone, Copy, Debug, PartialEq, Eq, defmt::Format)]
+pub struct Bounds {
+    pub guard: u32,
+    pub bottom: u32,
+    pub top: u32,
+}
+
+impl Bounds {
+    /// Size of the stack in bytes, without the guard
+    pub fn size(&self) -> u32 {
+        self.top - self.bottom
+    }
+}
+
+/// Bounds of the stack of `core`
+pub fn bounds(core: usize) -> Bounds {
+    let [guard, bottom, top] = match core {
+        0 => [
+            addr_of!(__core0_stack_guard),
+            addr_of!(__core0_stack_bottom),
+            addr_of!(__core0_stack_top),
+        ],
+        _ => [
+            addr_of!(__core1_stack_guard),
+            addr_of!(__core1_stack_bottom),
+            addr_of!(__core1_stack_top),
+        ],
+    };
+    Bounds {
+        guard: guard as u32,
+        bottom: bottom as u32,
+        top: top as u32,
+    }
+}
+
+/// Maximum number of bytes used on the stack of `core` since boot.
+///
+/// A word pushed with the value `PAINT` is not counted when it is the deepest one.
+pub fn high_water_mark(core: usize) -> u32 {
+    let bounds = bounds(core);
+    let mut address = bounds.bottom;
+    while address < bounds.top && unsafe { (address as *const u32).read_volatile() } == PAINT {
+        address += 4;
+    }
+    bounds.top - address
+}
+
+/// Stack overflow found by `check()`: the stack of `core` reached its margin
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Overflow {
+    pub core: usize,
+    /// High-water mark of the stack, in bytes
+    pub used: u32,
+    /// Size of the stack in bytes, with its margin
+    pub size: u32,
+}
+
+impl defmt::Format for Overflow {
+    fn format(&self, f: defmt::Formatter) {
+        defmt::write!(
+            f,
+            "stack overflow on core{=usize}: {=u32} of {=u32} bytes used, {=u32} bytes in the margin",
+            self.core,
+            self.used,
+            self.size,
+            self.used - (self.size - MARGIN)
+        );
+    }
+}
+
+/// High-water mark of the stack of `core` (see `high_water_mark()`), or an `Overflow` if it
+/// reached the margin.
+pub fn check(core: usize) -> Result<u32, Overflow> {
+    let used = high_water_mark(core);
+    let size = bounds(core).size();
+    if used > size - MARGIN {
+        Err(Overflow { core, used, size })
+    } else {
+        Ok(used)
+    }
+}
+
+/// Stack of core1, to start it with `Core::spawn()`.
+///
+/// # Safety
+/// Must be called once, core1 uses the stack until it is reset.
+pub unsafe fn core1_stack() -> &'static mut [usize] {
+    let bounds = bounds(1);
+    core::slice::from_raw_parts_mut(
+        bounds.bottom as *mut usize,
+        bounds.size() as usize / core::mem::size_of::<usize>(),
+    )
+}
+
+/// Makes the guard below the stack of the calling core inaccessible, so that an overflow past the
+/// margin locks the core up. Each core has its own MPU, it must be called on both cores.
+pub fn install_guard() {
+    let bounds = bounds(core_id());
+    let size = bounds.bottom - bounds.guard;
+    unsafe {
+        let mpu = &*MPU::PTR;
+        mpu.ctrl.write(0);
+        mpu.rnr.write(GUARD_REGION);
+        mpu.rbar.write(bounds.guard);
+        // XN, no access (AP = 0), SIZE = log2(size) - 1, enabled
+        mpu.rasr
+            .write(1 << 28 | (size.trailing_zeros() - 1) << 1 | 1);
+        // PRIVDEFENA: the default memory map everywhere else, HFNMIENA is left cleared
+        mpu.ctrl.write(1 << 2 | 1);
+    }
+    asm::dsb();
+    asm::isb();
+}
+
+#[exception]
+unsafe fn HardFault(frame: &ExceptionFrame) -> ! {
+    // an overflow in the guard never gets here, the core locks up while stacking this frame
+    defmt::error!(
+        "HardFault on core{=usize} at pc={=u32:#x}",
+        core_id(),
+        frame.pc()
+    );
+    // as panic-probe does, the core locks up and the debugger stops
+    asm::udf()
+}
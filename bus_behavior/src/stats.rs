@@ -0,0 +1,181 @@
+//! Streaming statistics of a measurement repeated over many trials.
+//!
+//! A single run of an experiment gives one sample, which says nothing about the worst case. A
+//! `Stats` aggregates the samples as they are measured, without storing them: count, min, max,
+//! mean and a histogram of `BINS` one-cycle bins, from which the percentiles are derived.
+//!
+//! ```ignore
+//! let mut stats = Stats::new();
+//! trials.repeat(|_| stats.add(measure()));
+//! defmt::println!("{}", stats);
+//! ```
+//!
+//! The histogram starts `BINS / 4` below the first sample, so a distribution spreading over less
+//! than `BINS` cycles around it is kept exactly. The samples outside the histogram are counted
+//! in an underflow and an overflow bin; a percentile falling in one of them is given as the
+//! upper bound of that bin, i.e. the last cycle below the histogram or the max.
+
+/// Number of bins of the histogram, one clock cycle or one count each
+pub const BINS: usize = 64;
+
+/// Aggregated samples of one quantity
+#[derive(Clone, Copy, Debug)]
+pub struct Stats {
+    count: u32,
+    min: u32,
+    max: u32,
+    sum: u64,
+    /// Value of the first bin, set by the first sample
+    origin: u32,
+    bins: [u32; BINS],
+    /// Samples below `origin`
+    underflow: u32,
+    /// Samples above the last bin
+    overflow: u32,
+}
+
+impl Stats {
+    pub const fn new() -> Self {
+        Stats {
+            count: 0,
+            min: u32::MAX,
+            max: 0,
+            sum: 0,
+            origin: 0,
+            bins: [0; BINS],
+            underflow: 0,
+            overflow: 0,
+        }
+    }
+
+    /// Adds one sample
+    pub fn add(&mut self, value: u32) {
+        if self.count == 0 {
+            self.origin = value.saturating_sub(BINS as u32 / 4);
+        }
+        self.count += 1;
+        self.min = self.min.min(value);
+        self.max = self.max.max(value);
+        self.sum += value as u64;
+        if value < self.origin {
+            self.underflow += 1;
+        } else if value - self.origin >= BINS as u32 {
+            self.overflow += 1;
+        } else {
+            self.bins[(value - self.origin) as usize] += 1;
+        }
+    }
+
+    /// Number of samples
+    pub fn count(&self) -> u32 {
+        self.count
+    }
+
+    /// Smallest sample, 0 without samples
+    pub fn min(&self) -> u32 {
+        if self.count == 0 {
+            0
+        } else {
+            self.min
+        }
+    }
+
+    /// Largest sample, 0 without samples
+    pub fn max(&self) -> u32 {
+        self.max
+    }
+
+    /// Difference between the largest and the smallest sample
+    pub fn jitter(&self) -> u32 {
+        self.max() - self.min()
+    }
+
+    pub fn mean(&self) -> f32 {
+        if self.count == 0 {
+            0.0
+        } else {
+            self.sum as f32 / self.count as f32
+        }
+    }
+
+    /// Smallest value that `percent` % of the samples don't exceed (nearest rank), 0 without
+    /// samples. `percentile(100)` is the max.
+    pub fn percentile(&self, percent: u32) -> u32 {
+        if self.count == 0 {
+            return 0;
+        }
+        // rank of the sample, from 1, rounded up
+        let rank = ((self.count as u64 * percent.min(100) as u64).div_ceil(100)).max(1) as u32;
+        let mut seen = self.underflow;
+        if seen >= rank {
+            return self.origin - 1;
+        }
+        for (i, &n) in self.bins.iter().enumerate() {
+            seen += n;
+            if seen >= rank {
+                return self.origin + i as u32;
+            }
+        }
+        self.max
+    }
+
+    /// Non empty bins of the histogram as (value, samples), in increasing order. The samples out
+    /// of the histogram are not included, see `out_of_range()`.
+    pub fn histogram(&self) -> impl Iterator<Item = (u32, u32)> + '_ {
+        self.bins
+            .iter()
+            .enumerate()
+            .filter(|(_, &n)| n > 0)
+            .map(|(i, &n)| (self.origin + i as u32, n))
+    }
+
+    /// Number of samples below and above the histogram
+    pub fn out_of_range(&self) -> (u32, u32) {
+        (self.underflow, self.overflow)
+    }
+}
+
+impl Default for Stats {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl defmt::Format for Stats {
+    fn format(&self, f: defmt::Formatter) {
+        defmt::write!(
+            f,
+            "{} trials: min {} max {} mean {=f32} p50 {} p90 {} p99 {} jitter {}",
+            self.count,
+            self.min(),
+            self.max(),
+            self.mean(),
+            self.percentile(50),
+            self.percentile(90),
+            self.percentile(99),
+            self.jitter()
+        );
+    }
+}
+
+/// The histogram of a `Stats` as `value:samples` pairs separated by commas, e.g. `2:3,3:997`, the
+/// samples below and above the histogram as `<value:samples` and `>value:samples`
+pub struct Histogram<'a>(pub &'a Stats);
+
+impl defmt::Format for Histogram<'_> {
+    fn format(&self, f: defmt::Formatter) {
+        let stats = self.0;
+        if stats.underflow > 0 {
+            defmt::write!(f, "<{}:{},", stats.origin, stats.underflow);
+        }
+        for (i, (value, n)) in stats.histogram().enumerate() {
+            if i > 0 {
+                defmt::write!(f, ",");
+            }
+            defmt::write!(f, "{}:{}", value, n);
+        }
+        if stats.overflow > 0 {
+            defmt::write!(f, ",>{}:{}", stats.origin + BINS as u32 - 1, stats.overflow);
+        }
+    }
+}
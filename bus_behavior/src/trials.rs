@@ -0,0 +1,110 @@
+//! Repetition of a measurement, with a perturbation of the system between the trials.
+//!
+//! Repeating a measurement in exactly the same conditions mostly gives the same sample again. The
+//! `Perturbation` applied before each trial changes what the measured code runs into: a random
+//! delay shifts the trial relative to the loops core1 and the DMA are executing, and a flush of
+//! the XIP cache makes the code executed from flash miss again.
+//!
+//! ```ignore
+//! const TRIALS: Trials = Trials {
+//!     count: 1000,
+//!     perturbation: Perturbation { max_delay: 64, flush_xip_cache: true },
+//! };
+//! let mut stats = Stats::new();
+//! TRIALS.repeat(|_| stats.add(measure()));
+//! ```
+
+use cortex_m::asm;
+
+/// Writing 1 flushes the XIP cache, reading it back stalls until the flush is complete
+const XIP_CTRL_FLUSH: *mut u32 = 0x1400_0004 as *mut u32;
+
+/// Seed of the delays, the same sequence of delays is applied at every run
+const SEED: u32 = 0x2507_2040;
+
+/// What is done before each trial
+#[derive(Clone, Copy, Debug, PartialEq, Eq, defmt::Format)]
+pub struct Perturbation {
+    /// A random delay of 0 to `max_delay` clock cycles, 0 for no delay. `asm::delay()` executes a
+    /// loop, so the delays are only approximately uniform at the cycle level.
+    pub max_delay: u32,
+    /// Flush the XIP cache
+    pub flush_xip_cache: bool,
+}
+
+impl Perturbation {
+    pub const NONE: Perturbation = Perturbation {
+        max_delay: 0,
+        flush_xip_cache: false,
+    };
+
+    /// Name used in the records, e.g. `delay64+flush`
+    pub fn name(&self) -> PerturbationName {
+        PerturbationName(*self)
+    }
+
+    fn apply(&self, rng: &mut XorShift) {
+        if self.flush_xip_cache {
+            unsafe {
+                XIP_CTRL_FLUSH.write_volatile(1);
+                let _ = XIP_CTRL_FLUSH.read_volatile();
+            }
+        }
+        if self.max_delay > 0 {
+            asm::delay(rng.next() % (self.max_delay + 1));
+        }
+    }
+}
+
+/// Formats a `Perturbation` without spaces, for the records
+pub struct PerturbationName(Perturbation);
+
+impl defmt::Format for PerturbationName {
+    fn format(&self, f: defmt::Formatter) {
+        let p = self.0;
+        match (p.max_delay, p.flush_xip_cache) {
+            (0, false) => defmt::write!(f, "none"),
+            (0, true) => defmt::write!(f, "flush"),
+            (delay, false) => defmt::write!(f, "delay{}", delay),
+            (delay, true) => defmt::write!(f, "delay{}+flush", delay),
+        }
+    }
+}
+
+/// How many times a measurement is repeated, and what is perturbed between the trials
+#[derive(Clone, Copy, Debug, PartialEq, Eq, defmt::Format)]
+pub struct Trials {
+    pub count: u32,
+    pub perturbation: Perturbation,
+}
+
+impl Trials {
+    /// A single trial without perturbation, what the experiments did before trials existed
+    pub const ONCE: Trials = Trials {
+        count: 1,
+        perturbation: Perturbation::NONE,
+    };
+
+    /// Calls `f` with the index of each trial, after applying the perturbation
+    pub fn repeat(&self, mut f: impl FnMut(u32)) {
+        let mut rng = XorShift(SEED);
+        for trial in 0..self.count {
+            self.perturbation.apply(&mut rng);
+            f(trial);
+        }
+    }
+}
+
+/// xorshift32 pseudo-random generator, good enough to spread the delays
+struct XorShift(u32);
+
+impl XorShift {
+    fn next(&mut self) -> u32 {
+        let mut x = self.0;
+        x ^= x << 13;
+        x ^= x >> 17;
+        x ^= x << 5;
+        self.0 = x;
+        x
+    }
+}
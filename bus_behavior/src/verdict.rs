@@ -0,0 +1,227 @@
+//! Expected results of an experiment, and the verdict of comparing them with a measurement.
+//!
+//! The expectations are the numbers documented in the README for each experiment, e.g. core0 3
+//! cycles, core1 2 cycles and sram4_contested = 1 for experiment 1. A run that doesn't reproduce
+//! them after a toolchain, HAL or clock change is reported as `FAIL` together with the
+//! differences, instead of relying on someone comparing printed numbers by eye. A measurement that
+//! can't be compared, because an expected event wasn't counted or its counter saturated, is
+//! `INCONCLUSIVE`.
+
+use crate::{PerfCounts, PerfEvent};
+
+/// Maximum number of differences kept in a `Diff`: cycles and value of both cores, and 4 event
+/// counts. Further differences are only counted.
+const MAX_MISMATCHES: usize = 8;
+
+/// Results an experiment is expected to produce. `None` and the events that are not listed are
+/// not checked.
+#[derive(Clone, Copy, Debug)]
+pub struct Expectation {
+    /// Clock cycles taken by the operations of each core
+    pub cycles: [Option<u32>; 2],
+    /// Value loaded by the last `Op::Ldr` of each core
+    pub values: [Option<u32>; 2],
+    /// Counts of bus events, at most 4 since there are 4 performance counters. Each event must
+    /// be counted during the experiment.
+    pub counts: &'static [(PerfEvent, u32)],
+}
+
+impl Expectation {
+    /// Nothing is checked, the verdict is always a pass
+    pub const NONE: Expectation = Expectation {
+        cycles: [None; 2],
+        values: [None; 2],
+        counts: &[],
+    };
+
+    /// Compares `measurement` with the expectations
+    pub fn check(&self, measurement: &Measurement) -> Verdict {
+        let mut diff = Diff::new();
+        for core in 0..2 {
+            if let Some(expected) = self.cycles[core] {
+                diff.compare(Quantity::Cycles(core), expected, measurement.cycles[core]);
+            }
+            if let Some(expected) = self.values[core] {
+                diff.compare(Quantity::Value(core), expected, measurement.values[core]);
+            }
+        }
+        for &(event, expected) in self.counts {
+            let counts = &measurement.counts;
+            let Some(counter) = counts.events.iter().position(|e| *e == event) else {
+                return Verdict::Inconclusive(Unchecked::NotCounted(event));
+            };
+            if counts.is_saturated(counter) {
+                return Verdict::Inconclusive(Unchecked::Saturated(event));
+            }
+            diff.compare(Quantity::Count(event), expected, counts.counts[counter]);
+        }
+
+        if diff.is_empty() {
+            Verdict::Pass
+        } else {
+            Verdict::Fail(diff)
+        }
+    }
+}
+
+/// What an experiment measured
+#[derive(Clone, Copy, Debug)]
+pub struct Measurement {
+    /// Clock cycles taken by the operations of each core
+    pub cycles: [u32; 2],
+    /// Value loaded by the last `Op::Ldr` of each core
+    pub values: [u32; 2],
+    /// Bus events counted while the operations executed
+    pub counts: PerfCounts,
+}
+
+/// A checked quantity
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Quantity {
+    /// Clock cycles of a core
+    Cycles(usize),
+    /// Value loaded by a core
+    Value(usize),
+    /// Count of a bus event
+    Count(PerfEvent),
+}
+
+impl defmt::Format for Quantity {
+    fn format(&self, f: defmt::Formatter) {
+        match self {
+            Quantity::Cycles(core) => defmt::write!(f, "core{} cycles", core),
+            Quantity::Value(core) => defmt::write!(f, "core{} value", core),
+            Quantity::Count(event) => defmt::write!(f, "{=str}", event.name()),
+        }
+    }
+}
+
+/// A quantity that differs from its expected value
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Mismatch {
+    pub quantity: Quantity,
+    pub expected: u32,
+    pub measured: u32,
+}
+
+impl defmt::Format for Mismatch {
+    fn format(&self, f: defmt::Formatter) {
+        defmt::write!(
+            f,
+            "{}: expected {}, measured {}",
+            self.quantity,
+            self.expected,
+            self.measured
+        );
+    }
+}
+
+/// Differences between the expectations and a measurement
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Diff {
+    mismatches: [Mismatch; MAX_MISMATCHES],
+    len: usize,
+    /// Differences that didn't fit in `mismatches`, when an expectation lists an event twice
+    omitted: usize,
+}
+
+impl Diff {
+    fn new() -> Self {
+        Diff {
+            mismatches: [Mismatch {
+                quantity: Quantity::Cycles(0),
+                expected: 0,
+                measured: 0,
+            }; MAX_MISMATCHES],
+            len: 0,
+            omitted: 0,
+        }
+    }
+
+    /// Records a mismatch if `measured` is not `expected`, or only counts it when the diff is full
+    fn compare(&mut self, quantity: Quantity, expected: u32, measured: u32) {
+        if measured == expected {
+            return;
+        }
+        if self.len == MAX_MISMATCHES {
+            self.omitted += 1;
+            return;
+        }
+        self.mismatches[self.len] = Mismatch {
+            quantity,
+            expected,
+            measured,
+        };
+        self.len += 1;
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0 && self.omitted == 0
+    }
+
+    /// Number of mismatches that are not in `mismatches()`
+    pub fn omitted(&self) -> usize {
+        self.omitted
+    }
+
+    pub fn mismatches(&self) -> &[Mismatch] {
+        &self.mismatches[..self.len]
+    }
+}
+
+impl defmt::Format for Diff {
+    fn format(&self, f: defmt::Formatter) {
+        defmt::write!(f, "[");
+        for (i, mismatch) in self.mismatches().iter().enumerate() {
+            if i > 0 {
+                defmt::write!(f, ", ");
+            }
+            defmt::write!(f, "{}", mismatch);
+        }
+        if self.omitted > 0 {
+            defmt::write!(f, " and {} more", self.omitted);
+        }
+        defmt::write!(f, "]");
+    }
+}
+
+/// Why a measurement could not be checked
+#[derive(Clone, Copy, Debug, PartialEq, Eq, defmt::Format)]
+pub enum Unchecked {
+    /// An expected event is not selected on any of the performance counters
+    NotCounted(PerfEvent),
+    /// The counter of an expected event saturated, its real count is unknown
+    Saturated(PerfEvent),
+}
+
+/// Outcome of checking a measurement against the expectations
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Verdict {
+    /// All the expectations are met
+    Pass,
+    /// The measurement differs from the expectations
+    Fail(Diff),
+    /// The measurement can't be compared with the expectations
+    Inconclusive(Unchecked),
+}
+
+impl Verdict {
+    /// Name of the verdict in the records, see `record`
+    pub fn name(&self) -> &'static str {
+        match self {
+            Verdict::Pass => "pass",
+            Verdict::Fail(_) => "fail",
+            Verdict::Inconclusive(_) => "inconclusive",
+        }
+    }
+}
+
+impl defmt::Format for Verdict {
+    fn format(&self, f: defmt::Formatter) {
+        match self {
+            Verdict::Pass => defmt::write!(f, "PASS"),
+            Verdict::Fail(diff) => defmt::write!(f, "FAIL {}", diff),
+            Verdict::Inconclusive(reason) => defmt::write!(f, "INCONCLUSIVE {}", reason),
+        }
+    }
+}
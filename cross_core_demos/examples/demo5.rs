@@ -0,0 +1,629 @@
+// DEMO5: Latency of the cross-core signaling mechanisms, in clock cycles
+//
+// Each mechanism of the previous demos is measured one way, from the instruction before the
+// sender signals to the first instruction the receiver executes once signaled, and round trip,
+// from core0 signaling until it gets the answer of core1 through the same mechanism:
+// - fifo: raw FIFO write -> polling read (DEMO1)
+// - fifo_irq: FIFO write -> SIO_IRQ_PROC proxy -> entry of the forwarded TIMER_IRQ_1 (DEMO4)
+// - queue: MessageQueue push + pend -> pop in the TIMER_IRQ_0 dispatcher (DEMO4 spawn API)
+// - sev: SEV -> return from WFE (DEMO3)
+//
+// The Systick of a core only sees that core, so the one way latencies are timestamped with the
+// counter of PWM slice 0, running at the system clock and read by both cores through the same
+// APB access. The round trips are measured with the Systick of core0. The cost of the timestamp
+// read itself is measured back-to-back and subtracted.
+//
+// Each measurement is repeated TRIALS times with an idle bus, then with DMA channel 0 sweeping the
+// striped SRAM (SRAM0-3, where the stacks, the queues and the flags live) as fast as it can. The
+// code runs from flash, the first trial of each measurement pays for the XIP cache misses, which
+// shows in the max.
+//
+// # Assumption/Expected result
+// Not verified on hardware yet, these are estimates from the instruction timings and the exception
+// entry latency of the Cortex-M0+:
+// - the raw FIFO is the fastest, a handful of cycles: the polling loop sees VLD the cycle after
+//   the write
+// - fifo_irq adds two exception entries, the proxy and the forwarded interrupt, ~15 cycles each
+// - queue adds the push, the pop and the function calls around them to fifo_irq
+// - sev is as fast as the raw FIFO, plus the wake up from WFE
+// - the contended bus mostly shows on the mechanisms going through SRAM: the exception stacking,
+//   the queue and the flags
+
+#![no_std]
+#![no_main]
+
+use core::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+
+use cortex_m::asm;
+use cortex_m::peripheral::syst::SystClkSource;
+use defmt::*;
+use defmt_rtt as _;
+
+// Ensure we halt the program on panic (if we don't mention this crate it won't
+// be linked)
+use panic_probe as _;
+
+// Alias for our HAL crate
+use rp2040_hal as hal;
+
+// A shorter alias for the Peripheral Access Crate, which provides low-level
+// register access
+use hal::pac;
+
+// Some traits we need
+use embedded_hal::digital::v2::OutputPin;
+
+use cross_core_demos::{CrossCore, MessageQueue};
+use hal::multicore::{Multicore, Stack};
+use hal::pac::interrupt;
+
+static mut CORE1_STACK: Stack<4096> = Stack::new();
+
+/// The linker will place this boot block at the start of our program image. We
+/// need this to help the ROM bootloader get our code up and running.
+#[link_section = ".boot2"]
+#[used]
+pub static BOOT2: [u8; 256] = rp2040_boot2::BOOT_LOADER_GENERIC_03H;
+
+/// External high-speed crystal on the Raspberry Pi Pico board is 12 MHz.
+const XTAL_FREQ_HZ: u32 = 12_000_000u32;
+
+/// Number of trials of each measurement
+const TRIALS: u32 = 100;
+
+/// SIO base, FIFO_ST is at offset 0x50, FIFO_WR at 0x54 and FIFO_RD at 0x58
+const SIO_BASE: u32 = 0xd000_0000;
+
+// PWM slice 0, the common timebase
+const PWM_CH0_CSR: *mut u32 = 0x4005_0000 as *mut u32;
+const PWM_CH0_DIV: *mut u32 = 0x4005_0004 as *mut u32;
+const PWM_CH0_CTR: u32 = 0x4005_0008;
+const PWM_CH0_TOP: *mut u32 = 0x4005_0010 as *mut u32;
+
+/// Current value of the Systick
+const SYST_CVR: u32 = 0xe000_e018;
+
+// DMA channel 0, contending the bus
+const DMA_CH0_READ_ADDR: *mut u32 = 0x5000_0000 as *mut u32;
+const DMA_CH0_WRITE_ADDR: *mut u32 = 0x5000_0004 as *mut u32;
+const DMA_CH0_TRANS_COUNT: *mut u32 = 0x5000_0008 as *mut u32;
+const DMA_CH0_CTRL_TRIG: *mut u32 = 0x5000_000c as *mut u32;
+const DMA_CHAN_ABORT: *mut u32 = 0x5000_0444 as *mut u32;
+/// EN, DATA_SIZE = word, INCR_READ, RING_SIZE = 1 KiB on the read side, CHAIN_TO itself (no
+/// chaining), TREQ_SEL = permanent (unpaced)
+const DMA_CTRL_SWEEP: u32 = 1 | (2 << 2) | (1 << 4) | (10 << 6) | (0x3f << 15);
+
+/// Swept by the DMA read master, the read ring needs the buffer aligned on its size
+#[repr(C, align(1024))]
+struct SweepBuffer([u32; 256]);
+static mut SWEEP_BUFFER: SweepBuffer = SweepBuffer([0; 256]);
+static mut SWEEP_SINK: u32 = 0;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Mechanism {
+    Fifo = 1,
+    FifoIrq = 2,
+    Queue = 3,
+    Sev = 4,
+}
+
+impl Mechanism {
+    const ALL: [Mechanism; 4] = [
+        Mechanism::Fifo,
+        Mechanism::FifoIrq,
+        Mechanism::Queue,
+        Mechanism::Sev,
+    ];
+
+    fn name(&self) -> &'static str {
+        match self {
+            Mechanism::Fifo => "fifo",
+            Mechanism::FifoIrq => "fifo_irq",
+            Mechanism::Queue => "queue",
+            Mechanism::Sev => "sev",
+        }
+    }
+
+    fn from_command(command: u32) -> Self {
+        Self::ALL[(command & 0xff) as usize - 1]
+    }
+}
+
+/// Set in a command when core1 has to answer
+const ROUND_TRIP: u32 = 1 << 8;
+
+// ======================================== Shared state ==========================================
+
+/// Next mechanism core1 waits on, 0 when none
+static COMMAND: AtomicU32 = AtomicU32::new(0);
+/// Set by core1 once it waits on the mechanism
+static CORE1_READY: AtomicBool = AtomicBool::new(false);
+/// Set by core1 once it was signaled (and has answered for a round trip)
+static CORE1_DONE: AtomicBool = AtomicBool::new(false);
+/// PWM counter when core1 was signaled
+static CORE1_STAMP: AtomicU32 = AtomicU32::new(0);
+/// Whether the handlers of core1 answer core0
+static ANSWER: AtomicBool = AtomicBool::new(false);
+/// Set by the handlers of core0 when they get the answer of core1
+static CORE0_DONE: AtomicBool = AtomicBool::new(false);
+/// Systick of core0 when it got the answer
+static CORE0_STAMP: AtomicU32 = AtomicU32::new(0);
+/// Flags of the sev mechanism, WFE can return on other events
+static SEV_FLAG: AtomicU32 = AtomicU32::new(0);
+static SEV_ANSWER: AtomicU32 = AtomicU32::new(0);
+
+static mut QUEUE0: core::mem::MaybeUninit<MessageQueue<u32, 4>> = core::mem::MaybeUninit::uninit();
+static mut QUEUE1: core::mem::MaybeUninit<MessageQueue<u32, 4>> = core::mem::MaybeUninit::uninit();
+
+fn cpuid() -> u32 {
+    unsafe { pac::Peripherals::steal().SIO.cpuid.read().bits() }
+}
+
+#[inline(always)]
+fn pwm_counter() -> u32 {
+    unsafe { (PWM_CH0_CTR as *const u32).read_volatile() }
+}
+
+#[inline(always)]
+fn systick() -> u32 {
+    unsafe { (SYST_CVR as *const u32).read_volatile() }
+}
+
+// ========================================= Statistics ===========================================
+
+#[derive(Clone, Copy)]
+struct Summary {
+    min: u32,
+    max: u32,
+    sum: u32,
+    count: u32,
+}
+
+impl Summary {
+    const fn new() -> Self {
+        Summary {
+            min: u32::MAX,
+            max: 0,
+            sum: 0,
+            count: 0,
+        }
+    }
+
+    fn add(&mut self, cycles: u32) {
+        self.min = self.min.min(cycles);
+        self.max = self.max.max(cycles);
+        self.sum += cycles;
+        self.count += 1;
+    }
+
+    fn mean(&self) -> u32 {
+        self.sum / self.count.max(1)
+    }
+}
+
+// ============================================ Core0 =============================================
+
+#[rp2040_hal::entry]
+fn main() -> ! {
+    // Grab our singleton objects
+    let mut pac = pac::Peripherals::take().unwrap();
+    let mut core = pac::CorePeripherals::take().unwrap();
+
+    // Set up the watchdog driver - needed by the clock setup code
+    let mut watchdog = hal::Watchdog::new(pac.WATCHDOG);
+
+    // Configure the clocks
+    let _clocks = hal::clocks::init_clocks_and_plls(
+        XTAL_FREQ_HZ,
+        pac.XOSC,
+        pac.CLOCKS,
+        pac.PLL_SYS,
+        pac.PLL_USB,
+        &mut pac.RESETS,
+        &mut watchdog,
+    )
+    .ok()
+    .unwrap();
+
+    // Systick of core0 counts down every clock cycle, for the round trips
+    core.SYST.set_clock_source(SystClkSource::Core);
+    core.SYST.set_reload(0x00ff_ffff);
+    core.SYST.clear_current();
+    core.SYST.enable_counter();
+
+    // PWM slice 0 counts up every clock cycle and wraps at 0xffff, for the one way latencies
+    pac.RESETS
+        .reset
+        .modify(|_, w| w.pwm().clear_bit().dma().clear_bit());
+    while pac.RESETS.reset_done.read().pwm().bit_is_clear() {}
+    while pac.RESETS.reset_done.read().dma().bit_is_clear() {}
+    unsafe {
+        PWM_CH0_TOP.write_volatile(0xffff);
+        PWM_CH0_DIV.write_volatile(1 << 4);
+        PWM_CH0_CSR.write_volatile(1);
+    }
+
+    // make sure the queues are initialized
+    unsafe {
+        (*QUEUE0.as_mut_ptr()) = Default::default();
+        (*QUEUE1.as_mut_ptr()) = Default::default();
+    }
+
+    let mut sio = hal::Sio::new(pac.SIO);
+    let mut mc = Multicore::new(&mut pac.PSM, &mut pac.PPB, &mut sio.fifo);
+    let cores = mc.cores();
+    let core1 = &mut cores[1];
+    let _ = core1.spawn(unsafe { &mut CORE1_STACK.mem }, || core1_main());
+
+    // Draining the fifo must be done after starting the Core1, because the FIFO is used during waking up Core1
+    // in order to pass the stack pointer and vector table
+    sio.fifo.drain();
+
+    unsafe {
+        core.NVIC.set_priority(pac::Interrupt::SIO_IRQ_PROC0, 0);
+        pac::NVIC::unmask(pac::Interrupt::TIMER_IRQ_0);
+        pac::NVIC::unmask(pac::Interrupt::TIMER_IRQ_1);
+    }
+
+    let (pwm_overhead, systick_overhead) = timestamp_overheads();
+    info!(
+        "timestamp overhead: pwm {} cycles, systick {} cycles",
+        pwm_overhead, systick_overhead
+    );
+
+    for contended in [false, true] {
+        let bus = if contended { "contended" } else { "idle" };
+        if contended {
+            start_sweep();
+        }
+        for mechanism in Mechanism::ALL {
+            let mut one_way = Summary::new();
+            let mut round_trip = Summary::new();
+            for _ in 0..TRIALS {
+                one_way.add(measure_one_way(mechanism).wrapping_sub(pwm_overhead));
+                round_trip.add(measure_round_trip(mechanism).wrapping_sub(systick_overhead));
+            }
+            for (direction, summary) in [("one_way", one_way), ("round_trip", round_trip)] {
+                println!(
+                    "{} {} bus, {}: min {} max {} mean {} cycles",
+                    mechanism.name(),
+                    bus,
+                    direction,
+                    summary.min,
+                    summary.max,
+                    summary.mean()
+                );
+                println!(
+                    "@cross_core_latency mechanism={=str} bus={=str} direction={=str} trials={=u32} min={=u32} max={=u32} mean={=u32}",
+                    mechanism.name(),
+                    bus,
+                    direction,
+                    summary.count,
+                    summary.min,
+                    summary.max,
+                    summary.mean()
+                );
+            }
+        }
+        if contended {
+            stop_sweep();
+        }
+    }
+
+    // Set the pins to their default state
+    let pins = hal::gpio::Pins::new(
+        pac.IO_BANK0,
+        pac.PADS_BANK0,
+        sio.gpio_bank0,
+        &mut pac.RESETS,
+    );
+    let mut led_pin = pins.gpio25.into_push_pull_output();
+    loop {
+        // sign of life
+        led_pin.set_high().unwrap();
+        asm::delay(12_500_000);
+        led_pin.set_low().unwrap();
+        asm::delay(12_500_000);
+    }
+}
+
+/// Cycles between two back-to-back reads of the PWM counter and of the Systick, the smallest of a
+/// few tries
+fn timestamp_overheads() -> (u32, u32) {
+    let mut pwm = u32::MAX;
+    let mut systick = u32::MAX;
+    for _ in 0..8 {
+        let (p0, p1, s0, s1): (u32, u32, u32, u32);
+        unsafe {
+            core::arch::asm!(
+                "ldr {p0}, [{ctr}]",
+                "ldr {p1}, [{ctr}]",
+                "ldr {s0}, [{cvr}]",
+                "ldr {s1}, [{cvr}]",
+                ctr = in(reg) PWM_CH0_CTR,
+                cvr = in(reg) SYST_CVR,
+                p0 = out(reg) p0,
+                p1 = out(reg) p1,
+                s0 = out(reg) s0,
+                s1 = out(reg) s1,
+            );
+        }
+        pwm = pwm.min(p1.wrapping_sub(p0) & 0xffff);
+        systick = systick.min(s0.wrapping_sub(s1) & 0x00ff_ffff);
+    }
+    (pwm, systick)
+}
+
+/// Starts DMA channel 0 reading the 1 KiB `SWEEP_BUFFER` in a loop, which covers the 4 striped
+/// banks, and writing into `SWEEP_SINK`. 2^32 transfers last longer than the whole benchmark.
+fn start_sweep() {
+    unsafe {
+        DMA_CH0_READ_ADDR.write_volatile(core::ptr::addr_of!(SWEEP_BUFFER) as u32);
+        DMA_CH0_WRITE_ADDR.write_volatile(core::ptr::addr_of_mut!(SWEEP_SINK) as u32);
+        DMA_CH0_TRANS_COUNT.write_volatile(u32::MAX);
+        DMA_CH0_CTRL_TRIG.write_volatile(DMA_CTRL_SWEEP);
+    }
+}
+
+fn stop_sweep() {
+    unsafe {
+        DMA_CHAN_ABORT.write_volatile(1);
+        while DMA_CHAN_ABORT.read_volatile() != 0 {}
+    }
+}
+
+/// Asks core1 to wait on `command`, returns once core1 waits
+fn prepare(command: u32) {
+    CORE1_DONE.store(false, Ordering::SeqCst);
+    CORE0_DONE.store(false, Ordering::SeqCst);
+    SEV_FLAG.store(0, Ordering::SeqCst);
+    SEV_ANSWER.store(0, Ordering::SeqCst);
+    COMMAND.store(command, Ordering::SeqCst);
+    // thumbv6m has no atomic swap, core0 is the only core that clears the flag
+    while !CORE1_READY.load(Ordering::SeqCst) {}
+    CORE1_READY.store(false, Ordering::SeqCst);
+    // leave core1 the time to enter its wait loop, WFE or WFI
+    asm::delay(1000);
+}
+
+/// Cycles of the common timebase from core0 signaling to core1 being signaled
+fn measure_one_way(mechanism: Mechanism) -> u32 {
+    prepare(mechanism as u32);
+    let t0: u32;
+    match mechanism {
+        Mechanism::Fifo => unsafe {
+            core::arch::asm!(
+                "ldr {t0}, [{ctr}]",
+                "str {value}, [{sio}, #0x54]", // FIFO_WR
+                ctr = in(reg) PWM_CH0_CTR,
+                sio = in(reg) SIO_BASE,
+                value = in(reg) 1u32,
+                t0 = out(reg) t0,
+            );
+        },
+        Mechanism::FifoIrq => {
+            t0 = pwm_counter();
+            CrossCore::pend_irq(pac::Interrupt::TIMER_IRQ_1, 1);
+        }
+        Mechanism::Queue => {
+            t0 = pwm_counter();
+            unsafe { QUEUE1.assume_init_ref().push(1).unwrap() };
+            CrossCore::pend_irq(pac::Interrupt::TIMER_IRQ_0, 1);
+        }
+        Mechanism::Sev => unsafe {
+            core::arch::asm!(
+                "ldr {t0}, [{ctr}]",
+                "str {one}, [{flag}]",
+                "sev",
+                ctr = in(reg) PWM_CH0_CTR,
+                flag = in(reg) SEV_FLAG.as_ptr(),
+                one = in(reg) 1u32,
+                t0 = out(reg) t0,
+            );
+        },
+    }
+    while !CORE1_DONE.load(Ordering::SeqCst) {}
+    CORE1_STAMP.load(Ordering::SeqCst).wrapping_sub(t0) & 0xffff
+}
+
+/// Cycles of the Systick of core0 from signaling core1 to getting its answer
+fn measure_round_trip(mechanism: Mechanism) -> u32 {
+    prepare(mechanism as u32 | ROUND_TRIP);
+    let (t0, t1): (u32, u32);
+    match mechanism {
+        Mechanism::Fifo => unsafe {
+            core::arch::asm!(
+                "ldr {t0}, [{cvr}]",
+                "str {t1}, [{sio}, #0x54]", // FIFO_WR
+                "2:",
+                "ldr {t1}, [{sio}, #0x50]", // FIFO_ST
+                "lsrs {t1}, {t1}, #1",      // VLD in the carry
+                "bcc 2b",
+                "ldr {t1}, [{sio}, #0x58]", // FIFO_RD
+                "ldr {t1}, [{cvr}]",
+                cvr = in(reg) SYST_CVR,
+                sio = in(reg) SIO_BASE,
+                t0 = out(reg) t0,
+                t1 = inout(reg) 1u32 => t1,
+            );
+        },
+        Mechanism::FifoIrq | Mechanism::Queue => {
+            pac::NVIC::unpend(pac::Interrupt::SIO_IRQ_PROC0);
+            unsafe { pac::NVIC::unmask(pac::Interrupt::SIO_IRQ_PROC0) };
+            t0 = systick();
+            if mechanism == Mechanism::Queue {
+                unsafe { QUEUE1.assume_init_ref().push(1).unwrap() };
+                CrossCore::pend_irq(pac::Interrupt::TIMER_IRQ_0, 1);
+            } else {
+                CrossCore::pend_irq(pac::Interrupt::TIMER_IRQ_1, 1);
+            }
+            while !CORE0_DONE.load(Ordering::SeqCst) {}
+            pac::NVIC::mask(pac::Interrupt::SIO_IRQ_PROC0);
+            t1 = CORE0_STAMP.load(Ordering::SeqCst);
+        }
+        // the SEV of core0 also sets its own event register, so the first WFE returns at once and
+        // the loop waits for the answer of core1
+        Mechanism::Sev => unsafe {
+            core::arch::asm!(
+                "ldr {t0}, [{cvr}]",
+                "str {t1}, [{flag}]",
+                "sev",
+                "2:",
+                "wfe",
+                "ldr {t1}, [{answer}]",
+                "cmp {t1}, #0",
+                "beq 2b",
+                "ldr {t1}, [{cvr}]",
+                cvr = in(reg) SYST_CVR,
+                flag = in(reg) SEV_FLAG.as_ptr(),
+                answer = in(reg) SEV_ANSWER.as_ptr(),
+                t0 = out(reg) t0,
+                t1 = inout(reg) 1u32 => t1,
+            );
+        },
+    }
+    while !CORE1_DONE.load(Ordering::SeqCst) {}
+    t0.wrapping_sub(t1) & 0x00ff_ffff
+}
+
+// ============================================ Core1 =============================================
+
+fn core1_main() -> ! {
+    let pac = unsafe { pac::Peripherals::steal() };
+    let mut core = unsafe { pac::CorePeripherals::steal() };
+    unsafe {
+        core.NVIC.set_priority(pac::Interrupt::SIO_IRQ_PROC1, 0);
+        pac::NVIC::unmask(pac::Interrupt::TIMER_IRQ_0);
+        pac::NVIC::unmask(pac::Interrupt::TIMER_IRQ_1);
+    }
+    loop {
+        let command = loop {
+            // core1 is the only core that clears the command
+            let command = COMMAND.load(Ordering::SeqCst);
+            if command != 0 {
+                COMMAND.store(0, Ordering::SeqCst);
+                break command;
+            }
+        };
+        let round_trip = command & ROUND_TRIP != 0;
+        match Mechanism::from_command(command) {
+            Mechanism::Fifo => {
+                CORE1_READY.store(true, Ordering::SeqCst);
+                let t1: u32;
+                unsafe {
+                    core::arch::asm!(
+                        "2:",
+                        "ldr {t1}, [{sio}, #0x50]", // FIFO_ST
+                        "lsrs {t1}, {t1}, #1",      // VLD in the carry
+                        "bcc 2b",
+                        "ldr {value}, [{sio}, #0x58]", // FIFO_RD
+                        "ldr {t1}, [{ctr}]",
+                        "cmp {answer}, #0",
+                        "beq 3f",
+                        "str {value}, [{sio}, #0x54]", // FIFO_WR
+                        "3:",
+                        sio = in(reg) SIO_BASE,
+                        ctr = in(reg) PWM_CH0_CTR,
+                        answer = in(reg) round_trip as u32,
+                        value = out(reg) _,
+                        t1 = out(reg) t1,
+                    );
+                }
+                CORE1_STAMP.store(t1, Ordering::SeqCst);
+            }
+            Mechanism::FifoIrq | Mechanism::Queue => {
+                // the proxy and the handlers do the work
+                ANSWER.store(round_trip, Ordering::SeqCst);
+                pac.SIO.fifo_st.write(|wr| unsafe { wr.bits(0xff) });
+                pac::NVIC::unpend(pac::Interrupt::SIO_IRQ_PROC1);
+                unsafe { pac::NVIC::unmask(pac::Interrupt::SIO_IRQ_PROC1) };
+                CORE1_READY.store(true, Ordering::SeqCst);
+                while !CORE1_DONE.load(Ordering::SeqCst) {}
+                pac::NVIC::mask(pac::Interrupt::SIO_IRQ_PROC1);
+                continue;
+            }
+            Mechanism::Sev => {
+                // clear the event register, WFE must wait for core0
+                asm::sev();
+                asm::wfe();
+                CORE1_READY.store(true, Ordering::SeqCst);
+                let t1: u32;
+                unsafe {
+                    core::arch::asm!(
+                        "2:",
+                        "wfe",
+                        "ldr {t1}, [{flag}]",
+                        "cmp {t1}, #0",
+                        "beq 2b",
+                        "ldr {t1}, [{ctr}]",
+                        "cmp {answer}, #0",
+                        "beq 3f",
+                        "str {answer}, [{answer_flag}]",
+                        "sev",
+                        "3:",
+                        flag = in(reg) SEV_FLAG.as_ptr(),
+                        answer_flag = in(reg) SEV_ANSWER.as_ptr(),
+                        ctr = in(reg) PWM_CH0_CTR,
+                        answer = in(reg) round_trip as u32,
+                        t1 = out(reg) t1,
+                    );
+                }
+                CORE1_STAMP.store(t1, Ordering::SeqCst);
+            }
+        }
+        CORE1_DONE.store(true, Ordering::SeqCst);
+    }
+}
+
+// ================================= Signaled and answering handlers ==============================
+
+/// Forwarded interrupt of the fifo_irq mechanism
+#[interrupt]
+fn TIMER_IRQ_1() {
+    if cpuid() == 1 {
+        CORE1_STAMP.store(pwm_counter(), Ordering::SeqCst);
+        if ANSWER.load(Ordering::SeqCst) {
+            CrossCore::pend_irq(pac::Interrupt::TIMER_IRQ_1, 0);
+        }
+        CORE1_DONE.store(true, Ordering::SeqCst);
+    } else {
+        CORE0_STAMP.store(systick(), Ordering::SeqCst);
+        CORE0_DONE.store(true, Ordering::SeqCst);
+    }
+}
+
+/// Dispatcher of the queue mechanism
+#[interrupt]
+fn TIMER_IRQ_0() {
+    if cpuid() == 1 {
+        while let Some(data) = unsafe { QUEUE1.assume_init_ref().pop() } {
+            CORE1_STAMP.store(pwm_counter(), Ordering::SeqCst);
+            if ANSWER.load(Ordering::SeqCst) {
+                unsafe { QUEUE0.assume_init_ref().push(data).unwrap() };
+                CrossCore::pend_irq(pac::Interrupt::TIMER_IRQ_0, 0);
+            }
+        }
+        CORE1_DONE.store(true, Ordering::SeqCst);
+    } else {
+        while let Some(_data) = unsafe { QUEUE0.assume_init_ref().pop() } {
+            CORE0_STAMP.store(systick(), Ordering::SeqCst);
+        }
+        CORE0_DONE.store(true, Ordering::SeqCst);
+    }
+}
+
+//================================== FIFO irqs acting as proxy ====================================
+
+#[interrupt]
+fn SIO_IRQ_PROC0() {
+    if let Some(signal) = CrossCore::get_pended_irq() {
+        pac::NVIC::pend(signal);
+    }
+}
+
+#[interrupt]
+fn SIO_IRQ_PROC1() {
+    if let Some(signal) = CrossCore::get_pended_irq() {
+        pac::NVIC::pend(signal);
+    }
+}
@@ -0,0 +1,167 @@
+//! Masters and slaves of the rp2040 bus fabric, and the address map tying slaves to addresses.
+//!
+//! The crossbar has four upstream ports, the two cores and the read and write masters of the DMA,
+//! and one downstream port per slave. Each SRAM bank is a slave of its own: the 256 KiB at
+//! 0x20000000 are striped word by word over SRAM0-3, the same banks are mapped unstriped at
+//! 0x21000000, and SRAM4/5 are two 4 KiB banks at 0x20040000 and 0x20041000.
+
+use core::fmt;
+
+/// Upstream ports of the crossbar, in the order of the BUS_PRIORITY bits
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Master {
+    Proc0,
+    Proc1,
+    DmaR,
+    DmaW,
+}
+
+impl Master {
+    pub const ALL: [Master; 4] = [Master::Proc0, Master::Proc1, Master::DmaR, Master::DmaW];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Master::Proc0 => "proc0",
+            Master::Proc1 => "proc1",
+            Master::DmaR => "dma_r",
+            Master::DmaW => "dma_w",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Master> {
+        Master::ALL.into_iter().find(|m| m.name() == name)
+    }
+
+    pub fn index(&self) -> usize {
+        *self as usize
+    }
+}
+
+impl fmt::Display for Master {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// Downstream ports of the crossbar modelled by the simulator
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Slave {
+    Sram0,
+    Sram1,
+    Sram2,
+    Sram3,
+    Sram4,
+    Sram5,
+    Xip,
+    Rom,
+    /// The APB bridge, in front of all the APB peripherals
+    Apb,
+}
+
+impl Slave {
+    pub const ALL: [Slave; 9] = [
+        Slave::Sram0,
+        Slave::Sram1,
+        Slave::Sram2,
+        Slave::Sram3,
+        Slave::Sram4,
+        Slave::Sram5,
+        Slave::Xip,
+        Slave::Rom,
+        Slave::Apb,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Slave::Sram0 => "sram0",
+            Slave::Sram1 => "sram1",
+            Slave::Sram2 => "sram2",
+            Slave::Sram3 => "sram3",
+            Slave::Sram4 => "sram4",
+            Slave::Sram5 => "sram5",
+            Slave::Xip => "xip",
+            Slave::Rom => "rom",
+            Slave::Apb => "apb",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Slave> {
+        Slave::ALL.into_iter().find(|s| s.name() == name)
+    }
+
+    pub fn index(&self) -> usize {
+        *self as usize
+    }
+
+    /// Slave serving `address`, `None` for the regions that are not modelled (AHB-Lite
+    /// peripherals, SIO which is local to each core, PPB) or not mapped
+    pub fn from_address(address: u32) -> Option<Slave> {
+        const SRAM: [Slave; 4] = [Slave::Sram0, Slave::Sram1, Slave::Sram2, Slave::Sram3];
+        match address {
+            0x0000_0000..=0x0000_3fff => Some(Slave::Rom),
+            // cached and uncached aliases of the flash
+            0x1000_0000..=0x13ff_ffff => Some(Slave::Xip),
+            // striped, consecutive words are in consecutive banks
+            0x2000_0000..=0x2003_ffff => Some(SRAM[(address as usize >> 2) & 3]),
+            0x2004_0000..=0x2004_0fff => Some(Slave::Sram4),
+            0x2004_1000..=0x2004_1fff => Some(Slave::Sram5),
+            // non-striped alias, 64 KiB per bank
+            0x2100_0000..=0x2103_ffff => Some(SRAM[(address as usize - 0x2100_0000) >> 16]),
+            0x4000_0000..=0x4006_ffff => Some(Slave::Apb),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Slave {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// Bits of BUS_PRIORITY, `true` is high priority, indexed by `Master::index()`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct BusPriority(pub [bool; 4]);
+
+impl BusPriority {
+    /// Both cores at high priority, the DMA at low priority, as in the experiments
+    pub const CORES_HIGH: BusPriority = BusPriority([true, true, false, false]);
+
+    pub fn is_high(&self, master: Master) -> bool {
+        self.0[master.index()]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Slave;
+
+    #[test]
+    fn test_striped_sram() {
+        assert_eq!(Slave::from_address(0x2000_0000), Some(Slave::Sram0));
+        assert_eq!(Slave::from_address(0x2000_0004), Some(Slave::Sram1));
+        assert_eq!(Slave::from_address(0x2000_000c), Some(Slave::Sram3));
+        assert_eq!(Slave::from_address(0x2000_0010), Some(Slave::Sram0));
+        assert_eq!(Slave::from_address(0x2003_fffc), Some(Slave::Sram3));
+    }
+
+    #[test]
+    fn test_non_striped_sram() {
+        assert_eq!(Slave::from_address(0x2100_0000), Some(Slave::Sram0));
+        assert_eq!(Slave::from_address(0x2100_0004), Some(Slave::Sram0));
+        assert_eq!(Slave::from_address(0x2102_0000), Some(Slave::Sram2));
+        assert_eq!(Slave::from_address(0x2103_ffff), Some(Slave::Sram3));
+        assert_eq!(Slave::from_address(0x2004_0000), Some(Slave::Sram4));
+        assert_eq!(Slave::from_address(0x2004_1000), Some(Slave::Sram5));
+        assert_eq!(Slave::from_address(0x2004_2000), None);
+    }
+
+    #[test]
+    fn test_other_regions() {
+        assert_eq!(Slave::from_address(0x0000_0100), Some(Slave::Rom));
+        assert_eq!(Slave::from_address(0x1000_0100), Some(Slave::Xip));
+        assert_eq!(Slave::from_address(0x4005_0008), Some(Slave::Apb));
+        assert_eq!(Slave::from_address(0x5000_0000), None);
+        assert_eq!(Slave::from_address(0xd000_0000), None);
+    }
+}
@@ -0,0 +1,12 @@
+//! Cycle-level simulation of the AHB-Lite crossbar of the rp2040, to predict the outcome of the
+//! `bus_behavior` experiments before running them on hardware.
+
+mod fabric;
+mod scenario;
+mod simulation;
+
+pub use fabric::{BusPriority, Master, Slave};
+pub use scenario::ScenarioError;
+pub use simulation::{
+    simulate, AccessKind, MasterOutcome, Outcome, Scenario, Step, StepKind, StepTiming, Trace,
+};
@@ -0,0 +1,89 @@
+use std::{env, fs, process::ExitCode};
+
+use crossbar_sim::{simulate, Scenario, Slave, StepKind};
+
+fn main() -> ExitCode {
+    let Some(path) = env::args().nth(1) else {
+        eprintln!("usage: crossbar_sim <scenario.toml>");
+        return ExitCode::FAILURE;
+    };
+    let src = match fs::read_to_string(&path) {
+        Ok(src) => src,
+        Err(e) => {
+            eprintln!("could not read {path}: {e}");
+            return ExitCode::FAILURE;
+        }
+    };
+    let scenario = match Scenario::from_toml(&src) {
+        Ok(scenario) => scenario,
+        Err(e) => {
+            eprintln!("{path}: {e}");
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let outcome = simulate(&scenario);
+
+    println!(
+        "{:<6} {:>6} {:>8} {:>8} {:>8}",
+        "master", "steps", "end", "stalls", "measured"
+    );
+    for m in &outcome.masters {
+        let measured = m
+            .measured_cycles()
+            .map_or_else(|| "-".to_string(), |c| c.to_string());
+        println!(
+            "{:<6} {:>6} {:>8} {:>8} {:>8}",
+            m.master,
+            m.steps.len(),
+            m.end(),
+            m.steps.iter().map(|s| s.stalls).sum::<u32>(),
+            measured
+        );
+    }
+
+    println!("\ndelayed accesses:");
+    let mut delayed = 0;
+    for (trace, m) in scenario.traces.iter().zip(&outcome.masters) {
+        // the simulation drops the steps of zero cycles, so the timings are matched to the others
+        let steps = trace.steps.iter().filter(|s| match s.kind {
+            StepKind::Idle(cycles) | StepKind::Access { cycles, .. } => cycles > 0,
+        });
+        for (i, (step, timing)) in steps.zip(&m.steps).enumerate() {
+            if let (StepKind::Access { slave, kind, .. }, Some(granted)) =
+                (step.kind, timing.granted)
+            {
+                if timing.stalls > 0 {
+                    delayed += 1;
+                    println!(
+                        "{} step {} ({} {}): requested at cycle {}, granted at cycle {}",
+                        m.master,
+                        i,
+                        kind.name(),
+                        slave,
+                        timing.start,
+                        granted
+                    );
+                }
+            }
+        }
+    }
+    if delayed == 0 {
+        println!("none");
+    }
+
+    let contested: Vec<String> = Slave::ALL
+        .iter()
+        .filter(|&&s| outcome.contested(s) > 0)
+        .map(|s| format!("{} = {}", s, outcome.contested(*s)))
+        .collect();
+    println!(
+        "\ncontested accesses [{}]",
+        if contested.is_empty() {
+            "none".to_string()
+        } else {
+            contested.join(", ")
+        }
+    );
+    ExitCode::SUCCESS
+}
@@ -0,0 +1,268 @@
+//! Scenario description as read from a `.toml` file.
+//!
+//! Each master listed executes its trace from cycle 0. A step is an access to a slave, given by
+//! name or by address (the striping of SRAM0-3 is resolved), or idle cycles:
+//!
+//! ```toml
+//! # BUS_PRIORITY, the masters not listed are at low priority
+//! high_priority = ["proc0", "proc1"]
+//!
+//! [[trace]]
+//! master = "proc0"
+//! steps = [
+//!     { fetch = "sram2" },                                  # first instruction of the ISR
+//!     { read = 0x20040000, measured = true, repeat = 100 }, # 100 `ldr` of a SRAM4 word
+//! ]
+//!
+//! [[trace]]
+//! master = "dma_r"
+//! steps = [{ read = "sram4", cycles = 1, repeat = 50 }]
+//! ```
+//!
+//! `read` and `write` take 2 cycles and `fetch` 1 cycle when not contested, unless `cycles` is
+//! given. `repeat` expands into that many identical steps, and `measured` marks the steps
+//! measured between the two Systick reads of a core.
+
+use core::fmt;
+
+use serde::Deserialize;
+
+use crate::fabric::{BusPriority, Master, Slave};
+use crate::simulation::{AccessKind, Scenario, Step, StepKind, Trace};
+
+#[derive(Debug)]
+pub enum ScenarioError {
+    Parse(toml::de::Error),
+    UnknownMaster(String),
+    DuplicateMaster(Master),
+    UnknownSlave {
+        master: Master,
+        slave: String,
+    },
+    UnmappedAddress {
+        master: Master,
+        address: u32,
+    },
+    /// A step that is not exactly one of `read`, `write`, `fetch` or `idle`
+    InvalidStep {
+        master: Master,
+        step: usize,
+    },
+}
+
+impl fmt::Display for ScenarioError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ScenarioError::Parse(e) => write!(f, "invalid scenario description: {e}"),
+            ScenarioError::UnknownMaster(master) => write!(
+                f,
+                "unknown master `{master}`, the masters are proc0, proc1, dma_r and dma_w"
+            ),
+            ScenarioError::DuplicateMaster(master) => {
+                write!(f, "master `{master}` has two traces")
+            }
+            ScenarioError::UnknownSlave { master, slave } => write!(
+                f,
+                "trace of `{master}` accesses unknown slave `{slave}`, the slaves are \
+                 sram0 to sram5, xip, rom and apb"
+            ),
+            ScenarioError::UnmappedAddress { master, address } => write!(
+                f,
+                "trace of `{master}` accesses {address:#010x}, which is not served by a \
+                 modelled slave"
+            ),
+            ScenarioError::InvalidStep { master, step } => write!(
+                f,
+                "step {step} of `{master}` must have exactly one of `read`, `write`, `fetch` \
+                 or `idle`"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ScenarioError {}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawScenario {
+    #[serde(default)]
+    high_priority: Vec<String>,
+    #[serde(default, rename = "trace")]
+    traces: Vec<RawTrace>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawTrace {
+    master: String,
+    steps: Vec<RawStep>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawStep {
+    read: Option<Target>,
+    write: Option<Target>,
+    fetch: Option<Target>,
+    idle: Option<u32>,
+    cycles: Option<u32>,
+    #[serde(default)]
+    measured: bool,
+    #[serde(default = "one")]
+    repeat: u32,
+}
+
+fn one() -> u32 {
+    1
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Target {
+    Name(String),
+    Address(u32),
+}
+
+fn master(name: &str) -> Result<Master, ScenarioError> {
+    Master::from_name(name).ok_or_else(|| ScenarioError::UnknownMaster(name.to_string()))
+}
+
+impl Scenario {
+    /// Parses and validates a scenario description
+    pub fn from_toml(src: &str) -> Result<Self, ScenarioError> {
+        let raw: RawScenario = toml::from_str(src).map_err(ScenarioError::Parse)?;
+
+        let mut bus_priority = BusPriority::default();
+        for name in &raw.high_priority {
+            bus_priority.0[master(name)?.index()] = true;
+        }
+
+        let mut traces: Vec<Trace> = Vec::with_capacity(raw.traces.len());
+        for raw_trace in raw.traces {
+            let master = master(&raw_trace.master)?;
+            if traces.iter().any(|t| t.master == master) {
+                return Err(ScenarioError::DuplicateMaster(master));
+            }
+            let mut steps = Vec::new();
+            for (i, raw_step) in raw_trace.steps.into_iter().enumerate() {
+                let kind = match raw_step {
+                    RawStep {
+                        idle: Some(cycles),
+                        read: None,
+                        write: None,
+                        fetch: None,
+                        cycles: None,
+                        ..
+                    } => StepKind::Idle(cycles),
+                    RawStep {
+                        idle: None,
+                        read,
+                        write,
+                        fetch,
+                        cycles,
+                        ..
+                    } => {
+                        let (kind, target) = match (read, write, fetch) {
+                            (Some(target), None, None) => (AccessKind::Read, target),
+                            (None, Some(target), None) => (AccessKind::Write, target),
+                            (None, None, Some(target)) => (AccessKind::Fetch, target),
+                            _ => return Err(ScenarioError::InvalidStep { master, step: i }),
+                        };
+                        let slave = match target {
+                            Target::Name(slave) => Slave::from_name(&slave)
+                                .ok_or(ScenarioError::UnknownSlave { master, slave })?,
+                            Target::Address(address) => Slave::from_address(address)
+                                .ok_or(ScenarioError::UnmappedAddress { master, address })?,
+                        };
+                        StepKind::Access {
+                            slave,
+                            kind,
+                            cycles: cycles.unwrap_or(kind.default_cycles()),
+                        }
+                    }
+                    _ => return Err(ScenarioError::InvalidStep { master, step: i }),
+                };
+                let step = Step {
+                    kind,
+                    measured: raw_step.measured,
+                };
+                steps.extend((0..raw_step.repeat).map(|_| step));
+            }
+            traces.push(Trace { master, steps });
+        }
+
+        Ok(Scenario {
+            bus_priority,
+            traces,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ScenarioError;
+    use crate::fabric::{Master, Slave};
+    use crate::simulation::{simulate, Scenario};
+
+    #[test]
+    fn test_example() {
+        // experiment 5 with the addresses of the firmware
+        let src = include_str!("../examples/exp5.toml");
+        let scenario = Scenario::from_toml(src).unwrap();
+        assert_eq!(scenario.traces.len(), 2);
+        assert_eq!(scenario.traces[0].steps.len(), 101);
+        let outcome = simulate(&scenario);
+        let cycles = [Master::Proc0, Master::Proc1]
+            .map(|m| outcome.master(m).unwrap().measured_cycles().unwrap());
+        assert_eq!(cycles, [201, 200]);
+        assert_eq!(outcome.contested(Slave::Sram4), 1);
+    }
+
+    #[test]
+    fn test_invalid_step() {
+        let src = r#"
+            [[trace]]
+            master = "proc0"
+            steps = [{ read = "sram4", idle = 1 }]
+        "#;
+        assert!(matches!(
+            Scenario::from_toml(src),
+            Err(ScenarioError::InvalidStep {
+                master: Master::Proc0,
+                step: 0
+            })
+        ));
+    }
+
+    #[test]
+    fn test_unmapped_address() {
+        let src = r#"
+            [[trace]]
+            master = "dma_w"
+            steps = [{ write = 0xd0000000 }]
+        "#;
+        assert!(matches!(
+            Scenario::from_toml(src),
+            Err(ScenarioError::UnmappedAddress {
+                master: Master::DmaW,
+                address: 0xd000_0000
+            })
+        ));
+    }
+
+    #[test]
+    fn test_duplicate_master() {
+        let src = r#"
+            [[trace]]
+            master = "proc1"
+            steps = []
+            [[trace]]
+            master = "proc1"
+            steps = []
+        "#;
+        assert!(matches!(
+            Scenario::from_toml(src),
+            Err(ScenarioError::DuplicateMaster(Master::Proc1))
+        ));
+    }
+}
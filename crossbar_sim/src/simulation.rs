@@ -0,0 +1,530 @@
+//! Cycle-level simulation of the crossbar arbiters.
+//!
+//! Each master executes a trace of steps. An access step requests its slave in its first cycle,
+//! the address phase, and the arbiter of the slave grants one of the masters requesting it in
+//! that cycle. The others are put in a wait state and request again in the next cycle. Once
+//! granted, an access takes the rest of its cycles without the bus, e.g. the data phase of a
+//! `ldr` (2 cycles). An idle step takes its cycles without requesting anything.
+//!
+//! The arbiter of each slave grants the high priority masters first (BUS_PRIORITY) and breaks
+//! ties between masters of the same priority level round-robin: it grants the first requesting
+//! master after the one it granted last, in the order proc0, proc1, dma_r, dma_w. Every grant
+//! moves the round-robin pointer, contested or not. The arbiters start as if proc0 had been
+//! granted last, since core0 is the one setting up the experiments, which is why proc1 wins the
+//! first tie of experiment 1.
+//!
+//! An access delayed by at least one wait state is counted once as contested on its slave, like
+//! the `*_contested` events of the bus performance counters.
+
+use crate::fabric::{BusPriority, Master, Slave};
+
+/// What an access does, it makes no difference to the arbiter
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AccessKind {
+    Read,
+    Write,
+    Fetch,
+}
+
+impl AccessKind {
+    pub fn name(&self) -> &'static str {
+        match self {
+            AccessKind::Read => "read",
+            AccessKind::Write => "write",
+            AccessKind::Fetch => "fetch",
+        }
+    }
+
+    /// Cycles of an uncontested access: 2 for `ldr`/`str` (Table1 of bus_behavior), 1 for an
+    /// instruction fetch, which overlaps with the execution of the previous instruction
+    pub fn default_cycles(&self) -> u32 {
+        match self {
+            AccessKind::Read | AccessKind::Write => 2,
+            AccessKind::Fetch => 1,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StepKind {
+    /// Cycles without bus access, e.g. `nop`
+    Idle(u32),
+    Access {
+        slave: Slave,
+        kind: AccessKind,
+        /// Cycles when not contested, including the address phase
+        cycles: u32,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Step {
+    pub kind: StepKind,
+    /// Part of the sequence measured between the two Systick reads
+    pub measured: bool,
+}
+
+impl Step {
+    pub fn idle(cycles: u32) -> Step {
+        Step {
+            kind: StepKind::Idle(cycles),
+            measured: false,
+        }
+    }
+
+    pub fn access(slave: Slave, kind: AccessKind) -> Step {
+        Step {
+            kind: StepKind::Access {
+                slave,
+                kind,
+                cycles: kind.default_cycles(),
+            },
+            measured: false,
+        }
+    }
+
+    pub fn measured(self) -> Step {
+        Step {
+            measured: true,
+            ..self
+        }
+    }
+
+    fn cycles(&self) -> u32 {
+        match self.kind {
+            StepKind::Idle(cycles) | StepKind::Access { cycles, .. } => cycles,
+        }
+    }
+}
+
+/// Steps executed by one master, starting at cycle 0
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Trace {
+    pub master: Master,
+    pub steps: Vec<Step>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Scenario {
+    pub bus_priority: BusPriority,
+    /// At most one trace per master
+    pub traces: Vec<Trace>,
+}
+
+/// When a step was executed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StepTiming {
+    /// First cycle of the step
+    pub start: u64,
+    /// Cycle the access was granted, `None` for an idle step
+    pub granted: Option<u64>,
+    /// Cycle after the last cycle of the step, the start of the next step
+    pub end: u64,
+    /// Wait states inserted by the arbiter
+    pub stalls: u32,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MasterOutcome {
+    pub master: Master,
+    /// One timing per step of the trace, in order, the steps of 0 cycles left out
+    pub steps: Vec<StepTiming>,
+    measured: Vec<bool>,
+}
+
+impl MasterOutcome {
+    /// Cycles from the start of the first measured step to the end of the last one, what the
+    /// Systick of the core measures. `None` if no step is measured.
+    pub fn measured_cycles(&self) -> Option<u64> {
+        let mut measured = self
+            .steps
+            .iter()
+            .zip(&self.measured)
+            .filter(|(_, &m)| m)
+            .map(|(timing, _)| timing);
+        let first = measured.next()?;
+        let last = measured.next_back().unwrap_or(first);
+        Some(last.end - first.start)
+    }
+
+    /// Cycle after the last step
+    pub fn end(&self) -> u64 {
+        self.steps.last().map_or(0, |s| s.end)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Outcome {
+    /// In the order of the traces
+    pub masters: Vec<MasterOutcome>,
+    /// Accesses delayed by another master, indexed by `Slave::index()`
+    pub contested: [u32; Slave::ALL.len()],
+}
+
+impl Outcome {
+    pub fn master(&self, master: Master) -> Option<&MasterOutcome> {
+        self.masters.iter().find(|m| m.master == master)
+    }
+
+    pub fn contested(&self, slave: Slave) -> u32 {
+        self.contested[slave.index()]
+    }
+}
+
+/// Progress of a master through its trace
+struct Cursor {
+    step: usize,
+    start: u64,
+    granted: Option<u64>,
+    stalls: u32,
+    /// Cycles of the current step executed so far, from the grant for an access
+    elapsed: u32,
+}
+
+/// Runs the traces of `scenario` until every master has executed its last step.
+///
+/// # Panics
+///
+/// If two traces are for the same master.
+pub fn simulate(scenario: &Scenario) -> Outcome {
+    let traces = &scenario.traces;
+    for (i, trace) in traces.iter().enumerate() {
+        assert!(
+            traces[..i].iter().all(|t| t.master != trace.master),
+            "two traces for {}",
+            trace.master
+        );
+    }
+    // steps of zero cycles don't exist on the bus
+    let steps: Vec<Vec<Step>> = traces
+        .iter()
+        .map(|t| t.steps.iter().copied().filter(|s| s.cycles() > 0).collect())
+        .collect();
+
+    let mut outcome = Outcome {
+        masters: traces
+            .iter()
+            .zip(&steps)
+            .map(|(t, steps)| MasterOutcome {
+                master: t.master,
+                steps: Vec::with_capacity(steps.len()),
+                measured: steps.iter().map(|s| s.measured).collect(),
+            })
+            .collect(),
+        contested: [0; Slave::ALL.len()],
+    };
+    let mut cursors: Vec<Cursor> = traces
+        .iter()
+        .map(|_| Cursor {
+            step: 0,
+            start: 0,
+            granted: None,
+            stalls: 0,
+            elapsed: 0,
+        })
+        .collect();
+    let mut last_granted = [Master::Proc0; Slave::ALL.len()];
+
+    let mut cycle = 0;
+    loop {
+        let current: Vec<Option<Step>> = cursors
+            .iter()
+            .zip(&steps)
+            .map(|(c, steps)| steps.get(c.step).copied())
+            .collect();
+        if current.iter().all(Option::is_none) {
+            break;
+        }
+
+        // address phases of this cycle
+        let mut requests: [Vec<usize>; Slave::ALL.len()] = Default::default();
+        for (i, step) in current.iter().enumerate() {
+            if let Some(Step {
+                kind: StepKind::Access { slave, .. },
+                ..
+            }) = step
+            {
+                if cursors[i].granted.is_none() {
+                    requests[slave.index()].push(i);
+                }
+            }
+        }
+        let mut grants = vec![false; traces.len()];
+        for (slave, requesters) in requests.iter().enumerate() {
+            let Some(winner) = arbitrate(
+                &scenario.bus_priority,
+                last_granted[slave],
+                requesters.iter().map(|&i| traces[i].master),
+            ) else {
+                continue;
+            };
+            last_granted[slave] = winner;
+            for &i in requesters {
+                grants[i] = traces[i].master == winner;
+            }
+        }
+
+        for (i, step) in current.iter().enumerate() {
+            let Some(step) = step else {
+                continue;
+            };
+            let cursor = &mut cursors[i];
+            if let StepKind::Access { slave, .. } = step.kind {
+                if cursor.granted.is_none() {
+                    if !grants[i] {
+                        cursor.stalls += 1;
+                        continue;
+                    }
+                    cursor.granted = Some(cycle);
+                    if cursor.stalls > 0 {
+                        outcome.contested[slave.index()] += 1;
+                    }
+                }
+            }
+            cursor.elapsed += 1;
+            if cursor.elapsed == step.cycles() {
+                outcome.masters[i].steps.push(StepTiming {
+                    start: cursor.start,
+                    granted: cursor.granted,
+                    end: cycle + 1,
+                    stalls: cursor.stalls,
+                });
+                *cursor = Cursor {
+                    step: cursor.step + 1,
+                    start: cycle + 1,
+                    granted: None,
+                    stalls: 0,
+                    elapsed: 0,
+                };
+            }
+        }
+        cycle += 1;
+    }
+    outcome
+}
+
+/// Master granted among `requesters`: the high priority ones if any, then the first one after
+/// `last_granted` in round-robin order
+fn arbitrate(
+    priority: &BusPriority,
+    last_granted: Master,
+    requesters: impl Iterator<Item = Master> + Clone,
+) -> Option<Master> {
+    let high = requesters.clone().any(|m| priority.is_high(m));
+    let candidates = requesters.filter(|&m| priority.is_high(m) == high);
+    let order = |m: Master| (m.index() + Master::ALL.len() - last_granted.index() - 1) % 4;
+    candidates.min_by_key(|&m| order(m))
+}
+
+#[cfg(test)]
+mod tests {
+    //! The lockstep experiments of bus_behavior/README.md. Each core fetches the first
+    //! instruction of its TIMER_IRQ_0 handler from the bank of its code in the same cycle,
+    //! executes the `padding` NOPs and then the measured operations on the shared data.
+
+    use super::*;
+    use crate::fabric::{BusPriority, Master, Slave};
+
+    const LDR: AccessKind = AccessKind::Read;
+    const STR: AccessKind = AccessKind::Write;
+
+    struct Core {
+        code: Slave,
+        padding: u32,
+        data: Slave,
+        ops: &'static [AccessKind],
+    }
+
+    const CORE0: Core = Core {
+        code: Slave::Sram2,
+        padding: 0,
+        data: Slave::Sram4,
+        ops: &[LDR],
+    };
+    const CORE1: Core = Core {
+        code: Slave::Sram3,
+        ..CORE0
+    };
+
+    fn trace(master: Master, core: &Core) -> Trace {
+        let mut steps = vec![
+            Step::access(core.code, AccessKind::Fetch),
+            Step::idle(core.padding),
+        ];
+        steps.extend(
+            core.ops
+                .iter()
+                .map(|&op| Step::access(core.data, op).measured()),
+        );
+        Trace { master, steps }
+    }
+
+    fn run(bus_priority: BusPriority, core0: &Core, core1: &Core) -> Outcome {
+        simulate(&Scenario {
+            bus_priority,
+            traces: vec![trace(Master::Proc0, core0), trace(Master::Proc1, core1)],
+        })
+    }
+
+    fn cycles(outcome: &Outcome) -> [u64; 2] {
+        [Master::Proc0, Master::Proc1]
+            .map(|m| outcome.master(m).unwrap().measured_cycles().unwrap())
+    }
+
+    /// Cycle at which the `op`-th measured access of `master` was granted
+    fn granted(outcome: &Outcome, master: Master, op: usize) -> u64 {
+        let steps = &outcome.master(master).unwrap().steps;
+        steps[steps.len() - 1 - op].granted.unwrap()
+    }
+
+    #[test]
+    fn test_exp1() {
+        // core1 is granted access first, core0 waits one clock cycle
+        let outcome = run(BusPriority::CORES_HIGH, &CORE0, &CORE1);
+        assert_eq!(cycles(&outcome), [3, 2]);
+        assert_eq!(outcome.contested(Slave::Sram4), 1);
+    }
+
+    #[test]
+    fn test_exp1_1() {
+        let core0 = Core {
+            data: Slave::Sram2,
+            ..CORE0
+        };
+        let core1 = Core {
+            data: Slave::Sram3,
+            ..CORE1
+        };
+        let outcome = run(BusPriority::CORES_HIGH, &core0, &core1);
+        assert_eq!(cycles(&outcome), [2, 2]);
+        assert_eq!(outcome.contested(Slave::Sram2), 0);
+        assert_eq!(outcome.contested(Slave::Sram3), 0);
+    }
+
+    #[test]
+    fn test_exp1_low_priority() {
+        // the README notes that priorities 0 and 1 give the same results
+        let outcome = run(BusPriority([false; 4]), &CORE0, &CORE1);
+        assert_eq!(cycles(&outcome), [3, 2]);
+        assert_eq!(outcome.contested(Slave::Sram4), 1);
+    }
+
+    #[test]
+    fn test_exp2() {
+        let outcome = run(BusPriority([false, true, false, false]), &CORE0, &CORE1);
+        assert_eq!(cycles(&outcome), [3, 2]);
+        assert_eq!(outcome.contested(Slave::Sram4), 1);
+    }
+
+    #[test]
+    fn test_exp3() {
+        let outcome = run(BusPriority([true, false, false, false]), &CORE0, &CORE1);
+        assert_eq!(cycles(&outcome), [2, 3]);
+        assert_eq!(outcome.contested(Slave::Sram4), 1);
+    }
+
+    #[test]
+    fn test_exp4() {
+        // core1 writes 7 before core0 reads, so core0 reads 7
+        let core1 = Core {
+            ops: &[STR],
+            ..CORE1
+        };
+        let outcome = run(BusPriority::CORES_HIGH, &CORE0, &core1);
+        assert_eq!(cycles(&outcome), [3, 2]);
+        assert_eq!(outcome.contested(Slave::Sram4), 1);
+        assert!(granted(&outcome, Master::Proc1, 0) < granted(&outcome, Master::Proc0, 0));
+    }
+
+    #[test]
+    fn test_exp5() {
+        // core1 stays one clock cycle ahead after the first contested access
+        let core0 = Core {
+            ops: &[LDR; 100],
+            ..CORE0
+        };
+        let core1 = Core {
+            ops: &[LDR; 100],
+            ..CORE1
+        };
+        let outcome = run(BusPriority::CORES_HIGH, &core0, &core1);
+        assert_eq!(cycles(&outcome), [201, 200]);
+        assert_eq!(outcome.contested(Slave::Sram4), 1);
+    }
+
+    #[test]
+    fn test_exp6() {
+        let core0 = Core {
+            ops: &[LDR; 100],
+            ..CORE0
+        };
+        let core1 = Core {
+            ops: &[LDR; 100],
+            ..CORE1
+        };
+        let outcome = run(BusPriority([true, false, false, false]), &core0, &core1);
+        assert_eq!(cycles(&outcome), [200, 201]);
+        assert_eq!(outcome.contested(Slave::Sram4), 1);
+    }
+
+    #[test]
+    fn test_exp7() {
+        // core1 wins the fetch of the first ISR instruction and stays one cycle ahead
+        let core1 = Core {
+            code: Slave::Sram2,
+            ..CORE1
+        };
+        let outcome = run(BusPriority::CORES_HIGH, &CORE0, &core1);
+        assert_eq!(cycles(&outcome), [2, 2]);
+        assert_eq!(outcome.contested(Slave::Sram4), 0);
+        assert_eq!(outcome.contested(Slave::Sram2), 1);
+    }
+
+    #[test]
+    fn test_exp7_1() {
+        // one NOP puts core1 back in sync with core0
+        let core1 = Core {
+            code: Slave::Sram2,
+            padding: 1,
+            ..CORE1
+        };
+        let outcome = run(BusPriority::CORES_HIGH, &CORE0, &core1);
+        assert_eq!(cycles(&outcome), [3, 2]);
+        assert_eq!(outcome.contested(Slave::Sram4), 1);
+    }
+
+    #[test]
+    fn test_high_priority_never_stalls() {
+        // a low priority DMA read hammering sram4 never delays core0
+        let scenario = Scenario {
+            bus_priority: BusPriority::CORES_HIGH,
+            traces: vec![
+                Trace {
+                    master: Master::Proc0,
+                    steps: vec![Step::access(Slave::Sram4, LDR).measured(); 10],
+                },
+                Trace {
+                    master: Master::DmaR,
+                    steps: vec![
+                        Step {
+                            kind: StepKind::Access {
+                                slave: Slave::Sram4,
+                                kind: LDR,
+                                cycles: 1,
+                            },
+                            measured: false,
+                        };
+                        30
+                    ],
+                },
+            ],
+        };
+        let outcome = simulate(&scenario);
+        assert_eq!(
+            outcome.master(Master::Proc0).unwrap().measured_cycles(),
+            Some(20)
+        );
+        // the DMA gets the 10 cycles core0 is in a data phase
+        assert_eq!(outcome.master(Master::DmaR).unwrap().end(), 40);
+    }
+}
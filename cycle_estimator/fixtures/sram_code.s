@@ -0,0 +1,61 @@
+@ Fixture of the cycle estimator tests, with the handlers of the interrupt latency experiment
+@ as placed by the memory.x of bus_behavior:
+@   llvm-mc -triple=thumbv6m-none-eabi -filetype=obj sram_code.s -o sram_code.o
+@   rust-lld -flavor gnu -N -e 0 -T sram_code.ld sram_code.o -o sram_code.elf
+
+    .syntax unified
+    .thumb
+
+@ the naked handler of `stamp_handler!`, whose first instruction reads the CVR
+    .section .sram2_code, "ax", %progbits
+    .globl _ZN12bus_behavior5stamp15core1_timer_irq17h1f2e3d4c5b6a7980E
+    .type _ZN12bus_behavior5stamp15core1_timer_irq17h1f2e3d4c5b6a7980E, %function
+    .thumb_func
+_ZN12bus_behavior5stamp15core1_timer_irq17h1f2e3d4c5b6a7980E:
+    ldr r0, [r1]
+    ldr r2, =0x20041f00
+    str r0, [r2]
+    bx lr
+    .ltorg
+    .size _ZN12bus_behavior5stamp15core1_timer_irq17h1f2e3d4c5b6a7980E, . - _ZN12bus_behavior5stamp15core1_timer_irq17h1f2e3d4c5b6a7980E
+
+@ the handler of the README, with the prologue of opt-level=0 before the read of the CVR
+    .section .sram3_code, "ax", %progbits
+    .globl core0_timer_irq
+    .type core0_timer_irq, %function
+    .thumb_func
+core0_timer_irq:
+    push {r7, lr}
+    add r7, sp, #0
+    sub sp, #32
+    ldr r1, .CVR_PTR
+    ldr r0, [r1]
+    str r0, [sp, #4]
+    add sp, #32
+    pop {r7, pc}
+    .p2align 2
+.CVR_PTR:
+    .word 0xe000e018
+    .size core0_timer_irq, . - core0_timer_irq
+
+    .section .sram4_code, "ax", %progbits
+@ a loop, without a straight-line path
+    .globl spin
+    .type spin, %function
+    .thumb_func
+spin:
+    movs r0, #10
+1:
+    subs r0, #1
+    bne 1b
+    bx lr
+
+@ a call, whose callee is not part of the estimate
+    .globl calls_spin
+    .type calls_spin, %function
+    .thumb_func
+calls_spin:
+    push {r4, lr}
+    bl spin
+    movs r4, r0
+    pop {r4, pc}
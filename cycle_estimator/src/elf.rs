@@ -0,0 +1,126 @@
+//! Functions of the `.sramN_code` sections of a firmware ELF.
+
+use core::fmt;
+
+use object::{Object, ObjectSection, ObjectSymbol, SymbolKind};
+
+use crate::estimate::Function;
+
+#[derive(Debug)]
+pub enum ElfError {
+    Parse(object::Error),
+    /// The ELF has no `.sramN_code` section
+    NoSramCode,
+}
+
+impl fmt::Display for ElfError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ElfError::Parse(e) => write!(f, "invalid ELF: {e}"),
+            ElfError::NoSramCode => write!(f, "the ELF has no `.sramN_code` section"),
+        }
+    }
+}
+
+impl std::error::Error for ElfError {}
+
+/// Whether `name` is one of the sections placed in a SRAM bank by `memory.x`
+pub fn is_sram_code(name: &str) -> bool {
+    name.strip_prefix(".sram")
+        .and_then(|rest| rest.strip_suffix("_code"))
+        .is_some_and(|bank| bank.len() == 1 && bank.as_bytes()[0].is_ascii_digit())
+}
+
+/// The functions of the `.sramN_code` sections in address order. A function symbol without a size
+/// (the ones of `global_asm!`) extends to the next symbol or to the end of its section.
+pub fn sram_functions(data: &[u8]) -> Result<Vec<Function>, ElfError> {
+    let file = object::File::parse(data).map_err(ElfError::Parse)?;
+    let mut functions = Vec::new();
+    let mut found = false;
+    for section in file.sections() {
+        let Ok(section_name) = section.name() else {
+            continue;
+        };
+        if !is_sram_code(section_name) {
+            continue;
+        }
+        found = true;
+        let bytes = section.data().map_err(ElfError::Parse)?;
+        let start = section.address() as u32;
+        let end = start + bytes.len() as u32;
+
+        let mut symbols: Vec<(u32, u32, String)> = file
+            .symbols()
+            .filter(|s| s.section_index() == Some(section.index()) && s.kind() == SymbolKind::Text)
+            .filter_map(|s| {
+                let name = s.name().ok()?;
+                Some((s.address() as u32 & !1, s.size() as u32, name.to_string()))
+            })
+            .collect();
+        symbols.sort();
+        symbols.dedup_by_key(|(address, _, _)| *address);
+
+        for (i, (address, size, name)) in symbols.iter().enumerate() {
+            let limit = symbols.get(i + 1).map_or(end, |next| next.0);
+            let size = if *size == 0 { limit - address } else { *size };
+            let offset = (address - start) as usize;
+            functions.push(Function {
+                name: format!("{:#}", rustc_demangle::demangle(name)),
+                section: section_name.to_string(),
+                address: *address,
+                bytes: bytes[offset..offset + size as usize].to_vec(),
+            });
+        }
+    }
+    if !found {
+        return Err(ElfError::NoSramCode);
+    }
+    functions.sort_by_key(|f| f.address);
+    Ok(functions)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{is_sram_code, sram_functions};
+
+    #[test]
+    fn test_is_sram_code() {
+        assert!(is_sram_code(".sram2_code"));
+        assert!(is_sram_code(".sram5_code"));
+        assert!(!is_sram_code(".ram_code"));
+        assert!(!is_sram_code(".sram_code"));
+        assert!(!is_sram_code(".sram10_code"));
+    }
+
+    #[test]
+    fn test_sram_functions() {
+        let functions = sram_functions(include_bytes!("../fixtures/sram_code.elf")).unwrap();
+        let summary: Vec<_> = functions
+            .iter()
+            .map(|f| {
+                (
+                    f.name.as_str(),
+                    f.section.as_str(),
+                    f.address,
+                    f.bytes.len(),
+                )
+            })
+            .collect();
+        // `spin` and `calls_spin` have no size, they extend to the next symbol and the section end
+        assert_eq!(
+            summary,
+            [
+                ("spin", ".sram4_code", 0x2004_0000, 8),
+                ("calls_spin", ".sram4_code", 0x2004_0008, 10),
+                (
+                    "bus_behavior::stamp::core1_timer_irq",
+                    ".sram2_code",
+                    0x2102_0000,
+                    12
+                ),
+                ("core0_timer_irq", ".sram3_code", 0x2103_0000, 20),
+            ]
+        );
+        assert_eq!(functions[3].word(0x2103_0010), Some(0xe000_e018));
+    }
+}
@@ -0,0 +1,301 @@
+//! Basic blocks of a function and their estimated cycles.
+//!
+//! The instructions are found by following the control flow from the entry of the function, so
+//! the literal pools and the padding between the instructions are never decoded. A basic block
+//! ends at a branch, a return or before the target of a branch; a `bl` doesn't end it, but the
+//! cycles of the callee are not part of the estimate.
+
+use core::fmt;
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::thumb::{decode, Flow, Instruction};
+
+/// A function placed in one of the `.sramN_code` sections
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Function {
+    /// Demangled, without the hash
+    pub name: String,
+    pub section: String,
+    /// Address of the first instruction, without the Thumb bit
+    pub address: u32,
+    pub bytes: Vec<u8>,
+}
+
+impl Function {
+    fn contains(&self, address: u32) -> bool {
+        address >= self.address && address - self.address < self.bytes.len() as u32
+    }
+
+    /// The word at `address`, for the literals loaded by the function
+    pub fn word(&self, address: u32) -> Option<u32> {
+        let offset = address.checked_sub(self.address)? as usize;
+        let bytes = self.bytes.get(offset..offset + 4)?;
+        Some(u32::from_le_bytes(bytes.try_into().unwrap()))
+    }
+
+    fn decode(&self, address: u32) -> Result<Instruction, EstimateError> {
+        if !self.contains(address) {
+            return Err(EstimateError::OutOfFunction { address });
+        }
+        decode(address, &self.bytes[(address - self.address) as usize..])
+            .ok_or(EstimateError::Undecodable { address })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Block {
+    pub instructions: Vec<Instruction>,
+}
+
+impl Block {
+    pub fn start(&self) -> u32 {
+        self.instructions[0].address
+    }
+
+    /// Address after the last instruction
+    pub fn end(&self) -> u32 {
+        let last = self.instructions.last().unwrap();
+        last.address + last.size
+    }
+
+    /// Cycles of the block, with the branch ending it not taken if it is conditional
+    pub fn cycles(&self) -> u32 {
+        self.instructions.iter().map(|i| i.cycles).sum()
+    }
+
+    /// Cycles of the block when the conditional branch ending it is taken
+    pub fn taken_cycles(&self) -> Option<u32> {
+        let last = self.instructions.last().unwrap();
+        (last.taken_cycles() != last.cycles)
+            .then(|| self.cycles() - last.cycles + last.taken_cycles())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EstimateError {
+    Undecodable {
+        address: u32,
+    },
+    /// The path leaves the function before reaching the end of the estimate
+    OutOfFunction {
+        address: u32,
+    },
+    /// The path depends on a conditional branch
+    ConditionalBranch {
+        address: u32,
+    },
+    /// The path calls a function, whose cycles are unknown
+    Call {
+        address: u32,
+    },
+    /// The path returns before reaching the instruction ending the estimate
+    NotReached {
+        address: u32,
+    },
+    /// The path loops
+    Loop {
+        address: u32,
+    },
+}
+
+impl fmt::Display for EstimateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EstimateError::Undecodable { address } => {
+                write!(f, "no Armv6-M instruction at {address:#010x}")
+            }
+            EstimateError::OutOfFunction { address } => {
+                write!(f, "the path leaves the function at {address:#010x}")
+            }
+            EstimateError::ConditionalBranch { address } => write!(
+                f,
+                "the path depends on the conditional branch at {address:#010x}"
+            ),
+            EstimateError::Call { address } => write!(
+                f,
+                "the path calls a function at {address:#010x}, its cycles are not estimated"
+            ),
+            EstimateError::NotReached { address } => write!(
+                f,
+                "the path ends at {address:#010x} without reaching the end of the estimate"
+            ),
+            EstimateError::Loop { address } => {
+                write!(f, "the path loops back to {address:#010x}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for EstimateError {}
+
+/// Basic blocks of `function` in address order
+pub fn basic_blocks(function: &Function) -> Result<Vec<Block>, EstimateError> {
+    let mut instructions = BTreeMap::new();
+    let mut leaders = BTreeSet::from([function.address]);
+    let mut pending = vec![function.address];
+    while let Some(mut address) = pending.pop() {
+        while function.contains(address) && !instructions.contains_key(&address) {
+            let instruction = function.decode(address)?;
+            let next = address + instruction.size;
+            if let Flow::Branch {
+                target,
+                conditional,
+            } = instruction.flow
+            {
+                if function.contains(target) {
+                    leaders.insert(target);
+                    pending.push(target);
+                }
+                if conditional {
+                    leaders.insert(next);
+                }
+            }
+            let falls_through = instruction.falls_through();
+            instructions.insert(address, instruction);
+            if !falls_through {
+                break;
+            }
+            address = next;
+        }
+    }
+
+    let mut blocks: Vec<Block> = Vec::new();
+    for (address, instruction) in instructions {
+        match blocks.last_mut() {
+            Some(block)
+                if !leaders.contains(&address)
+                    && block.end() == address
+                    && matches!(
+                        block.instructions.last().unwrap().flow,
+                        Flow::Next | Flow::Call { .. } | Flow::IndirectCall
+                    ) =>
+            {
+                block.instructions.push(instruction)
+            }
+            _ => blocks.push(Block {
+                instructions: vec![instruction],
+            }),
+        }
+    }
+    Ok(blocks)
+}
+
+/// Cycles of the straight-line path from the entry of `function` to the instruction at
+/// `offset` bytes from the entry, excluded, or to the end of the function (the return
+/// included) without `offset`. The unconditional branches are followed; a conditional branch or
+/// a call on the path makes the estimate impossible.
+pub fn path_cycles(function: &Function, offset: Option<u32>) -> Result<u32, EstimateError> {
+    let end = offset.map(|offset| function.address + offset);
+    let mut visited = BTreeSet::new();
+    let mut address = function.address;
+    let mut cycles = 0;
+    loop {
+        if Some(address) == end {
+            return Ok(cycles);
+        }
+        if !visited.insert(address) {
+            return Err(EstimateError::Loop { address });
+        }
+        let instruction = function.decode(address)?;
+        match instruction.flow {
+            Flow::Next => address += instruction.size,
+            Flow::Branch {
+                conditional: true, ..
+            } => return Err(EstimateError::ConditionalBranch { address }),
+            Flow::Branch { target, .. } => address = target,
+            Flow::Call { .. } | Flow::IndirectCall => return Err(EstimateError::Call { address }),
+            Flow::Return | Flow::IndirectBranch | Flow::Stop => {
+                return match end {
+                    None => Ok(cycles + instruction.cycles),
+                    Some(_) => Err(EstimateError::NotReached { address }),
+                };
+            }
+        }
+        cycles += instruction.cycles;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{basic_blocks, path_cycles, EstimateError, Function};
+    use crate::elf::sram_functions;
+
+    fn function(name: &str) -> Function {
+        let elf = include_bytes!("../fixtures/sram_code.elf");
+        sram_functions(elf)
+            .unwrap()
+            .into_iter()
+            .find(|f| f.name == name)
+            .unwrap()
+    }
+
+    #[test]
+    fn test_irq_handler_delay() {
+        // the instructions executed before the read of the CVR, 7 cycles in the README
+        let handler = function("core0_timer_irq");
+        assert_eq!(path_cycles(&handler, Some(8)), Ok(7));
+        assert_eq!(path_cycles(&handler, None), Ok(16));
+
+        let stamp = function("bus_behavior::stamp::core1_timer_irq");
+        assert_eq!(path_cycles(&stamp, Some(0)), Ok(0));
+        assert_eq!(path_cycles(&stamp, None), Ok(8));
+    }
+
+    #[test]
+    fn test_loop() {
+        let spin = function("spin");
+        let blocks = basic_blocks(&spin).unwrap();
+        let summary: Vec<_> = blocks
+            .iter()
+            .map(|b| (b.start(), b.cycles(), b.taken_cycles()))
+            .collect();
+        assert_eq!(
+            summary,
+            [
+                (0x2004_0000, 1, None),
+                (0x2004_0002, 2, Some(3)),
+                (0x2004_0006, 2, None)
+            ]
+        );
+        assert_eq!(
+            path_cycles(&spin, None),
+            Err(EstimateError::ConditionalBranch {
+                address: 0x2004_0004
+            })
+        );
+    }
+
+    #[test]
+    fn test_call() {
+        // the `bl` doesn't end the block, the literal pool after the return is not decoded
+        let caller = function("calls_spin");
+        let blocks = basic_blocks(&caller).unwrap();
+        assert_eq!(blocks.len(), 1);
+        assert_eq!(blocks[0].instructions.len(), 4);
+        assert_eq!(blocks[0].cycles(), 11);
+        assert_eq!(path_cycles(&caller, Some(2)), Ok(3));
+        assert_eq!(
+            path_cycles(&caller, None),
+            Err(EstimateError::Call {
+                address: 0x2004_000a
+            })
+        );
+        assert_eq!(
+            path_cycles(&caller, Some(3)),
+            Err(EstimateError::Call {
+                address: 0x2004_000a
+            })
+        );
+    }
+
+    #[test]
+    fn test_not_reached() {
+        let stamp = function("bus_behavior::stamp::core1_timer_irq");
+        assert_eq!(
+            path_cycles(&stamp, Some(0x20)),
+            Err(EstimateError::NotReached {
+                address: 0x2102_0006
+            })
+        );
+    }
+}
@@ -0,0 +1,11 @@
+//! Static estimate of the cycles of the code placed in the SRAM banks by the `.sramN_code`
+//! sections, to check the cycle counts the experiments assume against the disassembly of the
+//! firmware that is actually measured.
+
+mod elf;
+mod estimate;
+mod thumb;
+
+pub use elf::{is_sram_code, sram_functions, ElfError};
+pub use estimate::{basic_blocks, path_cycles, Block, EstimateError, Function};
+pub use thumb::{decode, Flow, Instruction};
@@ -0,0 +1,162 @@
+use std::{env, fs, process::ExitCode};
+
+use cycle_estimator::{basic_blocks, path_cycles, sram_functions, Function};
+
+const USAGE: &str =
+    "usage: cycle_estimator <firmware.elf> [--measured <function>[+<offset>]=<cycles>]...";
+
+/// A cycle count measured on hardware, for the path from the entry of `function` to `offset`
+struct Measured {
+    function: String,
+    offset: Option<u32>,
+    cycles: u32,
+}
+
+fn parse_number(s: &str) -> Option<u32> {
+    match s.strip_prefix("0x") {
+        Some(hex) => u32::from_str_radix(hex, 16).ok(),
+        None => s.parse().ok(),
+    }
+}
+
+fn parse_measured(arg: &str) -> Option<Measured> {
+    let (path, cycles) = arg.split_once('=')?;
+    let (function, offset) = match path.split_once('+') {
+        Some((function, offset)) => (function, Some(parse_number(offset)?)),
+        None => (path, None),
+    };
+    Some(Measured {
+        function: function.to_string(),
+        offset,
+        cycles: parse_number(cycles)?,
+    })
+}
+
+/// The function named `name`, either in full or by the last segment of its path
+fn find<'a>(functions: &'a [Function], name: &str) -> Result<&'a Function, String> {
+    let matches: Vec<&Function> = functions
+        .iter()
+        .filter(|f| f.name == name || f.name.rsplit("::").next() == Some(name))
+        .collect();
+    match matches[..] {
+        [function] => Ok(function),
+        [] => Err(format!(
+            "no function `{name}` in the `.sramN_code` sections"
+        )),
+        _ => Err(format!(
+            "`{name}` is ambiguous: {}",
+            matches
+                .iter()
+                .map(|f| f.name.as_str())
+                .collect::<Vec<_>>()
+                .join(", ")
+        )),
+    }
+}
+
+fn main() -> ExitCode {
+    let mut args = env::args().skip(1);
+    let Some(path) = args.next() else {
+        eprintln!("{USAGE}");
+        return ExitCode::FAILURE;
+    };
+    let mut measured = Vec::new();
+    while let Some(arg) = args.next() {
+        let m = match (arg.as_str(), args.next()) {
+            ("--measured", Some(value)) => parse_measured(&value),
+            _ => None,
+        };
+        let Some(m) = m else {
+            eprintln!("{USAGE}");
+            return ExitCode::FAILURE;
+        };
+        measured.push(m);
+    }
+
+    let data = match fs::read(&path) {
+        Ok(data) => data,
+        Err(e) => {
+            eprintln!("could not read {path}: {e}");
+            return ExitCode::FAILURE;
+        }
+    };
+    let functions = match sram_functions(&data) {
+        Ok(functions) => functions,
+        Err(e) => {
+            eprintln!("{path}: {e}");
+            return ExitCode::FAILURE;
+        }
+    };
+
+    for function in &functions {
+        println!(
+            "{} ({}, {:#010x})",
+            function.name, function.section, function.address
+        );
+        let blocks = match basic_blocks(function) {
+            Ok(blocks) => blocks,
+            Err(e) => {
+                println!("  {e}\n");
+                continue;
+            }
+        };
+        for block in &blocks {
+            let taken = block
+                .taken_cycles()
+                .map_or_else(String::new, |c| format!(", {c} when taken"));
+            println!(
+                "  block {:#010x}: {} cycles{}",
+                block.start(),
+                block.cycles(),
+                taken
+            );
+            for instruction in &block.instructions {
+                let literal = instruction
+                    .literal
+                    .and_then(|address| function.word(address))
+                    .map_or_else(String::new, |word| format!(", ={word:#010x}"));
+                println!(
+                    "    {:<48} ; {}{}",
+                    instruction.to_string(),
+                    instruction.cycles,
+                    literal
+                );
+            }
+        }
+        println!();
+    }
+
+    let mut mismatches = 0;
+    for m in &measured {
+        let name = match m.offset {
+            Some(offset) => format!("{}+{offset}", m.function),
+            None => m.function.clone(),
+        };
+        let estimate = find(&functions, &m.function)
+            .and_then(|f| path_cycles(f, m.offset).map_err(|e| e.to_string()));
+        match estimate {
+            Ok(cycles) if cycles == m.cycles => {
+                println!(
+                    "{name}: estimated {cycles} cycles, measured {}: ok",
+                    m.cycles
+                )
+            }
+            Ok(cycles) => {
+                mismatches += 1;
+                println!(
+                    "{name}: estimated {cycles} cycles, measured {}: MISMATCH",
+                    m.cycles
+                )
+            }
+            Err(e) => {
+                mismatches += 1;
+                println!("{name}: {e}");
+            }
+        }
+    }
+    if mismatches == 0 {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::FAILURE
+    }
+}
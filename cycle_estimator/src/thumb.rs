@@ -0,0 +1,470 @@
+//! Decoder of the Thumb instructions of the Armv6-M architecture, with the cycle count of each
+//! instruction on the Cortex-M0+.
+//!
+//! The cycles are the ones of the Cortex-M0+ technical reference manual for a system without wait
+//! states, which is the case of the SRAM banks when they are not contested: 1 cycle per data
+//! processing instruction (the multiplier of the rp2040 is the single cycle one), 2 per load or
+//! store, 1 + N per `ldm`/`stm`/`push`/`pop` of N registers, 3 + N for a `pop` loading the PC,
+//! 2 per taken branch and 1 per conditional branch not taken, 3 per `bl`, `msr`, `mrs` and
+//! barrier. They match the ones measured by `bus_behavior/examples/instruction_timing.rs`.
+
+use core::fmt;
+
+/// Where the execution goes after an instruction
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Flow {
+    /// The next instruction
+    Next,
+    /// `b` or `b<cond>`, which costs one more cycle when taken
+    Branch { target: u32, conditional: bool },
+    /// `bl`, the execution continues after it when the callee returns
+    Call { target: u32 },
+    /// `blx`
+    IndirectCall,
+    /// `bx lr` or `pop {.., pc}`
+    Return,
+    /// `bx`, `mov pc` or `add pc` with a register
+    IndirectBranch,
+    /// `udf` or `bkpt`, the execution doesn't continue
+    Stop,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Instruction {
+    pub address: u32,
+    /// 2 or 4 bytes
+    pub size: u32,
+    /// The halfword, or both halfwords of a 32-bit instruction with the first one in the high bits
+    pub encoding: u32,
+    pub text: String,
+    /// Cycles without wait states, for a conditional branch when it is not taken
+    pub cycles: u32,
+    pub flow: Flow,
+    /// Address of the word loaded by `ldr Rt, [pc, #imm]`
+    pub literal: Option<u32>,
+}
+
+impl Instruction {
+    /// Cycles when the branch is taken, for a conditional branch
+    pub fn taken_cycles(&self) -> u32 {
+        match self.flow {
+            Flow::Branch {
+                conditional: true, ..
+            } => self.cycles + 1,
+            _ => self.cycles,
+        }
+    }
+
+    /// Whether the instruction after this one is executed next, possibly after a call
+    pub fn falls_through(&self) -> bool {
+        match self.flow {
+            Flow::Next | Flow::Call { .. } | Flow::IndirectCall => true,
+            Flow::Branch { conditional, .. } => conditional,
+            Flow::Return | Flow::IndirectBranch | Flow::Stop => false,
+        }
+    }
+}
+
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let encoding = if self.size == 4 {
+            format!("{:04x} {:04x}", self.encoding >> 16, self.encoding & 0xffff)
+        } else {
+            format!("{:04x}", self.encoding)
+        };
+        write!(f, "{:#010x}  {:<9}  {}", self.address, encoding, self.text)
+    }
+}
+
+const REGISTERS: [&str; 16] = [
+    "r0", "r1", "r2", "r3", "r4", "r5", "r6", "r7", "r8", "r9", "r10", "r11", "r12", "sp", "lr",
+    "pc",
+];
+
+const CONDITIONS: [&str; 14] = [
+    "eq", "ne", "hs", "lo", "mi", "pl", "vs", "vc", "hi", "ls", "ge", "lt", "gt", "le",
+];
+
+fn reg(r: u16) -> &'static str {
+    REGISTERS[r as usize & 0xf]
+}
+
+/// `{r0, r4, lr}` for the low registers of `list` and `extra`
+fn register_list(list: u16, extra: Option<&'static str>) -> String {
+    let names: Vec<&str> = (0..8)
+        .filter(|r| list & (1 << r) != 0)
+        .map(reg)
+        .chain(extra)
+        .collect();
+    format!("{{{}}}", names.join(", "))
+}
+
+fn special_register(sysm: u32) -> String {
+    match sysm {
+        0 => "apsr".to_string(),
+        1 => "iapsr".to_string(),
+        2 => "eapsr".to_string(),
+        3 => "xpsr".to_string(),
+        5 => "ipsr".to_string(),
+        6 => "epsr".to_string(),
+        7 => "iepsr".to_string(),
+        8 => "msp".to_string(),
+        9 => "psp".to_string(),
+        16 => "primask".to_string(),
+        20 => "control".to_string(),
+        _ => format!("sysm{sysm}"),
+    }
+}
+
+/// Sign extends the `bits` low bits of `value`
+fn sign_extend(value: u32, bits: u32) -> u32 {
+    let shift = 32 - bits;
+    (((value << shift) as i32) >> shift) as u32
+}
+
+/// Decodes the instruction at `address` from `bytes`, which start at `address`. `None` if the
+/// bytes are not a valid Armv6-M instruction or too short.
+pub fn decode(address: u32, bytes: &[u8]) -> Option<Instruction> {
+    let hw = u16::from_le_bytes([*bytes.first()?, *bytes.get(1)?]);
+    if hw >> 11 >= 0b11101 {
+        let hw2 = u16::from_le_bytes([*bytes.get(2)?, *bytes.get(3)?]);
+        return decode_32(address, hw, hw2);
+    }
+
+    let mut cycles = 1;
+    let mut flow = Flow::Next;
+    let mut literal = None;
+    let low = |shift: u16| reg((hw >> shift) & 7);
+    let imm5 = (hw >> 6) & 0x1f;
+    let imm8 = hw & 0xff;
+
+    let text = if hw >> 13 == 0b000 {
+        let op = (hw >> 11) & 3;
+        if op == 3 {
+            // add/sub with a register or a 3-bit immediate
+            let name = if hw & (1 << 9) == 0 { "adds" } else { "subs" };
+            let operand = if hw & (1 << 10) == 0 {
+                low(6).to_string()
+            } else {
+                format!("#{}", (hw >> 6) & 7)
+            };
+            format!("{name} {}, {}, {operand}", low(0), low(3))
+        } else if op == 0 && imm5 == 0 {
+            format!("movs {}, {}", low(0), low(3))
+        } else {
+            let name = ["lsls", "lsrs", "asrs"][op as usize];
+            let shift = if op != 0 && imm5 == 0 { 32 } else { imm5 };
+            format!("{name} {}, {}, #{shift}", low(0), low(3))
+        }
+    } else if hw >> 13 == 0b001 {
+        let name = ["movs", "cmp", "adds", "subs"][((hw >> 11) & 3) as usize];
+        format!("{name} {}, #{imm8}", low(8))
+    } else if hw >> 10 == 0b010000 {
+        let (rdn, rm) = (low(0), low(3));
+        match (hw >> 6) & 0xf {
+            0x9 => format!("rsbs {rdn}, {rm}, #0"),
+            0xd => format!("muls {rdn}, {rm}, {rdn}"),
+            op => {
+                let name = [
+                    "ands", "eors", "lsls", "lsrs", "asrs", "adcs", "sbcs", "rors", "tst", "",
+                    "cmp", "cmn", "orrs", "", "bics", "mvns",
+                ][op as usize];
+                format!("{name} {rdn}, {rm}")
+            }
+        }
+    } else if hw >> 10 == 0b010001 {
+        let rm = (hw >> 3) & 0xf;
+        let rdn = ((hw >> 4) & 8) | (hw & 7);
+        match (hw >> 8) & 3 {
+            0 | 2 => {
+                if rdn == 15 {
+                    cycles = 2;
+                    flow = Flow::IndirectBranch;
+                }
+                let name = if (hw >> 8) & 3 == 0 { "add" } else { "mov" };
+                format!("{name} {}, {}", reg(rdn), reg(rm))
+            }
+            1 => format!("cmp {}, {}", reg(rdn), reg(rm)),
+            _ => {
+                cycles = 2;
+                if hw & (1 << 7) != 0 {
+                    flow = Flow::IndirectCall;
+                    format!("blx {}", reg(rm))
+                } else {
+                    flow = if rm == 14 {
+                        Flow::Return
+                    } else {
+                        Flow::IndirectBranch
+                    };
+                    format!("bx {}", reg(rm))
+                }
+            }
+        }
+    } else if hw >> 11 == 0b01001 {
+        cycles = 2;
+        let offset = imm8 as u32 * 4;
+        literal = Some((address.wrapping_add(4) & !3).wrapping_add(offset));
+        format!("ldr {}, [pc, #{offset}]", low(8))
+    } else if hw >> 12 == 0b0101 {
+        cycles = 2;
+        let name = [
+            "str", "strh", "strb", "ldrsb", "ldr", "ldrh", "ldrb", "ldrsh",
+        ][((hw >> 9) & 7) as usize];
+        format!("{name} {}, [{}, {}]", low(0), low(3), low(6))
+    } else if hw >> 13 == 0b011 {
+        cycles = 2;
+        let (name, offset) = match (hw >> 11) & 3 {
+            0 => ("str", imm5 * 4),
+            1 => ("ldr", imm5 * 4),
+            2 => ("strb", imm5),
+            _ => ("ldrb", imm5),
+        };
+        format!("{name} {}, [{}, #{offset}]", low(0), low(3))
+    } else if hw >> 12 == 0b1000 {
+        cycles = 2;
+        let name = if hw & (1 << 11) == 0 { "strh" } else { "ldrh" };
+        format!("{name} {}, [{}, #{}]", low(0), low(3), imm5 * 2)
+    } else if hw >> 12 == 0b1001 {
+        cycles = 2;
+        let name = if hw & (1 << 11) == 0 { "str" } else { "ldr" };
+        format!("{name} {}, [sp, #{}]", low(8), imm8 as u32 * 4)
+    } else if hw >> 12 == 0b1010 {
+        let base = if hw & (1 << 11) == 0 { "pc" } else { "sp" };
+        format!("add {}, {base}, #{}", low(8), imm8 as u32 * 4)
+    } else if hw >> 12 == 0b1011 {
+        match hw {
+            _ if hw & 0xff00 == 0xb000 => {
+                let name = if hw & (1 << 7) == 0 { "add" } else { "sub" };
+                format!("{name} sp, #{}", (hw & 0x7f) as u32 * 4)
+            }
+            _ if hw & 0xff00 == 0xb200 => {
+                let name = ["sxth", "sxtb", "uxth", "uxtb"][((hw >> 6) & 3) as usize];
+                format!("{name} {}, {}", low(0), low(3))
+            }
+            _ if hw & 0xfe00 == 0xb400 => {
+                let lr = hw & (1 << 8) != 0;
+                cycles = 1 + (hw & 0xff).count_ones() + lr as u32;
+                format!("push {}", register_list(hw, lr.then_some("lr")))
+            }
+            _ if hw & 0xffef == 0xb662 => {
+                let name = if hw & (1 << 4) == 0 { "cpsie" } else { "cpsid" };
+                format!("{name} i")
+            }
+            _ if hw & 0xff00 == 0xba00 && (hw >> 6) & 3 != 2 => {
+                let name = ["rev", "rev16", "", "revsh"][((hw >> 6) & 3) as usize];
+                format!("{name} {}, {}", low(0), low(3))
+            }
+            _ if hw & 0xfe00 == 0xbc00 => {
+                let pc = hw & (1 << 8) != 0;
+                cycles = 1 + (hw & 0xff).count_ones();
+                if pc {
+                    cycles += 2;
+                    flow = Flow::Return;
+                }
+                format!("pop {}", register_list(hw, pc.then_some("pc")))
+            }
+            _ if hw & 0xff00 == 0xbe00 => {
+                flow = Flow::Stop;
+                format!("bkpt #{imm8}")
+            }
+            _ if hw & 0xff0f == 0xbf00 && (hw >> 4) & 0xf <= 4 => {
+                let hint = (hw >> 4) & 0xf;
+                if hint == 2 || hint == 3 {
+                    cycles = 2;
+                }
+                ["nop", "yield", "wfe", "wfi", "sev"][hint as usize].to_string()
+            }
+            _ => return None,
+        }
+    } else if hw >> 12 == 0b1100 {
+        let rn = (hw >> 8) & 7;
+        let list = hw & 0xff;
+        if list == 0 {
+            return None;
+        }
+        cycles = 1 + list.count_ones();
+        if hw & (1 << 11) == 0 {
+            format!("stm {}!, {}", reg(rn), register_list(list, None))
+        } else {
+            let writeback = if list & (1 << rn) == 0 { "!" } else { "" };
+            format!("ldm {}{writeback}, {}", reg(rn), register_list(list, None))
+        }
+    } else if hw >> 12 == 0b1101 {
+        match (hw >> 8) & 0xf {
+            0xe => {
+                flow = Flow::Stop;
+                format!("udf #{imm8}")
+            }
+            // the exception taken is not part of the estimate
+            0xf => format!("svc #{imm8}"),
+            cond => {
+                let target = address
+                    .wrapping_add(4)
+                    .wrapping_add(sign_extend(imm8 as u32, 8) << 1);
+                flow = Flow::Branch {
+                    target,
+                    conditional: true,
+                };
+                format!("b{} {target:#x}", CONDITIONS[cond as usize])
+            }
+        }
+    } else if hw >> 11 == 0b11100 {
+        cycles = 2;
+        let target = address
+            .wrapping_add(4)
+            .wrapping_add(sign_extend((hw & 0x7ff) as u32, 11) << 1);
+        flow = Flow::Branch {
+            target,
+            conditional: false,
+        };
+        format!("b {target:#x}")
+    } else {
+        return None;
+    };
+
+    Some(Instruction {
+        address,
+        size: 2,
+        encoding: hw as u32,
+        text,
+        cycles,
+        flow,
+        literal,
+    })
+}
+
+fn decode_32(address: u32, hw1: u16, hw2: u16) -> Option<Instruction> {
+    let mut flow = Flow::Next;
+    let text = if hw1 >> 11 == 0b11110 && hw2 & 0xd000 == 0xd000 {
+        let s = ((hw1 >> 10) & 1) as u32;
+        let i1 = !(((hw2 >> 13) & 1) as u32 ^ s) & 1;
+        let i2 = !(((hw2 >> 11) & 1) as u32 ^ s) & 1;
+        let offset = (s << 24)
+            | (i1 << 23)
+            | (i2 << 22)
+            | (((hw1 & 0x3ff) as u32) << 12)
+            | (((hw2 & 0x7ff) as u32) << 1);
+        let target = address
+            .wrapping_add(4)
+            .wrapping_add(sign_extend(offset, 25));
+        flow = Flow::Call { target };
+        format!("bl {target:#x}")
+    } else if hw1 & 0xfff0 == 0xf380 && hw2 & 0xff00 == 0x8800 {
+        format!(
+            "msr {}, {}",
+            special_register((hw2 & 0xff) as u32),
+            reg(hw1 & 0xf)
+        )
+    } else if hw1 == 0xf3ef && hw2 & 0xf000 == 0x8000 {
+        format!(
+            "mrs {}, {}",
+            reg((hw2 >> 8) & 0xf),
+            special_register((hw2 & 0xff) as u32)
+        )
+    } else if hw1 == 0xf3bf && hw2 & 0xfff0 == 0x8f40 {
+        "dsb sy".to_string()
+    } else if hw1 == 0xf3bf && hw2 & 0xfff0 == 0x8f50 {
+        "dmb sy".to_string()
+    } else if hw1 == 0xf3bf && hw2 & 0xfff0 == 0x8f60 {
+        "isb sy".to_string()
+    } else if hw1 & 0xfff0 == 0xf7f0 && hw2 & 0xf000 == 0xa000 {
+        flow = Flow::Stop;
+        format!(
+            "udf.w #{}",
+            (((hw1 & 0xf) as u32) << 12) | (hw2 & 0xfff) as u32
+        )
+    } else {
+        return None;
+    };
+    Some(Instruction {
+        address,
+        size: 4,
+        encoding: ((hw1 as u32) << 16) | hw2 as u32,
+        text,
+        cycles: 3,
+        flow,
+        literal: None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{decode, Flow};
+
+    fn text(address: u32, bytes: &[u8]) -> (String, u32) {
+        let instruction = decode(address, bytes).unwrap();
+        (instruction.text, instruction.cycles)
+    }
+
+    #[test]
+    fn test_loads_and_stores() {
+        assert_eq!(text(0, &[0x08, 0x68]), ("ldr r0, [r1, #0]".into(), 2));
+        assert_eq!(text(0, &[0x4a, 0x60]), ("str r2, [r1, #4]".into(), 2));
+        assert_eq!(text(0, &[0x08, 0x5c]), ("ldrb r0, [r1, r0]".into(), 2));
+        assert_eq!(text(0, &[0x01, 0x91]), ("str r1, [sp, #4]".into(), 2));
+        // the literal is word aligned, relative to the address of the instruction + 4
+        let ldr = decode(0x2102_0002, &[0x01, 0x49]).unwrap();
+        assert_eq!(ldr.text, "ldr r1, [pc, #4]");
+        assert_eq!(ldr.literal, Some(0x2102_0008));
+    }
+
+    #[test]
+    fn test_multiple_registers() {
+        assert_eq!(text(0, &[0x80, 0xb5]), ("push {r7, lr}".into(), 3));
+        assert_eq!(text(0, &[0x0f, 0xb4]), ("push {r0, r1, r2, r3}".into(), 5));
+        assert_eq!(text(0, &[0x0f, 0xbc]), ("pop {r0, r1, r2, r3}".into(), 5));
+        let pop = decode(0, &[0x80, 0xbd]).unwrap();
+        assert_eq!((pop.text.as_str(), pop.cycles), ("pop {r7, pc}", 4));
+        assert_eq!(pop.flow, Flow::Return);
+        assert_eq!(text(0, &[0x03, 0xca]), ("ldm r2!, {r0, r1}".into(), 3));
+        assert_eq!(text(0, &[0x03, 0xc8]), ("ldm r0, {r0, r1}".into(), 3));
+        assert_eq!(
+            text(0, &[0x1e, 0xc0]),
+            ("stm r0!, {r1, r2, r3, r4}".into(), 5)
+        );
+    }
+
+    #[test]
+    fn test_data_processing() {
+        assert_eq!(text(0, &[0x00, 0xbf]), ("nop".into(), 1));
+        assert_eq!(text(0, &[0x00, 0xaf]), ("add r7, sp, #0".into(), 1));
+        assert_eq!(text(0, &[0x88, 0xb0]), ("sub sp, #32".into(), 1));
+        assert_eq!(text(0, &[0x48, 0x43]), ("muls r0, r1, r0".into(), 1));
+        assert_eq!(text(0, &[0x08, 0x46]), ("mov r0, r1".into(), 1));
+        assert_eq!(text(0, &[0x01, 0x30]), ("adds r0, #1".into(), 1));
+    }
+
+    #[test]
+    fn test_branches() {
+        let b = decode(0x100, &[0xfe, 0xe7]).unwrap();
+        assert_eq!((b.text.as_str(), b.cycles), ("b 0x100", 2));
+        let beq = decode(0x100, &[0xfc, 0xd0]).unwrap();
+        assert_eq!(beq.text, "beq 0xfc");
+        assert_eq!((beq.cycles, beq.taken_cycles()), (1, 2));
+        let bx = decode(0, &[0x70, 0x47]).unwrap();
+        assert_eq!(
+            (bx.text.as_str(), bx.cycles, bx.flow),
+            ("bx lr", 2, Flow::Return)
+        );
+        // bl to 0x1000 from 0x2000
+        let bl = decode(0x2000, &[0xfe, 0xf7, 0xfe, 0xff]).unwrap();
+        assert_eq!((bl.text.as_str(), bl.cycles), ("bl 0x1000", 3));
+        assert_eq!(bl.flow, Flow::Call { target: 0x1000 });
+    }
+
+    #[test]
+    fn test_system() {
+        assert_eq!(text(0, &[0xbf, 0xf3, 0x5f, 0x8f]), ("dmb sy".into(), 3));
+        assert_eq!(
+            text(0, &[0xef, 0xf3, 0x10, 0x80]),
+            ("mrs r0, primask".into(), 3)
+        );
+        assert_eq!(
+            text(0, &[0x80, 0xf3, 0x10, 0x88]),
+            ("msr primask, r0".into(), 3)
+        );
+        assert_eq!(text(0, &[0x72, 0xb6]), ("cpsid i".into(), 1));
+        assert_eq!(text(0, &[0x20, 0xbf]), ("wfe".into(), 2));
+        assert_eq!(decode(0, &[0x00, 0xde]).unwrap().flow, Flow::Stop);
+    }
+}
@@ -0,0 +1,8 @@
+//! Turns the records printed by the `bus_behavior` experiments into the result tables of
+//! `bus_behavior/README.md` and CSV files for plotting.
+
+mod record;
+mod report;
+
+pub use record::{parse_log, parse_record, Record};
+pub use report::{tables, update_readme, ReportError, Table};
@@ -0,0 +1,94 @@
+use std::{env, fs, path::PathBuf, process::ExitCode};
+
+use experiment_report::{parse_log, tables, update_readme};
+
+const USAGE: &str = "usage: experiment_report <log> [--readme <README.md>] [--csv <directory>]";
+
+fn main() -> ExitCode {
+    let mut args = env::args().skip(1);
+    let mut log_path = None;
+    let mut readme_path = None;
+    let mut csv_dir = None;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--readme" => readme_path = args.next(),
+            "--csv" => csv_dir = args.next().map(PathBuf::from),
+            _ if log_path.is_none() && !arg.starts_with("--") => log_path = Some(arg),
+            _ => {
+                eprintln!("{USAGE}");
+                return ExitCode::FAILURE;
+            }
+        }
+    }
+    let Some(log_path) = log_path else {
+        eprintln!("{USAGE}");
+        return ExitCode::FAILURE;
+    };
+
+    let log = match fs::read_to_string(&log_path) {
+        Ok(log) => log,
+        Err(e) => {
+            eprintln!("could not read {log_path}: {e}");
+            return ExitCode::FAILURE;
+        }
+    };
+    let tables = tables(&parse_log(&log));
+    if tables.is_empty() {
+        eprintln!("{log_path}: no records, was the log captured with `cargo run --example ...`?");
+        return ExitCode::FAILURE;
+    }
+
+    if readme_path.is_none() && csv_dir.is_none() {
+        for table in &tables {
+            println!("{}:\n\n{}", table.name, table.to_markdown());
+        }
+        return ExitCode::SUCCESS;
+    }
+
+    if let Some(readme_path) = readme_path {
+        let readme = match fs::read_to_string(&readme_path) {
+            Ok(readme) => readme,
+            Err(e) => {
+                eprintln!("could not read {readme_path}: {e}");
+                return ExitCode::FAILURE;
+            }
+        };
+        let (readme, updated) = match update_readme(&readme, &tables) {
+            Ok(result) => result,
+            Err(e) => {
+                eprintln!("{readme_path}: {e}");
+                return ExitCode::FAILURE;
+            }
+        };
+        if let Err(e) = fs::write(&readme_path, readme) {
+            eprintln!("could not write {readme_path}: {e}");
+            return ExitCode::FAILURE;
+        }
+        for table in &tables {
+            if updated.contains(&table.name) {
+                println!("updated table `{}` in {readme_path}", table.name);
+            } else {
+                println!(
+                    "table `{}` has no `<!-- report:{} -->` marker in {readme_path}",
+                    table.name, table.name
+                );
+            }
+        }
+    }
+
+    if let Some(csv_dir) = csv_dir {
+        if let Err(e) = fs::create_dir_all(&csv_dir) {
+            eprintln!("could not create {}: {e}", csv_dir.display());
+            return ExitCode::FAILURE;
+        }
+        for table in &tables {
+            let path = csv_dir.join(format!("{}.csv", table.name));
+            if let Err(e) = fs::write(&path, table.to_csv()) {
+                eprintln!("could not write {}: {e}", path.display());
+                return ExitCode::FAILURE;
+            }
+            println!("wrote {}", path.display());
+        }
+    }
+    ExitCode::SUCCESS
+}
@@ -0,0 +1,160 @@
+//! Records as printed by the experiments, see `bus_behavior/src/record.rs`:
+//!
+//! ```text
+//! @experiments experiment=exp1 sys_mhz=125 proc0=1 proc1=1 ... core0_cycles=3 core1_cycles=2 ... verdict=pass
+//! ```
+//!
+//! A record is found anywhere in a line, so the log can be captured with or without the
+//! timestamps, levels and locations printed by probe-rs.
+
+/// One line of results, e.g. one experiment
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Record {
+    /// Table the record belongs to, the name following `@`
+    pub table: String,
+    /// `key=value` fields in the order they were printed
+    pub fields: Vec<(String, String)>,
+}
+
+impl Record {
+    /// Value of the field `key`, if any
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.fields
+            .iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, v)| v.as_str())
+    }
+}
+
+/// Returns the records of a captured log in order, the other lines are ignored
+pub fn parse_log(log: &str) -> Vec<Record> {
+    log.lines().filter_map(parse_record).collect()
+}
+
+/// Parses the record contained in `line`, if any. The fields end at the first token that is not a
+/// `key=value` pair, e.g. the location appended by probe-rs.
+pub fn parse_record(line: &str) -> Option<Record> {
+    let line = strip_ansi(line);
+    let mut tokens = line.split_whitespace();
+    let table = tokens.find_map(|token| token.strip_prefix('@').filter(|name| is_name(name)))?;
+    let fields: Vec<(String, String)> = tokens
+        .map_while(|token| token.split_once('='))
+        .take_while(|(key, _)| is_name(key))
+        .map(|(key, value)| (key.to_string(), value.to_string()))
+        .collect();
+    if fields.is_empty() {
+        return None;
+    }
+    Some(Record {
+        table: table.to_string(),
+        fields,
+    })
+}
+
+/// Table names and keys are made of ASCII letters, digits and underscores
+fn is_name(s: &str) -> bool {
+    !s.is_empty() && s.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// Removes the color escape sequences (`ESC [ ... m`) probe-rs adds when printing to a terminal
+fn strip_ansi(line: &str) -> String {
+    let mut out = String::with_capacity(line.len());
+    let mut chars = line.chars();
+    while let Some(c) = chars.next() {
+        if c == '\u{1b}' {
+            // skip up to and including the final byte of the sequence
+            for c in chars.by_ref() {
+                if c.is_ascii_alphabetic() {
+                    break;
+                }
+            }
+        } else {
+            out.push(c);
+        }
+    }
+    out
+}
+
+// tests
+
+#[cfg(test)]
+mod tests {
+    use crate::{parse_log, parse_record};
+
+    #[test]
+    fn test_plain_record() {
+        let record =
+            parse_record("@experiments experiment=exp1 core0_cycles=3 verdict=pass").unwrap();
+        assert_eq!(record.table, "experiments");
+        assert_eq!(record.get("experiment"), Some("exp1"));
+        assert_eq!(record.get("core0_cycles"), Some("3"));
+        assert_eq!(record.get("verdict"), Some("pass"));
+        assert_eq!(record.get("core1_cycles"), None);
+    }
+
+    #[test]
+    fn test_probe_rs_decorations() {
+        // timestamp and level before the message, location after it, colors
+        let line = "0.002344 \u{1b}[32mINFO \u{1b}[0m @dma_contention dma=off core0_ldr=2 \
+                    (bus_behavior examples/dma_contention.rs:167)";
+        let record = parse_record(line).unwrap();
+        assert_eq!(record.table, "dma_contention");
+        assert_eq!(
+            record.fields,
+            vec![
+                ("dma".to_string(), "off".to_string()),
+                ("core0_ldr".to_string(), "2".to_string())
+            ]
+        );
+    }
+
+    #[test]
+    fn test_stats_record() {
+        let line = "@experiment_stats experiment=exp1 sys_mhz=125 perturbation=delay64+flush \
+                    quantity=core0_cycles trials=1000 min=2 max=3 mean=2.997 p50=3 p90=3 p99=3 \
+                    histogram=<2:1,2:2,3:997";
+        let record = parse_record(line).unwrap();
+        assert_eq!(record.table, "experiment_stats");
+        assert_eq!(record.fields.len(), 12);
+        assert_eq!(record.get("perturbation"), Some("delay64+flush"));
+        assert_eq!(record.get("mean"), Some("2.997"));
+        assert_eq!(record.get("histogram"), Some("<2:1,2:2,3:997"));
+    }
+
+    #[test]
+    fn test_not_records() {
+        assert_eq!(
+            parse_record("== exp1: both cores read the same sram4 word"),
+            None
+        );
+        assert_eq!(
+            parse_record("└─ bus_behavior::experiment::run @ src/experiment.rs:250"),
+            None
+        );
+        assert_eq!(parse_record("@experiments"), None);
+        assert_eq!(parse_record("email me @ someone@example.com"), None);
+    }
+
+    #[test]
+    fn test_recorded_logs() {
+        let records = parse_log(include_str!("../fixtures/experiments.log"));
+        assert_eq!(records.len(), 9);
+        assert!(records.iter().all(|r| r.table == "experiments"));
+        let names: Vec<&str> = records
+            .iter()
+            .map(|r| r.get("experiment").unwrap())
+            .collect();
+        assert_eq!(
+            names,
+            ["exp1", "exp1_1", "exp2", "exp3", "exp4", "exp5", "exp6", "exp7", "exp7_1"]
+        );
+        let exp4 = &records[4];
+        assert_eq!(exp4.get("core0_cycles"), Some("3"));
+        assert_eq!(exp4.get("core0_value"), Some("7"));
+        assert_eq!(exp4.get("sram4_contested"), Some("1"));
+
+        let records = parse_log(include_str!("../fixtures/dma_contention.log"));
+        assert_eq!(records.len(), 4);
+        assert!(records.iter().all(|r| r.fields.len() == 14));
+    }
+}
@@ -0,0 +1,303 @@
+//! Result tables built from the records, as markdown for the README and as CSV for plotting.
+//!
+//! The tables of `bus_behavior/README.md` that are generated live between two markers named
+//! after the table, everything between them is replaced by `update_readme()`:
+//!
+//! ```markdown
+//! <!-- report:experiments -->
+//! | experiment | proc0 | ... |
+//! <!-- /report:experiments -->
+//! ```
+
+use core::fmt;
+
+use crate::Record;
+
+/// The records of one table, one row per record and one column per key
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Table {
+    pub name: String,
+    /// Keys in the order they first appear in the records
+    pub columns: Vec<String>,
+    /// One value per column, empty when the record doesn't have the key
+    pub rows: Vec<Vec<String>>,
+}
+
+/// Groups the records by table, in the order the tables first appear
+pub fn tables(records: &[Record]) -> Vec<Table> {
+    let mut tables: Vec<Table> = Vec::new();
+    for record in records {
+        let index = match tables.iter().position(|t| t.name == record.table) {
+            Some(index) => index,
+            None => {
+                tables.push(Table {
+                    name: record.table.clone(),
+                    columns: Vec::new(),
+                    rows: Vec::new(),
+                });
+                tables.len() - 1
+            }
+        };
+        let table = &mut tables[index];
+        for (key, _) in &record.fields {
+            if !table.columns.contains(key) {
+                table.columns.push(key.clone());
+            }
+        }
+        table.rows.push(Vec::new());
+    }
+
+    // fill the rows once all the columns are known, a table can gain columns on later records
+    // (e.g. experiments counting different events)
+    for table in &mut tables {
+        let mut rows = records.iter().filter(|r| r.table == table.name);
+        for row in &mut table.rows {
+            let record = rows.next().unwrap();
+            *row = table
+                .columns
+                .iter()
+                .map(|column| record.get(column).unwrap_or_default().to_string())
+                .collect();
+        }
+    }
+    tables
+}
+
+impl Table {
+    /// Markdown table with the columns padded to the same width, like the tables of the README
+    pub fn to_markdown(&self) -> String {
+        let cells = |row: &[String]| -> Vec<String> {
+            row.iter().map(|cell| cell.replace('|', "\\|")).collect()
+        };
+        let header = cells(&self.columns);
+        let rows: Vec<Vec<String>> = self.rows.iter().map(|row| cells(row)).collect();
+        let widths: Vec<usize> = (0..header.len())
+            .map(|i| {
+                rows.iter()
+                    .map(|row| row[i].chars().count())
+                    .chain([header[i].chars().count(), 3])
+                    .max()
+                    .unwrap()
+            })
+            .collect();
+
+        let line = |row: &[String]| -> String {
+            let cells: Vec<String> = row
+                .iter()
+                .zip(&widths)
+                .map(|(cell, width)| format!("{cell:<width$}"))
+                .collect();
+            format!("| {} |\n", cells.join(" | "))
+        };
+        let mut out = line(&header);
+        let separator: Vec<String> = widths.iter().map(|width| "-".repeat(*width)).collect();
+        out.push_str(&line(&separator));
+        for row in &rows {
+            out.push_str(&line(row));
+        }
+        out
+    }
+
+    /// CSV with a header line, values are quoted when they contain a comma or a quote
+    pub fn to_csv(&self) -> String {
+        let line = |row: &[String]| -> String {
+            let cells: Vec<String> = row
+                .iter()
+                .map(|cell| {
+                    if cell.contains([',', '"', '\n']) {
+                        format!("\"{}\"", cell.replace('"', "\"\""))
+                    } else {
+                        cell.clone()
+                    }
+                })
+                .collect();
+            format!("{}\n", cells.join(","))
+        };
+        let mut out = line(&self.columns);
+        for row in &self.rows {
+            out.push_str(&line(row));
+        }
+        out
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum ReportError {
+    /// A begin marker without its end marker
+    Unterminated(String),
+    /// An end marker without its begin marker
+    UnexpectedEnd(String),
+}
+
+impl fmt::Display for ReportError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ReportError::Unterminated(table) => {
+                write!(
+                    f,
+                    "`<!-- report:{table} -->` has no `<!-- /report:{table} -->`"
+                )
+            }
+            ReportError::UnexpectedEnd(table) => {
+                write!(
+                    f,
+                    "`<!-- /report:{table} -->` has no `<!-- report:{table} -->`"
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for ReportError {}
+
+/// Replaces the content of every report marker of `readme` that has a table in `tables`. Returns
+/// the new README and the names of the tables that were updated, markers without a table are left
+/// as they are.
+pub fn update_readme(readme: &str, tables: &[Table]) -> Result<(String, Vec<String>), ReportError> {
+    let mut out = String::with_capacity(readme.len());
+    let mut updated = Vec::new();
+    let mut lines = readme.split_inclusive('\n');
+    while let Some(line) = lines.next() {
+        out.push_str(line);
+        if let Some(name) = marker(line, "/report:") {
+            return Err(ReportError::UnexpectedEnd(name.to_string()));
+        }
+        let Some(name) = marker(line, "report:") else {
+            continue;
+        };
+
+        // copy or replace everything up to the end marker
+        let table = tables.iter().find(|t| t.name == name);
+        let mut terminated = false;
+        for line in lines.by_ref() {
+            if marker(line, "/report:") == Some(name) {
+                if let Some(table) = table {
+                    out.push_str(&table.to_markdown());
+                    updated.push(name.to_string());
+                }
+                out.push_str(line);
+                terminated = true;
+                break;
+            }
+            if table.is_none() {
+                out.push_str(line);
+            }
+        }
+        if !terminated {
+            return Err(ReportError::Unterminated(name.to_string()));
+        }
+    }
+    Ok((out, updated))
+}
+
+/// Name of the table of a `<!-- {kind}name -->` marker line
+fn marker<'a>(line: &'a str, kind: &str) -> Option<&'a str> {
+    line.trim()
+        .strip_prefix("<!--")?
+        .strip_suffix("-->")?
+        .trim()
+        .strip_prefix(kind)
+}
+
+// tests
+
+#[cfg(test)]
+mod tests {
+    use crate::{parse_log, tables, update_readme, ReportError, Table};
+
+    fn table(log: &str) -> Table {
+        tables(&parse_log(log)).remove(0)
+    }
+
+    #[test]
+    fn test_missing_columns() {
+        let t = table("@t a=1 b=2\n@t a=3 c=4\n");
+        assert_eq!(t.columns, ["a", "b", "c"]);
+        assert_eq!(t.rows, [["1", "2", ""], ["3", "", "4"]]);
+    }
+
+    #[test]
+    fn test_tables_are_separated() {
+        let tables = tables(&parse_log("@a x=1\n@b y=2\n@a x=3\n"));
+        assert_eq!(tables.len(), 2);
+        assert_eq!(tables[0].rows, [["1"], ["3"]]);
+        assert_eq!(tables[1].rows, [["2"]]);
+    }
+
+    #[test]
+    fn test_markdown() {
+        let t = table("@t experiment=exp1 core0_cycles=3\n@t experiment=exp7_1 core0_cycles=201\n");
+        assert_eq!(
+            t.to_markdown(),
+            "| experiment | core0_cycles |\n\
+             | ---------- | ------------ |\n\
+             | exp1       | 3            |\n\
+             | exp7_1     | 201          |\n"
+        );
+    }
+
+    #[test]
+    fn test_csv() {
+        let mut t = table("@t a=1 b=2\n");
+        t.rows
+            .push(vec!["x,y".to_string(), "say \"hi\"".to_string()]);
+        assert_eq!(t.to_csv(), "a,b\n1,2\n\"x,y\",\"say \"\"hi\"\"\"\n");
+    }
+
+    #[test]
+    fn test_update_readme() {
+        let readme = "# results\n\
+                      <!-- report:t -->\n\
+                      old table\n\
+                      <!-- /report:t -->\n\
+                      <!-- report:other -->\n\
+                      kept\n\
+                      <!-- /report:other -->\n";
+        let (out, updated) = update_readme(readme, &[table("@t a=1\n")]).unwrap();
+        assert_eq!(updated, ["t"]);
+        assert_eq!(
+            out,
+            "# results\n\
+             <!-- report:t -->\n\
+             | a   |\n\
+             | --- |\n\
+             | 1   |\n\
+             <!-- /report:t -->\n\
+             <!-- report:other -->\n\
+             kept\n\
+             <!-- /report:other -->\n"
+        );
+        // regenerating gives the same README
+        let (again, _) = update_readme(&out, &[table("@t a=1\n")]).unwrap();
+        assert_eq!(again, out);
+    }
+
+    #[test]
+    fn test_bad_markers() {
+        assert_eq!(
+            update_readme("<!-- report:t -->\n| a |\n", &[]),
+            Err(ReportError::Unterminated("t".to_string()))
+        );
+        assert_eq!(
+            update_readme("<!-- /report:t -->\n", &[]),
+            Err(ReportError::UnexpectedEnd("t".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_recorded_experiments_log() {
+        let tables = tables(&parse_log(include_str!("../fixtures/experiments.log")));
+        assert_eq!(tables.len(), 1);
+        let t = &tables[0];
+        // exp1_1 counts sram2/sram3 events instead of sram4/rom, they are appended as columns
+        let column = |name: &str| t.columns.iter().position(|c| c == name).unwrap();
+        assert!(column("sram2_contested") > column("verdict"));
+        let exp1_1 = &t.rows[1];
+        assert_eq!(exp1_1[column("sram2_contested")], "0");
+        assert_eq!(exp1_1[column("sram4_contested")], "");
+        assert!(t.rows.iter().all(|row| row[column("verdict")] == "pass"));
+        let markdown = t.to_markdown();
+        assert_eq!(markdown.lines().count(), 2 + 9);
+        assert!(markdown.lines().nth(2).unwrap().starts_with("| exp1 "));
+    }
+}
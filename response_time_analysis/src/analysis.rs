@@ -0,0 +1,439 @@
+//! Fixed-priority response-time analysis for the two cores of the rp2040.
+//!
+//! Each core is analysed with the classic recurrence
+//!
+//! ```text
+//! w = C + B + sum_{j in hep(i)} ceil((w + J_j) / T_j) * C_j
+//! R = w + J
+//! ```
+//!
+//! where `hep(i)` are the other tasks on the same core with a priority higher than or equal to
+//! task `i`. The cores are coupled in two ways:
+//!
+//! - spinlocks: every critical section of a task may have to wait for one critical section of the
+//!   same spinlock executed by the other core. This spin time is added to the task's WCET `C`.
+//!   Since critical sections run with interrupts disabled, a lower priority task on the same core
+//!   that is spinning or holding a lock blocks the task for at most one critical section (`B`).
+//! - cross-core dependencies: a task triggered through the SIO FIFO is released `fifo_latency`
+//!   cycles after its predecessor completes. Its release jitter `J` is therefore the response
+//!   time of its predecessor plus the forwarding latency. Since that response time depends on
+//!   the jitter of the other tasks, the analysis iterates over both cores until the jitters
+//!   settle (holistic analysis).
+//!
+//! The recurrence is computed with 64-bit saturating arithmetic, a task whose interference
+//! doesn't fit is reported as unschedulable since it exceeds any 32-bit deadline.
+
+use core::fmt;
+
+use crate::taskset::{LockAccess, TaskSet};
+
+/// Upper bound for the holistic iteration, the jitters increase monotonically so this is only
+/// reached by task sets that are unschedulable anyway.
+const MAX_HOLISTIC_ITERATIONS: usize = 1000;
+
+/// The jitters didn't settle within the iteration bound, the response times of the last iteration
+/// are not bounds.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NotConverged {
+    pub iterations: usize,
+}
+
+impl fmt::Display for NotConverged {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "the holistic analysis did not converge after {} iterations",
+            self.iterations
+        )
+    }
+}
+
+impl std::error::Error for NotConverged {}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TaskResult {
+    pub name: String,
+    pub core: u8,
+    pub priority: u8,
+    pub wcet: u32,
+    /// Worst case time spent spinning on spinlocks held by the other core
+    pub spin: u64,
+    /// Worst case blocking by a lower priority task on the same core
+    pub blocking: u64,
+    /// Release jitter inherited from the task that triggers this one
+    pub jitter: u64,
+    /// Worst case response time, `None` if it exceeds the deadline
+    pub response_time: Option<u32>,
+    pub deadline: u32,
+}
+
+impl TaskResult {
+    pub fn schedulable(&self) -> bool {
+        self.response_time.is_some()
+    }
+}
+
+/// Computes the worst-case response time of every task, in the order they were declared.
+pub fn analyse(set: &TaskSet) -> Result<Vec<TaskResult>, NotConverged> {
+    analyse_bounded(set, MAX_HOLISTIC_ITERATIONS)
+}
+
+fn analyse_bounded(set: &TaskSet, max_iterations: usize) -> Result<Vec<TaskResult>, NotConverged> {
+    let tasks = &set.tasks;
+    let spin: Vec<u64> = tasks
+        .iter()
+        .map(|t| t.locks.iter().map(|l| remote_spin(set, t.core, l)).sum())
+        .collect();
+    let cost: Vec<u64> = tasks
+        .iter()
+        .zip(&spin)
+        .map(|(t, s)| u64::from(t.wcet) + s)
+        .collect();
+    let blocking: Vec<u64> = tasks
+        .iter()
+        .map(|t| {
+            tasks
+                .iter()
+                .filter(|lp| lp.core == t.core && lp.priority < t.priority)
+                .flat_map(|lp| {
+                    lp.locks
+                        .iter()
+                        .map(move |l| u64::from(l.length) + remote_spin(set, lp.core, l))
+                })
+                .max()
+                .unwrap_or(0)
+        })
+        .collect();
+
+    let mut jitter = vec![0u64; tasks.len()];
+    let mut response = vec![None; tasks.len()];
+    let mut converged = false;
+    for _ in 0..max_iterations {
+        for (i, task) in tasks.iter().enumerate() {
+            response[i] = if task.triggered_by.is_some_and(|p| response[p].is_none()) {
+                // the predecessor can miss its deadline, so no bound can be given for this task
+                None
+            } else {
+                response_time(set, i, &cost, &jitter, blocking[i])
+            };
+        }
+
+        let mut settled = true;
+        for (i, task) in tasks.iter().enumerate() {
+            if let Some(Some(pred_response)) = task.triggered_by.map(|p| response[p]) {
+                let new_jitter = u64::from(pred_response) + u64::from(set.fifo_latency);
+                if new_jitter != jitter[i] {
+                    jitter[i] = new_jitter;
+                    settled = false;
+                }
+            }
+        }
+        if settled {
+            converged = true;
+            break;
+        }
+    }
+    if !converged {
+        return Err(NotConverged {
+            iterations: max_iterations,
+        });
+    }
+
+    Ok(tasks
+        .iter()
+        .enumerate()
+        .map(|(i, t)| TaskResult {
+            name: t.name.clone(),
+            core: t.core,
+            priority: t.priority,
+            wcet: t.wcet,
+            spin: spin[i],
+            blocking: blocking[i],
+            jitter: jitter[i],
+            response_time: response[i],
+            deadline: t.deadline,
+        })
+        .collect())
+}
+
+/// Longest critical section on the other core that uses the same spinlock as `access`.
+fn remote_spin(set: &TaskSet, core: u8, access: &LockAccess) -> u64 {
+    set.tasks
+        .iter()
+        .filter(|t| t.core != core)
+        .flat_map(|t| t.locks.iter())
+        .filter(|l| l.spinlock == access.spinlock)
+        .map(|l| u64::from(l.length))
+        .max()
+        .unwrap_or(0)
+}
+
+/// Solves the response-time recurrence of task `i`, giving up as soon as the deadline is exceeded.
+fn response_time(
+    set: &TaskSet,
+    i: usize,
+    cost: &[u64],
+    jitter: &[u64],
+    blocking: u64,
+) -> Option<u32> {
+    let task = &set.tasks[i];
+    let interferers: Vec<usize> = set
+        .tasks
+        .iter()
+        .enumerate()
+        .filter(|(j, t)| *j != i && t.core == task.core && t.priority >= task.priority)
+        .map(|(j, _)| j)
+        .collect();
+
+    let mut w = cost[i] + blocking;
+    loop {
+        if w.saturating_add(jitter[i]) > u64::from(task.deadline) {
+            return None;
+        }
+        let next = interferers
+            .iter()
+            .map(|&j| {
+                (w + jitter[j])
+                    .div_ceil(u64::from(set.tasks[j].period))
+                    .saturating_mul(cost[j])
+            })
+            .fold(cost[i] + blocking, u64::saturating_add);
+        if next == w {
+            // bounded by the deadline checked above
+            return Some((w + jitter[i]) as u32);
+        }
+        w = next;
+    }
+}
+
+// tests
+
+#[cfg(test)]
+mod tests {
+    use super::analyse_bounded;
+    use crate::{analyse, NotConverged, TaskSet, TaskSetError};
+
+    fn response_times(src: &str) -> Vec<Option<u32>> {
+        let set = TaskSet::from_toml(src).unwrap();
+        analyse(&set)
+            .unwrap()
+            .into_iter()
+            .map(|r| r.response_time)
+            .collect()
+    }
+
+    #[test]
+    fn test_single_core_textbook() {
+        // three tasks on one core, the lowest priority one completes exactly at its deadline
+        let src = r#"
+            [[task]]
+            name = "t1"
+            core = 0
+            priority = 3
+            wcet = 1
+            period = 4
+            [[task]]
+            name = "t2"
+            core = 0
+            priority = 2
+            wcet = 2
+            period = 6
+            [[task]]
+            name = "t3"
+            core = 0
+            priority = 1
+            wcet = 3
+            period = 10
+        "#;
+        assert_eq!(response_times(src), vec![Some(1), Some(3), Some(10)]);
+    }
+
+    #[test]
+    fn test_cores_are_independent() {
+        let src = r#"
+            [[task]]
+            name = "a"
+            core = 0
+            priority = 2
+            wcet = 5
+            period = 10
+            [[task]]
+            name = "b"
+            core = 1
+            priority = 1
+            wcet = 8
+            period = 10
+        "#;
+        assert_eq!(response_times(src), vec![Some(5), Some(8)]);
+    }
+
+    #[test]
+    fn test_spinlock_blocking() {
+        // `low` holds spinlock 0 for 4 cycles, `remote` holds it for 6 cycles on the other core.
+        // `high` does not use the lock but can be blocked by `low` spinning and then holding it.
+        let src = r#"
+            [[task]]
+            name = "high"
+            core = 0
+            priority = 2
+            wcet = 10
+            period = 100
+            [[task]]
+            name = "low"
+            core = 0
+            priority = 1
+            wcet = 20
+            period = 100
+            locks = [{ spinlock = 0, length = 4 }]
+            [[task]]
+            name = "remote"
+            core = 1
+            priority = 1
+            wcet = 30
+            period = 100
+            locks = [{ spinlock = 0, length = 6 }]
+        "#;
+        let set = TaskSet::from_toml(src).unwrap();
+        let results = analyse(&set).unwrap();
+        assert_eq!(results[0].blocking, 4 + 6);
+        assert_eq!(results[0].response_time, Some(10 + 4 + 6));
+        assert_eq!(results[1].spin, 6);
+        assert_eq!(results[1].response_time, Some(20 + 6 + 10));
+        assert_eq!(results[2].spin, 4);
+        assert_eq!(results[2].response_time, Some(30 + 4));
+    }
+
+    #[test]
+    fn test_fifo_forwarding_jitter() {
+        let src = r#"
+            fifo_latency = 3
+            [[task]]
+            name = "producer"
+            core = 0
+            priority = 1
+            wcet = 10
+            period = 100
+            [[task]]
+            name = "consumer"
+            core = 1
+            priority = 1
+            wcet = 20
+            triggered_by = "producer"
+            [[task]]
+            name = "other"
+            core = 1
+            priority = 2
+            wcet = 5
+            period = 50
+        "#;
+        let set = TaskSet::from_toml(src).unwrap();
+        let results = analyse(&set).unwrap();
+        assert_eq!(results[1].jitter, 10 + 3);
+        assert_eq!(results[1].response_time, Some(10 + 3 + 20 + 5));
+    }
+
+    #[test]
+    fn test_not_converged() {
+        // the jitter of `consumer` is only known after the first iteration
+        let src = r#"
+            [[task]]
+            name = "producer"
+            core = 0
+            priority = 1
+            wcet = 10
+            period = 100
+            [[task]]
+            name = "consumer"
+            core = 1
+            priority = 1
+            wcet = 20
+            triggered_by = "producer"
+        "#;
+        let set = TaskSet::from_toml(src).unwrap();
+        assert_eq!(
+            analyse_bounded(&set, 1),
+            Err(NotConverged { iterations: 1 })
+        );
+        assert!(analyse_bounded(&set, 2).is_ok());
+    }
+
+    #[test]
+    fn test_unschedulable() {
+        let src = r#"
+            [[task]]
+            name = "hog"
+            core = 0
+            priority = 2
+            wcet = 6
+            period = 10
+            [[task]]
+            name = "starved"
+            core = 0
+            priority = 1
+            wcet = 5
+            period = 10
+            [[task]]
+            name = "downstream"
+            core = 1
+            priority = 1
+            wcet = 1
+            triggered_by = "starved"
+        "#;
+        assert_eq!(response_times(src), vec![Some(6), None, None]);
+    }
+
+    #[test]
+    fn test_interference_overflow() {
+        // the interference of `hog` exceeds 32 bits before `low` can complete
+        let src = r#"
+            [[task]]
+            name = "hog"
+            core = 0
+            priority = 2
+            wcet = 4000000000
+            period = 4000000000
+            [[task]]
+            name = "low"
+            core = 0
+            priority = 1
+            wcet = 1
+            period = 4294967295
+        "#;
+        assert_eq!(response_times(src), vec![Some(4_000_000_000), None]);
+    }
+
+    #[test]
+    fn test_zero_period() {
+        let src = r#"
+            [[task]]
+            name = "a"
+            core = 0
+            priority = 1
+            wcet = 1
+            period = 0
+        "#;
+        assert!(matches!(
+            TaskSet::from_toml(src),
+            Err(TaskSetError::ZeroPeriod(task)) if task == "a"
+        ));
+    }
+
+    #[test]
+    fn test_dependency_cycle() {
+        let src = r#"
+            [[task]]
+            name = "a"
+            core = 0
+            priority = 1
+            wcet = 1
+            triggered_by = "b"
+            [[task]]
+            name = "b"
+            core = 1
+            priority = 1
+            wcet = 1
+            triggered_by = "a"
+        "#;
+        assert!(TaskSet::from_toml(src).is_err());
+    }
+}
@@ -0,0 +1,7 @@
+//! Worst-case response-time analysis of a two-core task set, to check deadlines before flashing.
+
+mod analysis;
+mod taskset;
+
+pub use analysis::{analyse, NotConverged, TaskResult};
+pub use taskset::{LockAccess, Task, TaskSet, TaskSetError};
@@ -0,0 +1,73 @@
+use std::{env, fs, process::ExitCode};
+
+use response_time_analysis::{analyse, TaskSet};
+
+fn main() -> ExitCode {
+    let Some(path) = env::args().nth(1) else {
+        eprintln!("usage: response_time_analysis <taskset.toml>");
+        return ExitCode::FAILURE;
+    };
+    let src = match fs::read_to_string(&path) {
+        Ok(src) => src,
+        Err(e) => {
+            eprintln!("could not read {path}: {e}");
+            return ExitCode::FAILURE;
+        }
+    };
+    let set = match TaskSet::from_toml(&src) {
+        Ok(set) => set,
+        Err(e) => {
+            eprintln!("{path}: {e}");
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let mut results = match analyse(&set) {
+        Ok(results) => results,
+        Err(e) => {
+            eprintln!("{path}: {e}");
+            return ExitCode::FAILURE;
+        }
+    };
+    results.sort_by_key(|r| (r.core, std::cmp::Reverse(r.priority)));
+
+    println!(
+        "{:<4} {:<4} {:<20} {:>8} {:>8} {:>8} {:>8} {:>10} {:>10}  verdict",
+        "core", "prio", "task", "wcet", "spin", "block", "jitter", "response", "deadline"
+    );
+    for r in &results {
+        let response = r
+            .response_time
+            .map_or_else(|| format!("> {}", r.deadline), |rt| rt.to_string());
+        println!(
+            "{:<4} {:<4} {:<20} {:>8} {:>8} {:>8} {:>8} {:>10} {:>10}  {}",
+            r.core,
+            r.priority,
+            r.name,
+            r.wcet,
+            r.spin,
+            r.blocking,
+            r.jitter,
+            response,
+            r.deadline,
+            if r.schedulable() {
+                "ok"
+            } else {
+                "UNSCHEDULABLE"
+            }
+        );
+    }
+
+    let unschedulable: Vec<&str> = results
+        .iter()
+        .filter(|r| !r.schedulable())
+        .map(|r| r.name.as_str())
+        .collect();
+    if unschedulable.is_empty() {
+        println!("\nall {} tasks meet their deadlines", results.len());
+        ExitCode::SUCCESS
+    } else {
+        println!("\nunschedulable tasks: {}", unschedulable.join(", "));
+        ExitCode::FAILURE
+    }
+}
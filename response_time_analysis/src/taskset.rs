@@ -0,0 +1,263 @@
+//! Task-set description as read from a `.toml` file.
+//!
+//! All times are expressed in system clock cycles, so WCETs can be taken directly from the
+//! SysTick measurements made in `bus_behavior`.
+//!
+//! ```toml
+//! # cycles between `fifo_wr` on one core and the forwarded interrupt entering on the other core
+//! fifo_latency = 40
+//!
+//! [[task]]
+//! name = "sample"
+//! core = 0
+//! priority = 2
+//! wcet = 1200
+//! period = 125000
+//! locks = [{ spinlock = 0, length = 60 }]
+//!
+//! [[task]]
+//! name = "filter"
+//! core = 1
+//! priority = 1
+//! wcet = 8000
+//! triggered_by = "sample" # released through the SIO FIFO when `sample` completes
+//! ```
+
+use core::fmt;
+
+use serde::Deserialize;
+
+/// Number of cores on the rp2040
+pub const CORES: u8 = 2;
+/// Number of hardware spinlocks in the SIO block
+pub const SPINLOCKS: u8 = 32;
+
+#[derive(Debug)]
+pub struct TaskSet {
+    /// Cycles from writing the SIO FIFO on the sending core until the forwarded interrupt handler
+    /// starts executing on the receiving core.
+    pub fifo_latency: u32,
+    pub tasks: Vec<Task>,
+}
+
+#[derive(Debug)]
+pub struct Task {
+    pub name: String,
+    pub core: u8,
+    /// Higher value means higher urgency (same convention as RTIC).
+    pub priority: u8,
+    /// Worst case execution time in isolation, including the time spent inside critical sections
+    /// but excluding the time spent spinning on a lock held by the other core.
+    pub wcet: u32,
+    pub period: u32,
+    /// Relative to the release of the first task of the chain this task belongs to.
+    pub deadline: u32,
+    /// Index of the task on the other core whose completion releases this task.
+    pub triggered_by: Option<usize>,
+    pub locks: Vec<LockAccess>,
+}
+
+/// One critical section executed by a task while holding a hardware spinlock.
+/// Critical sections are assumed to run with interrupts disabled, i.e. non-preemptively.
+#[derive(Debug, Deserialize, Clone, Copy)]
+#[serde(deny_unknown_fields)]
+pub struct LockAccess {
+    pub spinlock: u8,
+    /// Length of the critical section in cycles
+    pub length: u32,
+}
+
+#[derive(Debug)]
+pub enum TaskSetError {
+    Parse(toml::de::Error),
+    DuplicateName(String),
+    InvalidCore { task: String, core: u8 },
+    InvalidSpinlock { task: String, spinlock: u8 },
+    MissingPeriod(String),
+    ZeroPeriod(String),
+    UnknownPredecessor { task: String, predecessor: String },
+    SameCorePredecessor { task: String, predecessor: String },
+    PeriodMismatch { task: String, head: String },
+    DependencyCycle(String),
+    DeadlineAfterPeriod(String),
+}
+
+impl fmt::Display for TaskSetError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TaskSetError::Parse(e) => write!(f, "invalid task-set description: {e}"),
+            TaskSetError::DuplicateName(task) => write!(f, "task `{task}` is defined twice"),
+            TaskSetError::InvalidCore { task, core } => {
+                write!(
+                    f,
+                    "task `{task}` is mapped to core {core}, but the rp2040 only has cores 0 and 1"
+                )
+            }
+            TaskSetError::InvalidSpinlock { task, spinlock } => {
+                write!(
+                    f,
+                    "task `{task}` uses spinlock {spinlock}, valid spinlocks are 0..{SPINLOCKS}"
+                )
+            }
+            TaskSetError::MissingPeriod(task) => {
+                write!(
+                    f,
+                    "task `{task}` has neither a `period` nor a `triggered_by`"
+                )
+            }
+            TaskSetError::ZeroPeriod(task) => write!(f, "task `{task}` has a period of 0 cycles"),
+            TaskSetError::UnknownPredecessor { task, predecessor } => {
+                write!(
+                    f,
+                    "task `{task}` is triggered by unknown task `{predecessor}`"
+                )
+            }
+            TaskSetError::SameCorePredecessor { task, predecessor } => write!(
+                f,
+                "task `{task}` is triggered by `{predecessor}` which runs on the same core, \
+                 only cross-core dependencies are supported"
+            ),
+            TaskSetError::PeriodMismatch { task, head } => write!(
+                f,
+                "task `{task}` declares a period different from the one of `{head}`, \
+                 which starts its dependency chain"
+            ),
+            TaskSetError::DependencyCycle(task) => {
+                write!(f, "task `{task}` is part of a dependency cycle")
+            }
+            TaskSetError::DeadlineAfterPeriod(task) => {
+                write!(f, "task `{task}` has a deadline larger than its period")
+            }
+        }
+    }
+}
+
+impl std::error::Error for TaskSetError {}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawTaskSet {
+    #[serde(default)]
+    fifo_latency: u32,
+    #[serde(default, rename = "task")]
+    tasks: Vec<RawTask>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawTask {
+    name: String,
+    core: u8,
+    priority: u8,
+    wcet: u32,
+    period: Option<u32>,
+    deadline: Option<u32>,
+    triggered_by: Option<String>,
+    #[serde(default)]
+    locks: Vec<LockAccess>,
+}
+
+impl TaskSet {
+    /// Parses and validates a task-set description. Tasks released by a cross-core dependency
+    /// inherit the period of the task that triggers them.
+    pub fn from_toml(src: &str) -> Result<Self, TaskSetError> {
+        let raw: RawTaskSet = toml::from_str(src).map_err(TaskSetError::Parse)?;
+
+        for (i, task) in raw.tasks.iter().enumerate() {
+            if raw.tasks[..i].iter().any(|t| t.name == task.name) {
+                return Err(TaskSetError::DuplicateName(task.name.clone()));
+            }
+            if task.core >= CORES {
+                return Err(TaskSetError::InvalidCore {
+                    task: task.name.clone(),
+                    core: task.core,
+                });
+            }
+            if task.period == Some(0) {
+                return Err(TaskSetError::ZeroPeriod(task.name.clone()));
+            }
+            if let Some(lock) = task.locks.iter().find(|l| l.spinlock >= SPINLOCKS) {
+                return Err(TaskSetError::InvalidSpinlock {
+                    task: task.name.clone(),
+                    spinlock: lock.spinlock,
+                });
+            }
+        }
+
+        // resolve predecessors by name
+        let mut predecessors = Vec::with_capacity(raw.tasks.len());
+        for task in &raw.tasks {
+            let pred = match &task.triggered_by {
+                None => None,
+                Some(name) => {
+                    let idx = raw
+                        .tasks
+                        .iter()
+                        .position(|t| &t.name == name)
+                        .ok_or_else(|| TaskSetError::UnknownPredecessor {
+                            task: task.name.clone(),
+                            predecessor: name.clone(),
+                        })?;
+                    if raw.tasks[idx].core == task.core {
+                        return Err(TaskSetError::SameCorePredecessor {
+                            task: task.name.clone(),
+                            predecessor: name.clone(),
+                        });
+                    }
+                    Some(idx)
+                }
+            };
+            predecessors.push(pred);
+        }
+
+        // follow each chain back to its head to find the period and detect cycles
+        let names: Vec<String> = raw.tasks.iter().map(|t| t.name.clone()).collect();
+        let mut heads = Vec::with_capacity(raw.tasks.len());
+        for (i, name) in names.iter().enumerate() {
+            let mut head = i;
+            let mut steps = 0;
+            while let Some(pred) = predecessors[head] {
+                head = pred;
+                steps += 1;
+                if steps > predecessors.len() {
+                    return Err(TaskSetError::DependencyCycle(name.clone()));
+                }
+            }
+            heads.push(head);
+        }
+        let head_periods: Vec<Option<u32>> = raw.tasks.iter().map(|t| t.period).collect();
+
+        let mut tasks = Vec::with_capacity(raw.tasks.len());
+        for (i, task) in raw.tasks.into_iter().enumerate() {
+            let period = match (task.period, head_periods[heads[i]]) {
+                (_, None) => return Err(TaskSetError::MissingPeriod(names[heads[i]].clone())),
+                (Some(own), Some(inherited)) if own != inherited => {
+                    return Err(TaskSetError::PeriodMismatch {
+                        task: task.name,
+                        head: names[heads[i]].clone(),
+                    })
+                }
+                (_, Some(period)) => period,
+            };
+            let deadline = task.deadline.unwrap_or(period);
+            if deadline > period {
+                return Err(TaskSetError::DeadlineAfterPeriod(task.name));
+            }
+            tasks.push(Task {
+                name: task.name,
+                core: task.core,
+                priority: task.priority,
+                wcet: task.wcet,
+                period,
+                deadline,
+                triggered_by: predecessors[i],
+                locks: task.locks,
+            });
+        }
+
+        Ok(TaskSet {
+            fifo_latency: raw.fifo_latency,
+            tasks,
+        })
+    }
+}